@@ -0,0 +1,77 @@
+//! Benchmarks of processing content and files, run with `cargo bench`.
+
+use std::fs;
+use std::hint::black_box;
+use std::path::PathBuf;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use regop::{FileOptions, Processor, Regop};
+
+/// A processor bumping the patch of every version.
+fn processor(lines: bool) -> Processor {
+    Regop::builder()
+        .capture(r#"version = "\d+\.\d+\.(?<patch>\d+)""#)
+        .capture(r"(?<name>[a-z]+)_\d+ =")
+        .op("<patch>:inc")
+        .op("<name>:upper")
+        .lines(lines)
+        .build()
+        .expect("valid captures and operators")
+}
+
+/// Content with a version on every other line.
+fn content(lines: usize) -> String {
+    (0..lines)
+        .map(|i| {
+            if i % 2 == 0 {
+                format!("dep_{i} = {{ version = \"1.2.{i}\" }}\n")
+            } else {
+                format!("# comment {i}\n")
+            }
+        })
+        .collect()
+}
+
+fn bench_content(c: &mut Criterion) {
+    let content = content(10_000);
+    for (name, lines) in [("process", false), ("process lines", true)] {
+        let processor = processor(lines);
+        c.bench_function(name, |b| {
+            b.iter(|| {
+                processor
+                    .process(black_box(&content))
+                    .expect("processing succeeds")
+            });
+        });
+    }
+    let processor = processor(false);
+    c.bench_function("process unchanged", |b| {
+        let content = "# nothing to see here\n".repeat(10_000);
+        b.iter(|| {
+            processor
+                .process(black_box(&content))
+                .expect("processing succeeds")
+        });
+    });
+}
+
+fn bench_files(c: &mut Criterion) {
+    let dir = std::env::temp_dir().join(format!("regop-bench-{}", std::process::id()));
+    fs::create_dir_all(&dir).expect("temporary directory is created");
+    let paths = (0..500)
+        .map(|i| {
+            let path = dir.join(format!("{i}.toml"));
+            fs::write(&path, content(100)).expect("file is written");
+            path
+        })
+        .collect::<Vec<PathBuf>>();
+
+    let processor = processor(false);
+    c.bench_function("process files", |b| {
+        b.iter(|| processor.process_files(black_box(&paths), &FileOptions::default()));
+    });
+    fs::remove_dir_all(dir).expect("temporary directory is removed");
+}
+
+criterion_group!(benches, bench_content, bench_files);
+criterion_main!(benches);
@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use regop::{Capture, Condition, Expectation, Fragment, Operator, Selector, TypeCheck};
+
+fuzz_target!(|data: &str| {
+    let _ = data.parse::<Capture>();
+    let _ = data.parse::<Operator>();
+    let _ = data.parse::<Selector>();
+    let _ = data.parse::<Condition>();
+    let _ = data.parse::<Expectation>();
+    let _ = data.parse::<TypeCheck>();
+    let _ = data.parse::<Fragment>();
+    let _ = regop::fixed_pattern(data);
+});
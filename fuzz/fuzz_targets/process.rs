@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use regop::{Options, Processor};
+
+// The first line is a capture, the second an operator and the rest the content
+fuzz_target!(|data: &str| {
+    let mut parts = data.splitn(3, '\n');
+    let (Some(capture), Some(operator), Some(content)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return;
+    };
+    let (Ok(capture), Ok(operator)) = (capture.parse(), operator.parse()) else {
+        return;
+    };
+    let processor = Processor::new(Options::default(), vec![capture], vec![operator]);
+    let _ = processor.process(content);
+    let _ = processor.report(content);
+});
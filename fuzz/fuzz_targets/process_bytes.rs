@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use regop::Regop;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(processor) = Regop::builder()
+        .capture(r"(?<num>-?\d+)")
+        .capture(r"(?<word>\w+)")
+        .op("<num>:inc")
+        .op("<word>:upper")
+        .build()
+    else {
+        return;
+    };
+    let _ = processor.process_bytes(data);
+    let _ = processor.process_stream(data, std::io::sink());
+});
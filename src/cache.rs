@@ -0,0 +1,192 @@
+//! Cache of files known to be left unchanged, for `--cache`.
+//!
+//! The cache remembers the modification time, length and a hash of the
+//! content of every file processing left unchanged, along with a key for the
+//! regexes, operators and options it was processed with. Files whose stamp
+//! or content match are skipped on later runs with the same key. Any other
+//! key starts over with an empty cache.
+
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use anyhow::Context;
+use regop::Processor;
+use serde::{Deserialize, Serialize};
+
+use crate::Stamp;
+
+/// Files known to be left unchanged by processing with the same key.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Cache {
+    /// Hash of the version of regop and everything processing depends on
+    key: u64,
+    /// Files left unchanged, by path
+    files: HashMap<String, Entry>,
+}
+
+/// A file left unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    /// Modification time as seconds and nanoseconds since the epoch, and
+    /// length
+    stamp: Option<(u64, u32, u64)>,
+    /// Hash of the content
+    hash: u64,
+}
+
+impl Cache {
+    /// Load the cache for the processor, empty if the file doesn't exist, is
+    /// not a valid cache or was written for other regexes, operators or
+    /// options.
+    pub fn load(path: &Path, processor: &Processor) -> Self {
+        let key = key(processor);
+        let cache = fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Self>(&content).ok());
+        match cache {
+            Some(cache) if cache.key == key => cache,
+            _ => Self {
+                key,
+                ..Self::default()
+            },
+        }
+    }
+
+    /// Write the cache.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)
+            .context(format!("unable to write cache '{}'", path.display()))
+    }
+
+    /// Whether a file is known to be left unchanged, by its current stamp.
+    pub fn fresh(&self, file: &str) -> bool {
+        let stamp = fs::metadata(file).ok().as_ref().and_then(crate::stamp);
+        stamp.is_some_and(|stamp| {
+            self.files
+                .get(file)
+                .is_some_and(|entry| entry.stamp == Some(encode(stamp)))
+        })
+    }
+
+    /// Whether a file is known to be left unchanged, by its content.
+    pub fn known(&self, file: &str, content: &str) -> bool {
+        self.files
+            .get(file)
+            .is_some_and(|entry| entry.hash == hash(content))
+    }
+
+    /// Remember whether processing a file left it unchanged.
+    pub fn record(&mut self, file: &str, stamp: Option<Stamp>, content: &str, unchanged: bool) {
+        if file == "-" {
+            return;
+        }
+        if unchanged {
+            let entry = Entry {
+                stamp: stamp.map(encode),
+                hash: hash(content),
+            };
+            self.files.insert(file.to_string(), entry);
+        } else {
+            self.files.remove(file);
+        }
+    }
+}
+
+/// The key of a processor, which changes with regop, the regexes, operators
+/// or options.
+fn key(processor: &Processor) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    for capture in processor.regex() {
+        format!("{:?}", capture.regex).hash(&mut hasher);
+    }
+    format!("{:?}", processor.ops()).hash(&mut hasher);
+    format!("{:?}", processor.options()).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hash of the content of a file.
+fn hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A stamp as seconds and nanoseconds since the epoch, and length.
+fn encode((mtime, len): Stamp) -> (u64, u32, u64) {
+    let since = mtime.duration_since(UNIX_EPOCH).unwrap_or_default();
+    (since.as_secs(), since.subsec_nanos(), len)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use regop::Regop;
+
+    use super::*;
+
+    fn processor(op: &str) -> Processor {
+        Regop::builder()
+            .capture(r"v = (?<v>\d+)")
+            .op(op)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_record() {
+        let mut cache = Cache::default();
+        cache.record("a.txt", None, "v = 1", true);
+        assert!(cache.known("a.txt", "v = 1"));
+        assert!(!cache.known("a.txt", "v = 2"));
+        assert!(!cache.known("b.txt", "v = 1"));
+
+        cache.record("a.txt", None, "v = 1", false);
+        assert!(!cache.known("a.txt", "v = 1"));
+
+        cache.record("-", None, "v = 1", true);
+        assert!(!cache.known("-", "v = 1"));
+    }
+
+    #[test]
+    fn test_fresh() {
+        let dir = std::env::temp_dir().join("regop-cache-fresh");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.txt");
+        fs::write(&file, "v = 1").unwrap();
+        let stamp = fs::metadata(&file).ok().as_ref().and_then(crate::stamp);
+        let file = file.to_str().unwrap();
+
+        let mut cache = Cache::default();
+        assert!(!cache.fresh(file));
+        cache.record(file, stamp, "v = 1", true);
+        assert!(cache.fresh(file));
+
+        let mtime = SystemTime::now() + Duration::from_mins(1);
+        fs::File::options()
+            .write(true)
+            .open(file)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        assert!(!cache.fresh(file));
+    }
+
+    #[test]
+    fn test_load() {
+        let path = std::env::temp_dir().join("regop-cache.json");
+        let mut cache = Cache::load(&path, &processor("<v>:inc"));
+        cache.record("a.txt", None, "v = 1", true);
+        cache.save(&path).unwrap();
+
+        assert!(Cache::load(&path, &processor("<v>:inc")).known("a.txt", "v = 1"));
+        assert!(!Cache::load(&path, &processor("<v>:dec")).known("a.txt", "v = 1"));
+
+        fs::write(&path, "not a cache").unwrap();
+        assert!(!Cache::load(&path, &processor("<v>:inc")).known("a.txt", "v = 1"));
+    }
+}
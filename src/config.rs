@@ -0,0 +1,128 @@
+//! Configuration with reusable tasks and profiles.
+//!
+//! Tasks are defined in a `regop.toml` in the current directory, and profiles
+//! in the user config `~/.config/regop/config.toml`. Both are tables of
+//! command line flags, named by their long form, along with a list of `files`
+//! globs:
+//!
+//! ```toml
+//! [tasks.bump-deps]
+//! regex = ['"(?<major>\d+)\.\d+\.\d+"']
+//! op = ['<major>:inc']
+//! files = ['*Cargo.toml']
+//! lines = true
+//! ```
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, anyhow, bail};
+use serde::Deserialize;
+
+use crate::walk::walk;
+
+/// Name of the project configuration file.
+pub const PROJECT_CONFIG: &str = "regop.toml";
+
+/// Location of the user configuration file, in `$XDG_CONFIG_HOME` if set or
+/// `~/.config` otherwise.
+pub fn user_config() -> anyhow::Result<PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .ok_or_else(|| anyhow!("unable to find the user config directory"))?;
+    Ok(dir.join("regop").join("config.toml"))
+}
+
+/// A configuration file with named tasks and profiles.
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    /// Tasks by name
+    #[serde(default)]
+    pub tasks: BTreeMap<String, toml::Table>,
+    /// Profiles by name
+    #[serde(default)]
+    pub profiles: BTreeMap<String, toml::Table>,
+}
+
+impl Config {
+    /// Load a configuration file.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)
+            .context(format!("unable to read config '{}'", path.display()))?;
+        toml::from_str(&content).context(format!("'{}' not a valid config", path.display()))
+    }
+
+    /// Look up a task by name.
+    pub fn task(&self, name: &str) -> anyhow::Result<&toml::Table> {
+        lookup(&self.tasks, "task", name)
+    }
+
+    /// Look up a profile by name.
+    pub fn profile(&self, name: &str) -> anyhow::Result<&toml::Table> {
+        lookup(&self.profiles, "profile", name)
+    }
+}
+
+/// Look up an entry by name, listing the available ones if it is missing.
+fn lookup<'a>(
+    entries: &'a BTreeMap<String, toml::Table>,
+    kind: &str,
+    name: &str,
+) -> anyhow::Result<&'a toml::Table> {
+    entries.get(name).ok_or_else(|| {
+        let names = entries.keys().cloned().collect::<Vec<_>>().join(", ");
+        anyhow!(format!(
+            "{kind} '{name}' not found, available {kind}s: {names}"
+        ))
+    })
+}
+
+/// Turn a table of flags into command line arguments, along with the file
+/// globs found under `files`. Flags in `given` are left out, so the values
+/// given for them replace the ones in the table rather than add to them.
+///
+/// Values are joined to their flags with `=`, so values starting with `-`
+/// aren't taken for flags.
+pub fn args(
+    table: &toml::Table,
+    given: &HashSet<String>,
+) -> anyhow::Result<(Vec<String>, Vec<String>)> {
+    let mut args = Vec::new();
+    let mut files = Vec::new();
+    for (key, value) in table {
+        if given.contains(key) {
+            continue;
+        }
+        let values = match value {
+            toml::Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                toml::Value::Boolean(false) => continue,
+                toml::Value::Boolean(true) => None,
+                toml::Value::String(s) => Some(s.clone()),
+                toml::Value::Integer(i) => Some(i.to_string()),
+                value => bail!(format!("'{key}' has unsupported value '{value}'")),
+            };
+            if key == "files" {
+                files.push(value.ok_or_else(|| anyhow!("'files' must be a list of globs"))?);
+            } else {
+                args.push(value.map_or_else(|| format!("--{key}"), |v| format!("--{key}={v}")));
+            }
+        }
+    }
+    Ok((args, files))
+}
+
+/// Find all files below the current directory matching any of the globs.
+///
+/// Paths are matched relative to the current directory, where `*` also
+/// matches `/`. Hidden and ignored files are skipped, see [`walk`].
+pub fn expand_globs(globs: &[String], no_ignore: bool) -> anyhow::Result<Vec<String>> {
+    let mut files = walk(".", no_ignore)?;
+    files.retain(|file| globs.iter().any(|glob| regop::glob_match(glob, file)));
+    Ok(files)
+}
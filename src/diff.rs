@@ -1,12 +1,186 @@
 //! Visual diff display for file changes.
 //!
 //! This module provides a colored diff output similar to git diff,
-//! showing the changes that would be made to files.
+//! showing the changes that would be made to files, as well as plain
+//! unified diffs that can be applied with `patch` or `git apply`.
 
 use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::OnceLock;
 
-use console::{Style, style};
+use anyhow::bail;
+use console::{Color as TermColor, Style};
 use similar::{ChangeTag, TextDiff};
+use unicode_width::UnicodeWidthChar;
+
+/// How diffs are displayed.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Format {
+    /// Colored diff with line numbers, see [`diff`]
+    #[default]
+    Preview,
+    /// Unified diff, see [`unified`]
+    Unified,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "preview" => Ok(Self::Preview),
+            "unified" => Ok(Self::Unified),
+            _ => bail!(format!(
+                "'{s}' not a valid diff format, use 'preview' or 'unified'"
+            )),
+        }
+    }
+}
+
+/// When to color output.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Color {
+    /// Color output written to a terminal, unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    /// Always color output
+    Always,
+    /// Never color output
+    Never,
+}
+
+impl Color {
+    /// Enable or disable colors on stdout and stderr. In auto mode colors are
+    /// left to be detected from the terminal and the `NO_COLOR`, `CLICOLOR`
+    /// and `CLICOLOR_FORCE` environment variables.
+    pub fn apply(self) {
+        let enabled = match self {
+            Self::Auto => return,
+            Self::Always => true,
+            Self::Never => false,
+        };
+        console::set_colors_enabled(enabled);
+        console::set_colors_enabled_stderr(enabled);
+    }
+}
+
+impl FromStr for Color {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => bail!(format!(
+                "'{s}' not a valid color choice, use 'auto', 'always' or 'never'"
+            )),
+        }
+    }
+}
+
+/// Part of a diff that can be styled.
+#[derive(Debug, Clone, Copy)]
+pub enum Part {
+    /// Deleted lines
+    Delete,
+    /// Inserted lines
+    Insert,
+    /// Unchanged lines around changes
+    Context,
+    /// The changed part of deleted and inserted lines
+    Emphasis,
+    /// The box, file name and line numbers
+    Frame,
+}
+
+/// Style of a part of diffs, written `<part>=<style>` where the style is a
+/// dot separated list of colors and attributes, e.g. `delete=blue.bold`.
+#[derive(Debug, Clone)]
+pub struct PartStyle {
+    part: Part,
+    style: Style,
+}
+
+impl FromStr for PartStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let Some((part, value)) = s.split_once('=') else {
+            bail!(format!("'{s}' not a valid style, use '<part>=<style>'"))
+        };
+        let part = match part {
+            "delete" => Part::Delete,
+            "insert" => Part::Insert,
+            "context" => Part::Context,
+            "emphasis" => Part::Emphasis,
+            "frame" => Part::Frame,
+            _ => bail!(format!(
+                "'{part}' not a diff part, use 'delete', 'insert', 'context', 'emphasis' or 'frame'"
+            )),
+        };
+        if value == "plain" {
+            return Ok(Self {
+                part,
+                style: Style::new(),
+            });
+        }
+        for attribute in value.split('.') {
+            if Style::from_dotted_str(attribute) == Style::new() {
+                bail!(format!(
+                    "'{attribute}' not a color or attribute, use e.g. 'red', 'on_blue', '#ff8800', \
+                     'bold', 'dim', 'underlined', 'reverse' or 'plain'"
+                ))
+            }
+        }
+        Ok(Self {
+            part,
+            style: Style::from_dotted_str(value),
+        })
+    }
+}
+
+/// Styles of the parts of diffs.
+#[derive(Debug, Clone)]
+struct Theme {
+    delete: Style,
+    insert: Style,
+    context: Style,
+    emphasis: Style,
+    frame: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            delete: Style::new().red(),
+            insert: Style::new().green(),
+            context: Style::new().dim(),
+            emphasis: Style::new().underlined().on_black(),
+            frame: Style::new().dim(),
+        }
+    }
+}
+
+/// Styles of diffs, set by [`set_styles`].
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Override the default styles of the parts of diffs.
+pub fn set_styles(styles: &[PartStyle]) {
+    let mut theme = Theme::default();
+    for PartStyle { part, style } in styles {
+        let style = style.clone();
+        match part {
+            Part::Delete => theme.delete = style,
+            Part::Insert => theme.insert = style,
+            Part::Context => theme.context = style,
+            Part::Emphasis => theme.emphasis = style,
+            Part::Frame => theme.frame = style,
+        }
+    }
+    let _ = THEME.set(theme);
+}
 
 /// Helper struct for formatting line numbers in diff output.
 struct Line(Option<usize>);
@@ -20,6 +194,72 @@ impl fmt::Display for Line {
     }
 }
 
+/// Width of the line number columns and the change sign before each line.
+const GUTTER: usize = 13;
+
+/// Columns between tab stops when expanding tabs in diffs.
+const TAB_WIDTH: usize = 8;
+
+/// Highlighter for diff content, set by [`enable_highlighting`].
+#[cfg(feature = "highlight")]
+static HIGHLIGHTER: OnceLock<crate::highlight::Highlighter> = OnceLock::new();
+
+/// Highlight the content of diffs by file type.
+#[cfg(feature = "highlight")]
+#[allow(clippy::unnecessary_wraps)]
+pub fn enable_highlighting() -> anyhow::Result<()> {
+    HIGHLIGHTER.get_or_init(crate::highlight::Highlighter::new);
+    Ok(())
+}
+
+#[cfg(not(feature = "highlight"))]
+pub fn enable_highlighting() -> anyhow::Result<()> {
+    bail!("regop was built without the `highlight` feature, diffs can't be highlighted")
+}
+
+/// Syntax highlighting colors of the ranges of a line, if enabled.
+#[cfg(feature = "highlight")]
+fn highlight(file: &str, line: &str) -> Vec<(Range<usize>, TermColor)> {
+    HIGHLIGHTER
+        .get()
+        .map(|highlighter| highlighter.colors(file, line))
+        .unwrap_or_default()
+}
+
+#[cfg(not(feature = "highlight"))]
+const fn highlight(_: &str, _: &str) -> Vec<(Range<usize>, TermColor)> {
+    Vec::new()
+}
+
+/// Split a part of a line, starting at `offset`, into the parts with
+/// different highlighting colors.
+fn split_colors<'a>(
+    text: &'a str,
+    offset: usize,
+    colors: &[(Range<usize>, TermColor)],
+) -> Vec<(&'a str, Option<TermColor>)> {
+    if colors.is_empty() {
+        return vec![(text, None)];
+    }
+    let end = offset + text.len();
+    colors
+        .iter()
+        .filter(|(range, _)| range.start < end && range.end > offset)
+        .map(|(range, color)| {
+            let start = range.start.max(offset) - offset;
+            let stop = range.end.min(end) - offset;
+            (&text[start..stop], Some(*color))
+        })
+        .collect()
+}
+
+/// Width of the terminal stdout is written to, or 80 if it isn't a terminal.
+pub fn terminal_width() -> usize {
+    console::Term::stdout()
+        .size_checked()
+        .map_or(80, |(_, width)| width.into())
+}
+
 /// Display a visual diff between old and new content.
 ///
 /// Shows changes in a format similar to git diff with:
@@ -28,50 +268,233 @@ impl fmt::Display for Line {
 /// - Line numbers on both sides
 /// - Highlighted inline changes
 ///
+/// Lines too long to fit the width are wrapped.
+///
 /// # Arguments
 ///
 /// * `file` - The filename to display in the header
 /// * `old` - The original content
 /// * `new` - The modified content
-pub fn diff(file: &str, old: &str, new: &str) {
-    print!("┌");
-    println!("{:─^1$}", "─", 79);
-    println!("│ {}", style(file).bold().dim());
-    print!("├");
-    println!("{:─^1$}", "─", 79);
+/// * `width` - The width of the output in columns
+pub fn diff(file: &str, old: &str, new: &str, width: usize) {
+    let theme = THEME.get_or_init(Theme::default);
+    let frame = &theme.frame;
+    let width = width.saturating_sub(1).max(GUTTER);
+    let rule = frame.apply_to(format!("{:─^1$}", "─", width)).to_string();
+    println!("{}{rule}", frame.apply_to("┌"));
+    println!("{} {}", frame.apply_to("│"), frame.apply_to(file).bold());
+    println!("{}{rule}", frame.apply_to("├"));
     let diff = TextDiff::from_lines(old, new);
     for (idx, group) in diff.grouped_ops(1).iter().enumerate() {
         if idx > 0 {
-            print!("├");
-            println!("{:─^1$}", "─", 79);
+            println!("{}{rule}", frame.apply_to("├"));
         }
         for op in group {
             for change in diff.iter_inline_changes(op) {
                 let (sign, s) = match change.tag() {
-                    ChangeTag::Delete => ("-", Style::new().red()),
-                    ChangeTag::Insert => ("+", Style::new().green()),
-                    ChangeTag::Equal => (" ", Style::new().dim()),
+                    ChangeTag::Delete => ("-", &theme.delete),
+                    ChangeTag::Insert => ("+", &theme.insert),
+                    ChangeTag::Equal => (" ", &theme.context),
                 };
                 print!(
-                    "│ {}{} │{}",
-                    style(Line(change.old_index())).dim(),
-                    style(Line(change.new_index())).dim(),
+                    "{} {}{} {}{}",
+                    frame.apply_to("│"),
+                    frame.apply_to(Line(change.old_index())),
+                    frame.apply_to(Line(change.new_index())),
+                    frame.apply_to("│"),
                     s.apply_to(sign).bold(),
                 );
-                for (emphasized, value) in change.iter_strings_lossy() {
-                    if emphasized {
-                        print!("{}", s.apply_to(value).underlined().on_black());
-                    } else {
-                        print!("{}", s.apply_to(value));
+                let mut column = 0;
+                let segments = change
+                    .iter_strings_lossy()
+                    .map(|(emphasized, value)| {
+                        let value = value.trim_end_matches(['\r', '\n']);
+                        (emphasized, expand_tabs(value, &mut column))
+                    })
+                    .collect::<Vec<_>>();
+                let line = segments.iter().map(|(_, v)| v.as_str()).collect::<String>();
+                let colors = highlight(file, &line);
+                let mut runs = Vec::new();
+                let mut offset = 0;
+                for (emphasized, value) in &segments {
+                    for (part, color) in split_colors(value, offset, &colors) {
+                        let run_style = color.map_or_else(|| s.clone(), |c| s.clone().fg(c));
+                        runs.push((run_style, *emphasized, part));
                     }
+                    offset += value.len();
                 }
-                if change.missing_newline() {
-                    println!();
+
+                let room = width.saturating_sub(GUTTER).max(1);
+                let mut column = 0;
+                for (run_style, emphasized, mut rest) in runs {
+                    while !rest.is_empty() {
+                        if column >= room {
+                            print!(
+                                "\n{} {:8} {} ",
+                                frame.apply_to("│"),
+                                "",
+                                frame.apply_to("│")
+                            );
+                            column = 0;
+                        }
+                        let (part, tail, part_width) = split_width(rest, room - column);
+                        if column > 0 && part_width > room - column {
+                            column = room;
+                            continue;
+                        }
+                        column += part_width;
+                        rest = tail;
+                        if emphasized {
+                            print!("{}", theme.emphasis.apply_to(run_style.apply_to(part)));
+                        } else {
+                            print!("{}", run_style.apply_to(part));
+                        }
+                    }
                 }
+                println!();
             }
         }
     }
 
-    print!("└");
-    println!("{:─^1$}", "─", 79);
+    println!("{}{rule}", frame.apply_to("└"));
+}
+
+/// Replace tabs with spaces up to the next tab stop, starting at `column`,
+/// which is advanced by the width of the text.
+fn expand_tabs(text: &str, column: &mut usize) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\t' {
+            let spaces = TAB_WIDTH - *column % TAB_WIDTH;
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+            *column += spaces;
+        } else {
+            expanded.push(c);
+            *column += c.width().unwrap_or(0);
+        }
+    }
+    expanded
+}
+
+/// Split off the longest prefix of `text` that fits in `room` columns, and
+/// return it with the rest and its width.
+///
+/// At least one character is taken, even if it is wider than `room`, so
+/// wrapping always makes progress.
+fn split_width(text: &str, room: usize) -> (&str, &str, usize) {
+    let mut width = 0;
+    for (i, c) in text.char_indices() {
+        let char_width = c.width().unwrap_or(0);
+        if i > 0 && width + char_width > room {
+            return (&text[..i], &text[i..], width);
+        }
+        width += char_width;
+    }
+    (text, "", width)
+}
+
+/// Number of lines inserted and deleted, like `git diff --stat` shows.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DiffStat {
+    /// Number of inserted lines
+    pub insertions: usize,
+    /// Number of deleted lines
+    pub deletions: usize,
+}
+
+impl DiffStat {
+    /// Count the lines inserted and deleted between old and new content.
+    pub fn new(old: &str, new: &str) -> Self {
+        let mut stat = Self::default();
+        for change in TextDiff::from_lines(old, new).iter_all_changes() {
+            match change.tag() {
+                ChangeTag::Insert => stat.insertions += 1,
+                ChangeTag::Delete => stat.deletions += 1,
+                ChangeTag::Equal => {}
+            }
+        }
+        stat
+    }
+
+    /// Add the lines of another diff to the totals.
+    pub const fn add(&mut self, other: Self) {
+        self.insertions += other.insertions;
+        self.deletions += other.deletions;
+    }
+}
+
+impl fmt::Display for DiffStat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        let mut parts = Vec::new();
+        if self.insertions > 0 || self.deletions == 0 {
+            parts.push(format!(
+                "{} insertion{}(+)",
+                self.insertions,
+                plural(self.insertions)
+            ));
+        }
+        if self.deletions > 0 || self.insertions == 0 {
+            parts.push(format!(
+                "{} deletion{}(-)",
+                self.deletions,
+                plural(self.deletions)
+            ));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Create a unified diff between old and new content.
+///
+/// The file is named `a/<file>` and `b/<file>` in the headers, like git
+/// does, so the diff applies with `patch -p1` or `git apply`.
+pub fn unified(file: &str, old: &str, new: &str) -> String {
+    let file = file.strip_prefix("./").unwrap_or(file);
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(&format!("a/{file}"), &format!("b/{file}"))
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diffstat() {
+        let stat = DiffStat::new("a\nb\nc\n", "a\nB\nc\nd\n");
+        assert_eq!((stat.insertions, stat.deletions), (2, 1));
+        assert_eq!(stat.to_string(), "2 insertions(+), 1 deletion(-)");
+
+        let mut total = DiffStat::default();
+        assert_eq!(total.to_string(), "0 insertions(+), 0 deletions(-)");
+        total.add(stat);
+        total.add(DiffStat::new("a\n", ""));
+        assert_eq!(total.to_string(), "2 insertions(+), 2 deletions(-)");
+        assert_eq!(DiffStat::new("", "a\n").to_string(), "1 insertion(+)");
+        assert_eq!(DiffStat::new("a\n", "").to_string(), "1 deletion(-)");
+    }
+
+    #[test]
+    fn test_split_width() {
+        assert_eq!(split_width("abcdef", 4), ("abcd", "ef", 4));
+        assert_eq!(split_width("ab", 4), ("ab", "", 2));
+        assert_eq!(split_width("日本語", 4), ("日本", "語", 4));
+        assert_eq!(split_width("日本語", 3), ("日", "本語", 2));
+        assert_eq!(split_width("日本", 1), ("日", "本", 2));
+    }
+
+    #[test]
+    fn test_expand_tabs() {
+        let mut column = 0;
+        assert_eq!(expand_tabs("\tx", &mut column), "        x");
+        assert_eq!(column, 9);
+        assert_eq!(expand_tabs("ab\tc", &mut column), "ab     c");
+        assert_eq!(column, 17);
+
+        let mut column = 0;
+        assert_eq!(expand_tabs("日\t", &mut column), "日      ");
+        assert_eq!(column, 8);
+    }
 }
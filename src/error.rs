@@ -0,0 +1,244 @@
+//! Errors returned by the library.
+
+use std::num::ParseIntError;
+use std::path::PathBuf;
+
+/// A boxed error from a regex engine.
+type EngineError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Result type of the library, see [`Error`].
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The ways parsing and processing can fail.
+///
+/// # Examples
+///
+/// ```
+/// use regop::{Capture, Error, Operator, process};
+/// use std::str::FromStr;
+///
+/// let capture = Capture::from_str(r"(?<num>\d+)").unwrap();
+/// let op = Operator::from_str("<num>:div:0").unwrap();
+/// let result = process(false, &[capture], &[op], "10".to_string());
+/// assert!(matches!(result, Err(Error::DivisionByZero)));
+/// ```
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// A regex failed to compile
+    #[error("'{pattern}' not a valid regex")]
+    Regex {
+        /// The pattern as it was given
+        pattern: String,
+        /// The error from the regex engine
+        #[source]
+        source: EngineError,
+    },
+    /// A regex compiles to more than [`RegexOptions::size_limit`](crate::RegexOptions::size_limit)
+    #[error("'{pattern}' exceeds the regex size limit of {limit} bytes")]
+    RegexLimit {
+        /// The pattern as it was given
+        pattern: String,
+        /// The size limit in bytes
+        limit: usize,
+    },
+    /// Matching a regex failed, e.g. when the fancy engine hits its
+    /// backtracking limit
+    #[error("unable to match '{pattern}'")]
+    Match {
+        /// The pattern that failed to match
+        pattern: String,
+        /// The error from the regex engine
+        #[source]
+        source: EngineError,
+    },
+    /// The regex engine requires a feature regop was built without
+    #[error("regop was built without the `{0}` feature, the {0} engine is not available")]
+    MissingFeature(&'static str),
+    /// A capture, operator, selector, guard, condition, expectation, type or
+    /// other option is not valid
+    #[error("{0}")]
+    Parse(String),
+    /// A value operated on as a number is not an int
+    #[error("cannot parse '{value}' as int")]
+    Int {
+        /// The value that isn't an int
+        value: String,
+        /// Why it isn't an int
+        #[source]
+        source: ParseIntError,
+    },
+    /// A locale that case conversion doesn't support
+    #[error("'{0}' is not a supported locale")]
+    Locale(String),
+    /// A capture used as the value of an operator has no matches
+    #[error("'<{0}>' used as value but not found")]
+    MissingCapture(String),
+    /// A capture matched a different number of times than expected
+    #[error("expected '<{target}>' to match {expected} times, found {found}")]
+    Expectation {
+        /// Name of the capture
+        target: String,
+        /// The expected count or range of counts, e.g. `1` or `1..3`
+        expected: String,
+        /// How many times the capture matched
+        found: usize,
+    },
+    /// A captured value is not of the expected type
+    #[error("value '{value}' of '<{target}>' is not {expected}")]
+    Type {
+        /// Name of the capture
+        target: String,
+        /// The captured value
+        value: String,
+        /// What the value was expected to be, e.g. `an int` or `in range 1..10`
+        expected: String,
+    },
+    /// An operator would edit more matches than its `@max` limit
+    #[error("operator on '<{target}>' would edit {count} matches, limit is {max}")]
+    OperatorLimit {
+        /// Target of the operator
+        target: String,
+        /// How many matches it would edit
+        count: usize,
+        /// The limit
+        max: usize,
+    },
+    /// All operators together would edit more matches than allowed
+    #[error("operators would edit {count} matches, limit is {max}")]
+    MatchLimit {
+        /// How many matches they would edit
+        count: usize,
+        /// The limit
+        max: usize,
+    },
+    /// An operator edited no matches with [`Options::strict`](crate::Options::strict)
+    #[error("operator on '<{0}>' edited no matches")]
+    NoEdits(String),
+    /// Captures to swap matched a different number of times
+    #[error(
+        "Cannot swap '{target}' and '{other}': different number of matches ({found} vs {other_found})"
+    )]
+    SwapMismatch {
+        /// Target of the swap operator
+        target: String,
+        /// The capture it swaps with
+        other: String,
+        /// Number of matches of the target
+        found: usize,
+        /// Number of matches of the other capture
+        other_found: usize,
+    },
+    /// Edits of different operators overlap each other
+    #[error("edits overlap each other")]
+    Overlap,
+    /// A planned edit doesn't match the content it is applied to
+    #[error("edit at {start}..{end} doesn't match the content")]
+    Stale {
+        /// Start position of the edit
+        start: usize,
+        /// End position of the edit
+        end: usize,
+    },
+    /// A division by zero
+    #[error("division by zero")]
+    DivisionByZero,
+    /// Arithmetic on a value overflows
+    #[error("arithmetic on '{0}' overflows")]
+    Overflow(String),
+    /// Reading or writing a stream failed
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// Reading a file failed
+    #[error("unable to read file '{}'", path.display())]
+    Read {
+        /// Path of the file
+        path: PathBuf,
+        /// Why reading failed
+        #[source]
+        source: std::io::Error,
+    },
+    /// Writing a file failed
+    #[error("unable to write '{}'", path.display())]
+    Write {
+        /// Path of the file
+        path: PathBuf,
+        /// Why writing failed
+        #[source]
+        source: std::io::Error,
+    },
+    /// A file is not valid UTF-8
+    #[error("'{}' is not valid UTF-8", .0.display())]
+    NotUtf8(PathBuf),
+}
+
+/// A problem with an operator found by [`validate`](crate::validate), along
+/// with the index of the operator.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum ValidationIssue {
+    /// The target is not a group of any capture, or a pattern matching none
+    #[error("operator on '<{target}>' targets no capture group")]
+    UnknownTarget {
+        /// Index of the operator
+        operator: usize,
+        /// Target of the operator
+        target: String,
+    },
+    /// A capture used as a parameter is not a group of any capture
+    #[error("operator on '<{target}>' uses '<{name}>' as value, which is not a capture group")]
+    UnknownCapture {
+        /// Index of the operator
+        operator: usize,
+        /// Target of the operator
+        target: String,
+        /// Name of the capture used as parameter
+        name: String,
+    },
+    /// A swap operator swaps with a plain value instead of a capture
+    #[error("operator on '<{target}>' swaps with '{value}', which is not a capture like '<name>'")]
+    SwapValue {
+        /// Index of the operator
+        operator: usize,
+        /// Target of the operator
+        target: String,
+        /// The value given to swap with
+        value: String,
+    },
+    /// A swap operator is part of a pipeline
+    #[error("operator on '<{target}>' uses 'swap' in a pipeline")]
+    SwapInPipeline {
+        /// Index of the operator
+        operator: usize,
+        /// Target of the operator
+        target: String,
+    },
+    /// A numeric operation has a parameter that is not an int
+    #[error("operator on '<{target}>' has parameter '{value}', which is not an int")]
+    NotAnInt {
+        /// Index of the operator
+        operator: usize,
+        /// Target of the operator
+        target: String,
+        /// The parameter
+        value: String,
+    },
+    /// A division by zero
+    #[error("operator on '<{target}>' divides by zero")]
+    DivisionByZero {
+        /// Index of the operator
+        operator: usize,
+        /// Target of the operator
+        target: String,
+    },
+    /// Case conversion with a locale that is not supported
+    #[error("operator on '<{target}>' uses '{locale}', which is not a supported locale")]
+    Locale {
+        /// Index of the operator
+        operator: usize,
+        /// Target of the operator
+        target: String,
+        /// The locale given
+        locale: String,
+    },
+}
@@ -0,0 +1,254 @@
+//! Processing of files on disk.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "tokio")]
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+use crate::{Error, Processor, Result, Stats, atomic_write};
+
+/// Options for [`Processor::process_files`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FileOptions {
+    /// Write changed content back to the files
+    pub write: bool,
+    /// Keep the modification time of written files
+    pub preserve_mtime: bool,
+    /// Skip files larger than this many bytes
+    pub max_filesize: Option<u64>,
+}
+
+/// What happened to a file, see [`FileResult`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileOutcome {
+    /// The file is larger than [`FileOptions::max_filesize`]
+    Skipped,
+    /// Processing didn't change the file
+    Unchanged,
+    /// The new content of the file, written back if [`FileOptions::write`] is
+    /// set
+    Changed(String),
+}
+
+/// The result of processing a single file with [`Processor::process_files`].
+#[derive(Debug)]
+pub struct FileResult {
+    /// Path of the file as it was given
+    pub path: PathBuf,
+    /// What happened to the file, or why reading, processing or writing it
+    /// failed
+    pub outcome: Result<FileOutcome>,
+    /// Statistics about processing the file, empty if it failed
+    pub stats: Stats,
+}
+
+impl Processor {
+    /// Read, process and optionally write back files in parallel.
+    ///
+    /// Files are independent of each other: a file that can't be read,
+    /// processed or written fails on its own in its [`FileResult`] without
+    /// affecting the others. Results are in the order of the paths. Each
+    /// thread reads its files into the same buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use regop::{FileOptions, FileOutcome, Regop};
+    ///
+    /// let path = std::env::temp_dir().join("regop-process-files.toml");
+    /// std::fs::write(&path, "version = \"1.0.0\"\n").unwrap();
+    ///
+    /// let processor = Regop::builder()
+    ///     .capture(r#"version = "(?<version>[^"]+)""#)
+    ///     .op("<version>:rep:2.0.0")
+    ///     .build()
+    ///     .unwrap();
+    /// let options = FileOptions { write: true, ..FileOptions::default() };
+    /// for result in processor.process_files(&[&path], &options) {
+    ///     match result.outcome {
+    ///         Ok(FileOutcome::Changed(_)) => println!("updated {}", result.path.display()),
+    ///         Ok(_) => {}
+    ///         Err(e) => eprintln!("{}: {e}", result.path.display()),
+    ///     }
+    /// }
+    /// assert_eq!(std::fs::read_to_string(&path).unwrap(), "version = \"2.0.0\"\n");
+    /// ```
+    pub fn process_files<P: AsRef<Path> + Sync>(
+        &self,
+        paths: &[P],
+        options: &FileOptions,
+    ) -> Vec<FileResult> {
+        paths
+            .par_iter()
+            .map_init(Vec::new, |buffer, path| {
+                let path = path.as_ref();
+                file_result(path, self.process_file(path, options, buffer))
+            })
+            .collect()
+    }
+
+    /// Read, process and optionally write back files like
+    /// [`process_files`](Self::process_files), but with `tokio::fs` so it can
+    /// be awaited from async code. Files are processed one after another.
+    ///
+    /// Processing the content of a file is CPU bound, so it runs on the
+    /// blocking thread pool of the runtime with `spawn_blocking` rather than
+    /// stalling the task awaiting this. The processor is cloned once for
+    /// that, as blocking tasks can't borrow it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::PathBuf;
+    ///
+    /// use regop::{FileOptions, Regop};
+    ///
+    /// async fn bump(paths: &[PathBuf]) -> regop::Result<()> {
+    ///     let processor = Regop::builder()
+    ///         .capture(r#"version = "(?<version>[^"]+)""#)
+    ///         .op("<version>:rep:2.0.0")
+    ///         .build()?;
+    ///     let options = FileOptions { write: true, ..FileOptions::default() };
+    ///     for result in processor.process_files_async(paths, &options).await {
+    ///         result.outcome?;
+    ///     }
+    ///     Ok(())
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("regop-process-files-async.toml");
+    /// std::fs::write(&path, "version = \"1.0.0\"\n").unwrap();
+    /// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    /// runtime.block_on(bump(&[path.clone()])).unwrap();
+    /// assert_eq!(std::fs::read_to_string(&path).unwrap(), "version = \"2.0.0\"\n");
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn process_files_async<P: AsRef<Path> + Sync>(
+        &self,
+        paths: &[P],
+        options: &FileOptions,
+    ) -> Vec<FileResult> {
+        let processor = Arc::new(self.clone());
+        let mut results = Vec::with_capacity(paths.len());
+        for path in paths {
+            let path = path.as_ref();
+            let result = processor.process_file_async(path, options).await;
+            results.push(file_result(path, result));
+        }
+        results
+    }
+
+    /// Read, process and optionally write back a single file, reading it into
+    /// the buffer.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(path = %path.display()))
+    )]
+    fn process_file(
+        &self,
+        path: &Path,
+        options: &FileOptions,
+        buffer: &mut Vec<u8>,
+    ) -> Result<(FileOutcome, Stats)> {
+        buffer.clear();
+        File::open(path)
+            .and_then(|f| f.take(limit(options)).read_to_end(buffer))
+            .map_err(|source| read_error(path, source))?;
+        let (outcome, stats) = self.process_bytes_of(path, buffer, options)?;
+        if options.write
+            && let FileOutcome::Changed(new) = &outcome
+        {
+            atomic_write(path, new, options.preserve_mtime)?;
+        }
+        Ok((outcome, stats))
+    }
+
+    /// Read, process and optionally write back a single file with `tokio::fs`,
+    /// processing it on the blocking thread pool.
+    #[cfg(feature = "tokio")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(path = %path.display()))
+    )]
+    async fn process_file_async(
+        self: &Arc<Self>,
+        path: &Path,
+        options: &FileOptions,
+    ) -> Result<(FileOutcome, Stats)> {
+        use tokio::io::AsyncReadExt;
+
+        let mut bytes = Vec::new();
+        let read = async {
+            let file = tokio::fs::File::open(path).await?;
+            file.take(limit(options)).read_to_end(&mut bytes).await
+        };
+        read.await.map_err(|source| read_error(path, source))?;
+        let processor = Arc::clone(self);
+        let (owned_path, owned_options) = (path.to_path_buf(), options.clone());
+        let processed = tokio::task::spawn_blocking(move || {
+            processor.process_bytes_of(&owned_path, &bytes, &owned_options)
+        })
+        .await;
+        let (outcome, stats) = match processed {
+            Ok(processed) => processed?,
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        };
+        if options.write
+            && let FileOutcome::Changed(new) = &outcome
+        {
+            crate::write::atomic_write_async(path, new, options.preserve_mtime).await?;
+        }
+        Ok((outcome, stats))
+    }
+
+    /// Process the content read from a file, skipping it if it is larger than
+    /// [`FileOptions::max_filesize`].
+    fn process_bytes_of(
+        &self,
+        path: &Path,
+        bytes: &[u8],
+        options: &FileOptions,
+    ) -> Result<(FileOutcome, Stats)> {
+        if options
+            .max_filesize
+            .is_some_and(|max| bytes.len() as u64 > max)
+        {
+            return Ok((FileOutcome::Skipped, Stats::default()));
+        }
+        let content = std::str::from_utf8(bytes).map_err(|_| Error::NotUtf8(path.to_path_buf()))?;
+        let (new, stats) = self.process_with_stats(content)?;
+        match new.filter(|new| *new != content) {
+            Some(new) => Ok((FileOutcome::Changed(new), stats)),
+            None => Ok((FileOutcome::Unchanged, stats)),
+        }
+    }
+}
+
+/// How many bytes to read from a file, one more than
+/// [`FileOptions::max_filesize`] to tell if it is larger.
+fn limit(options: &FileOptions) -> u64 {
+    options.max_filesize.map_or(u64::MAX, |max| max + 1)
+}
+
+/// The error for a file that can't be read.
+fn read_error(path: &Path, source: std::io::Error) -> Error {
+    Error::Read {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// The result of processing a file, with empty statistics if it failed.
+fn file_result(path: &Path, result: Result<(FileOutcome, Stats)>) -> FileResult {
+    let (outcome, stats) = match result {
+        Ok((outcome, stats)) => (Ok(outcome), stats),
+        Err(e) => (Err(e), Stats::default()),
+    };
+    FileResult {
+        path: path.to_path_buf(),
+        outcome,
+        stats,
+    }
+}
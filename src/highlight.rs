@@ -0,0 +1,57 @@
+//! Syntax highlighting of diff content by file type.
+
+use std::ops::Range;
+use std::path::Path;
+
+use console::Color;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+
+/// Name of the theme used for highlighting.
+const THEME: &str = "base16-ocean.dark";
+
+/// Syntax definitions and a theme to highlight lines with.
+pub struct Highlighter {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+impl Highlighter {
+    /// Load the default syntax definitions and theme.
+    pub fn new() -> Self {
+        let mut themes = ThemeSet::load_defaults().themes;
+        Self {
+            syntaxes: SyntaxSet::load_defaults_nonewlines(),
+            theme: themes.remove(THEME).unwrap_or_default(),
+        }
+    }
+
+    /// Colors of the ranges of a line, by the syntax of the file extension.
+    /// Lines of unknown file types have no colors.
+    ///
+    /// Each line is highlighted on its own, so constructs spanning several
+    /// lines, like multiline strings, may not be highlighted correctly.
+    pub fn colors(&self, file: &str, line: &str) -> Vec<(Range<usize>, Color)> {
+        let Some(syntax) = Path::new(file).extension().and_then(|ext| {
+            self.syntaxes
+                .find_syntax_by_extension(&ext.to_string_lossy())
+        }) else {
+            return Vec::new();
+        };
+        let mut highlighter = HighlightLines::new(syntax, &self.theme);
+        let Ok(ranges) = highlighter.highlight_line(line, &self.syntaxes) else {
+            return Vec::new();
+        };
+        let mut offset = 0;
+        ranges
+            .into_iter()
+            .map(|(style, text)| {
+                let range = offset..offset + text.len();
+                offset = range.end;
+                let fg = style.foreground;
+                (range, Color::TrueColor(fg.r, fg.g, fg.b))
+            })
+            .collect()
+    }
+}
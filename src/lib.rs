@@ -9,7 +9,7 @@
 //!
 //! - **Regex-based capture**: Use named capture groups to extract values from text
 //! - **Rich operators**: Transform captured values with operations like increment, decrement,
-//!   multiply, divide, replace, swap, append, prepend, and case conversion
+//!   multiply, divide, replace, swap, append, prepend, case conversion, ordinals and trimming
 //! - **Batch operations**: Apply multiple operators to multiple files efficiently
 //! - **Safe transformations**: All edits are validated to prevent overlapping changes
 //! - **Flexible input**: Process files, stdin, or multiple files from piped input
@@ -49,8 +49,17 @@
 //! | `swap` | Swap with another capture | Required | `<major>:swap:<minor>` |
 //! | `append` | Append text | Required | `<file>:append:.bak` |
 //! | `prepend` | Prepend text | Required | `<name>:prepend:prefix_` |
-//! | `upper` | Convert to uppercase | None | `<text>:upper` |
-//! | `lower` | Convert to lowercase | None | `<TEXT>:lower` |
+//! | `upper` | Convert to uppercase | None | `<text>:upper`, `<text>:upper:tr` |
+//! | `lower` | Convert to lowercase | None | `<TEXT>:lower`, `<TEXT>:lower:tr` |
+//! | `ordinal` | Number to ordinal | None | `<place>:ordinal` |
+//! | `trim` | Trim whitespace | None | `<name>:trim` |
+//!
+//! Operations can be chained with `|` to apply them in order to the same capture,
+//! e.g. `<name>:trim|lower|prepend:img_`.
+//!
+//! An operator can be limited to a single match of its target with a selector,
+//! e.g. `<version>[2]:inc` or `<version>[last]:rep:9`, or to a range of matches
+//! like `<version>[2..5]:inc`.
 //!
 //! ## Command Line Usage
 //!
@@ -65,20 +74,49 @@
 //! find -name '*.toml' | regop -w -r '"(?<v>\d+)"' -o '<v>:inc'
 //! ```
 
-use std::collections::{HashMap, HashSet};
-use std::ops::{Add, Sub};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::hash::BuildHasher;
+use std::io::{BufRead, Write};
 use std::str::FromStr;
 use std::string::ToString;
+use std::sync::{LazyLock, Mutex, PoisonError};
+
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder, RegexSet};
+use smallvec::{SmallVec, smallvec};
+
+mod error;
+mod files;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+mod write;
 
-use anyhow::{Context, anyhow, bail, ensure};
-use regex::Regex;
+pub use error::{Error, Result, ValidationIssue};
+pub use files::{FileOptions, FileOutcome, FileResult};
+pub use write::atomic_write;
 
-type CapturesMap<'a> = HashMap<String, Vec<(usize, usize, &'a str)>>;
+/// A single capture match: start, end, the matched text and the index of the
+/// regex match it is part of, unique within a unit.
+type Match<'a> = (usize, usize, &'a str, usize);
+
+type CapturesMap<'a> = HashMap<String, Vec<Match<'a>>>;
+
+/// Start and end of each group in a single regex match, indexed by group.
+type GroupSpans = Vec<Option<(usize, usize)>>;
+
+/// Index and name of each group of a regex, starting with the whole match.
+type GroupNames = Vec<(usize, String)>;
 
 /// A compiled regular expression with its named capture groups.
 ///
 /// This struct represents a regex pattern that can extract named values from text.
-/// The regex must use named capture groups in the format `(?<name>pattern)`.
+/// Capture groups are named in the format `(?<name>pattern)`, unnamed groups are
+/// referenced by their index, e.g. `1` for the first group. The whole match can
+/// always be referenced as `0`.
+///
+/// A capture is `Send` and `Sync`, so the compiled regex can be shared across
+/// threads.
 ///
 /// # Examples
 ///
@@ -89,1170 +127,7184 @@ type CapturesMap<'a> = HashMap<String, Vec<(usize, usize, &'a str)>>;
 /// let capture = Capture::from_str(r#"version = "(?<major>\d+)\.(?<minor>\d+)""#).unwrap();
 /// assert!(capture.names.contains("major"));
 /// assert!(capture.names.contains("minor"));
+///
+/// let capture = Capture::from_str(r#"version = "(\d+)\.(\d+)""#).unwrap();
+/// assert!(capture.names.contains("1"));
+/// assert!(capture.names.contains("2"));
 /// ```
 #[derive(Debug, Clone)]
 pub struct Capture {
     /// The compiled regular expression
-    pub regex: Regex,
-    /// Set of all named capture groups in the regex
+    pub regex: Pattern,
+    /// Set of the names of all capture groups in the regex
     pub names: HashSet<String>,
 }
 
-impl FromStr for Capture {
-    type Err = anyhow::Error;
+/// A regular expression compiled by one of the supported engines.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// Compiled with the `regex` crate
+    Standard(Regex),
+    /// Compiled with the `fancy-regex` crate, supports lookaround and backreferences
+    #[cfg(feature = "fancy")]
+    Fancy(fancy_regex::Regex),
+}
+
+impl Pattern {
+    /// Check if the pattern matches anywhere in the text.
+    pub fn is_match(&self, text: &str) -> Result<bool> {
+        match self {
+            Self::Standard(re) => Ok(re.is_match(text)),
+            #[cfg(feature = "fancy")]
+            Self::Fancy(re) => re.is_match(text).map_err(|e| Error::Match {
+                pattern: re.as_str().to_string(),
+                source: e.into(),
+            }),
+        }
+    }
+
+    /// The source of the pattern.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Standard(re) => re.as_str(),
+            #[cfg(feature = "fancy")]
+            Self::Fancy(re) => re.as_str(),
+        }
+    }
+
+    /// Names of all groups, `None` for unnamed ones, starting with the whole match.
+    fn capture_names(&self) -> Vec<Option<&str>> {
+        match self {
+            Self::Standard(re) => re.capture_names().collect(),
+            #[cfg(feature = "fancy")]
+            Self::Fancy(re) => re.capture_names().collect(),
+        }
+    }
+
+    /// Spans of all groups in every match in the text, indexed by group.
+    #[cfg_attr(not(feature = "fancy"), allow(clippy::unnecessary_wraps))]
+    fn spans(&self, text: &str) -> Result<Vec<GroupSpans>> {
+        let groups = self.capture_names().len();
+        match self {
+            Self::Standard(re) => Ok(re
+                .captures_iter(text)
+                .map(|m| {
+                    (0..groups)
+                        .map(|i| m.get(i).map(|g| (g.start(), g.end())))
+                        .collect()
+                })
+                .collect()),
+            #[cfg(feature = "fancy")]
+            Self::Fancy(re) => re
+                .captures_iter(text)
+                .map(|m| {
+                    let m = m.map_err(|e| Error::Match {
+                        pattern: re.as_str().to_string(),
+                        source: e.into(),
+                    })?;
+                    Ok((0..groups)
+                        .map(|i| m.get(i).map(|g| (g.start(), g.end())))
+                        .collect())
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The regex engine used to compile capture regexes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Engine {
+    /// The `regex` crate, guarantees linear time matching
+    #[default]
+    Standard,
+    /// The `fancy-regex` crate, supports lookaround and backreferences but
+    /// requires the `fancy` feature
+    Fancy,
+}
+
+impl FromStr for Engine {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(Self::Standard),
+            "fancy" => Ok(Self::Fancy),
+            _ => Err(Error::Parse(format!(
+                "engine '{s}' not recognized, expected 'standard' or 'fancy'"
+            ))),
+        }
+    }
+}
+
+/// Options for compiling capture regexes.
+///
+/// # Examples
+///
+/// ```
+/// use regop::{Capture, RegexOptions};
+///
+/// let options = RegexOptions {
+///     ignore_case: true,
+///     ..RegexOptions::default()
+/// };
+/// let capture = Capture::with_options("version = (?<v>\\d+)", &options).unwrap();
+/// assert!(capture.regex.is_match("VERSION = 1").unwrap());
+/// ```
+#[derive(Debug, Clone, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct RegexOptions {
+    /// Match letters case insensitively
+    pub ignore_case: bool,
+    /// Make `^` and `$` match at the start and end of lines
+    pub multi_line: bool,
+    /// Make `.` match line endings
+    pub dot_all: bool,
+    /// Ignore whitespace and allow `#` comments in patterns
+    pub extended: bool,
+    /// The engine to compile patterns with
+    pub engine: Engine,
+    /// Treat patterns as literal text where only `(?<name>*)` placeholders
+    /// capture, see [`fixed_pattern`]
+    pub fixed_strings: bool,
+    /// Fragments interpolated into patterns as `{name}`
+    pub fragments: Vec<Fragment>,
+    /// Only match whole words, see [`word_pattern`]
+    pub word_regexp: bool,
+    /// Maximum size in bytes of a compiled regex, patterns compiling to more
+    /// fail with [`Error::RegexLimit`] instead of using unbounded memory. The
+    /// engine's default if not set
+    pub size_limit: Option<usize>,
+    /// Maximum size in bytes of the cache of the lazy DFA used for matching
+    /// a regex, beyond which matching falls back to slower engines. The
+    /// engine's default if not set
+    pub dfa_size_limit: Option<usize>,
+}
+
+impl RegexOptions {
+    /// The size limit and DFA size limit.
+    const fn limits(&self) -> Limits {
+        (self.size_limit, self.dfa_size_limit)
+    }
+}
+
+/// Size limit and DFA size limit of a regex, see [`RegexOptions`].
+type Limits = (Option<usize>, Option<usize>);
+
+/// A named, reusable piece of a regex.
+///
+/// Fragments are specified in the format `name=pattern` and are interpolated
+/// into patterns wherever `{name}` appears. A fragment can use fragments
+/// defined before it.
+///
+/// # Examples
+///
+/// ```
+/// use regop::{Capture, Fragment, RegexOptions};
+/// use std::str::FromStr;
+///
+/// let options = RegexOptions {
+///     fragments: vec![
+///         Fragment::from_str(r"num=\d+").unwrap(),
+///         Fragment::from_str(r"ver={num}\.{num}").unwrap(),
+///     ],
+///     ..RegexOptions::default()
+/// };
+/// let capture = Capture::with_options("version = (?<v>{ver})", &options).unwrap();
+/// assert_eq!(capture.regex.as_str(), r"version = (?<v>(?:(?:\d+)\.(?:\d+)))");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Fragment {
+    /// The name the fragment is referenced by
+    pub name: String,
+    /// The regex the fragment expands to
+    pub pattern: String,
+}
+
+/// Whether a name is alphanumeric and doesn't start with a digit, as names
+/// of fragments, capture groups in fixed patterns and references in
+/// templates must be.
+fn is_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl FromStr for Fragment {
+    type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let regex = Regex::new(s).context(format!("'{s}' not a valid regex"))?;
-        let names = regex
-            .capture_names()
-            .filter_map(|n| n.map(ToString::to_string))
+        let (name, pattern) = s
+            .split_once('=')
+            .ok_or_else(|| Error::Parse(format!("'{s}' not a valid fragment format")))?;
+        if !is_name(name) {
+            return Err(Error::Parse(format!(
+                "fragment name '{name}' must be alphanumeric and not start with a digit"
+            )));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+        })
+    }
+}
+
+/// Interpolate fragments into a pattern, wrapping each in a non-capturing
+/// group so quantifiers apply to the whole fragment.
+fn expand_fragments(s: &str, fragments: &[Fragment]) -> String {
+    fragments.iter().rev().fold(s.to_string(), |pattern, f| {
+        pattern.replace(&format!("{{{}}}", f.name), &format!("(?:{})", f.pattern))
+    })
+}
+
+impl Capture {
+    /// Compile a capture regex with the given options.
+    pub fn with_options(s: &str, options: &RegexOptions) -> Result<Self> {
+        let pattern = if options.fixed_strings {
+            fixed_pattern(s)
+        } else {
+            expand_fragments(s, &options.fragments)
+        };
+        let pattern = if options.word_regexp {
+            word_pattern(&pattern, options.extended)
+        } else {
+            pattern
+        };
+        let regex = match options.engine {
+            // Flags are applied inline so the regex can be combined with
+            // others into a set, see `prefilter`
+            Engine::Standard => Pattern::Standard(
+                cached_regex(&with_flags(&pattern, options), options.limits()).map_err(
+                    |e| match e {
+                        regex::Error::CompiledTooBig(limit) => Error::RegexLimit {
+                            pattern: s.to_string(),
+                            limit,
+                        },
+                        e => Error::Regex {
+                            pattern: s.to_string(),
+                            source: e.into(),
+                        },
+                    },
+                )?,
+            ),
+            Engine::Fancy => fancy_pattern(&pattern, options)?,
+        };
+        let names = group_names(&regex)
+            .into_iter()
+            .map(|(_, name)| name)
             .collect::<HashSet<_>>();
         Ok(Self { regex, names })
     }
 }
 
+/// Compile a regex with the standard engine.
+fn compile(pattern: &str) -> Result<Regex> {
+    cached_regex(pattern, (None, None)).map_err(|e| Error::Regex {
+        pattern: pattern.to_string(),
+        source: e.into(),
+    })
+}
+
+/// Maximum number of compiled regexes kept in [`REGEX_CACHE`].
+const REGEX_CACHE_SIZE: usize = 256;
+
+/// Regexes compiled with the standard engine, by pattern and limits.
+static REGEX_CACHE: LazyLock<Mutex<HashMap<(String, Limits), Regex>>> =
+    LazyLock::new(Mutex::default);
+
+/// Compile a regex with the standard engine, reusing an earlier compilation
+/// of the same pattern with the same limits. Flags are part of the pattern,
+/// see [`with_flags`], so the same pattern with other flags is compiled on
+/// its own.
+fn cached_regex(pattern: &str, limits: Limits) -> Result<Regex, regex::Error> {
+    let key = (pattern.to_string(), limits);
+    // Entries are inserted whole, so a poisoned cache is still consistent
+    let cache = || REGEX_CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(regex) = cache().get(&key) {
+        return Ok(regex.clone());
+    }
+    let mut builder = RegexBuilder::new(pattern);
+    if let (Some(limit), _) = limits {
+        builder.size_limit(limit);
+    }
+    if let (_, Some(limit)) = limits {
+        builder.dfa_size_limit(limit);
+    }
+    let regex = builder.build()?;
+    let mut cache = cache();
+    if cache.len() >= REGEX_CACHE_SIZE {
+        cache.clear();
+    }
+    cache.insert(key, regex.clone());
+    drop(cache);
+    Ok(regex)
+}
+
+/// Wrap a pattern so it only matches whole words.
+///
+/// A match may neither be preceded nor followed by a word character, so
+/// `count` doesn't match inside `discount`, while patterns starting or ending
+/// with other characters, like `"(?<v>\d+)"`, still match. In extended mode
+/// the group is closed on a line of its own, so a trailing `#` comment
+/// doesn't swallow it.
+///
+/// # Examples
+///
+/// ```
+/// use regop::word_pattern;
+///
+/// assert_eq!(word_pattern("count", false), r"\b{start-half}(?:count)\b{end-half}");
+/// ```
+#[must_use]
+pub fn word_pattern(s: &str, extended: bool) -> String {
+    let newline = if extended { "\n" } else { "" };
+    format!(r"\b{{start-half}}(?:{s}{newline})\b{{end-half}}")
+}
+
+/// Turn literal text into a regex, escaping everything except placeholders.
+///
+/// A placeholder `(?<name>*)` captures one or more characters up to the
+/// text following it, or up to the end of the line if it is last.
+///
+/// # Examples
+///
+/// ```
+/// use regop::fixed_pattern;
+///
+/// assert_eq!(fixed_pattern("foo.bar=(?<v>*)"), r"foo\.bar=(?<v>.+)");
+/// assert_eq!(fixed_pattern("[(?<v>*)]"), r"\[(?<v>.+?)\]");
+/// ```
+#[must_use]
+pub fn fixed_pattern(s: &str) -> String {
+    let mut pattern = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("(?<") {
+        let after = &rest[start + 3..];
+        let Some((name, tail)) = after.split_once(">*)").filter(|(name, _)| is_name(name)) else {
+            pattern.push_str(&regex::escape(&rest[..start + 3]));
+            rest = after;
+            continue;
+        };
+        let repeat = if tail.is_empty() { "+" } else { "+?" };
+        pattern.push_str(&regex::escape(&rest[..start]));
+        pattern.push_str("(?<");
+        pattern.push_str(name);
+        pattern.push_str(">.");
+        pattern.push_str(repeat);
+        pattern.push(')');
+        rest = tail;
+    }
+    pattern.push_str(&regex::escape(rest));
+    pattern
+}
+
+/// Compile a pattern with `fancy-regex`, the flags are applied inline.
+#[cfg(feature = "fancy")]
+fn fancy_pattern(s: &str, options: &RegexOptions) -> Result<Pattern> {
+    let mut builder = fancy_regex::RegexBuilder::new(&with_flags(s, options));
+    if let Some(limit) = options.size_limit {
+        builder.delegate_size_limit(limit);
+    }
+    if let Some(limit) = options.dfa_size_limit {
+        builder.delegate_dfa_size_limit(limit);
+    }
+    let regex = builder.build().map_err(|e| Error::Regex {
+        pattern: s.to_string(),
+        source: e.into(),
+    })?;
+    Ok(Pattern::Fancy(regex))
+}
+
+/// Prefix a pattern with the flags enabled in the options, e.g. `(?i)`.
+fn with_flags(s: &str, options: &RegexOptions) -> String {
+    let flags = [
+        (options.ignore_case, 'i'),
+        (options.multi_line, 'm'),
+        (options.dot_all, 's'),
+        (options.extended, 'x'),
+    ]
+    .iter()
+    .filter(|(enabled, _)| *enabled)
+    .map(|(_, flag)| *flag)
+    .collect::<String>();
+    if flags.is_empty() {
+        s.to_string()
+    } else {
+        format!("(?{flags}){s}")
+    }
+}
+
+#[cfg(not(feature = "fancy"))]
+const fn fancy_pattern(_: &str, _: &RegexOptions) -> Result<Pattern> {
+    Err(Error::MissingFeature("fancy"))
+}
+
+impl FromStr for Capture {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::with_options(s, &RegexOptions::default())
+    }
+}
+
+/// Names of all capture groups in a regex along with their index. Unnamed
+/// groups are named by their index.
+fn group_names(regex: &Pattern) -> Vec<(usize, String)> {
+    regex
+        .capture_names()
+        .into_iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, n)| (i, n.map_or_else(|| i.to_string(), ToString::to_string)))
+        .collect()
+}
+
 /// An operator that transforms captured values.
 ///
-/// Operators are specified in the format `<target>:operation:parameter` where:
-/// - `target` is the name of a capture group
+/// Operators are specified in the format `<target>[selector]?=guard:operation:parameter` where:
+/// - `target` is the name of a capture group, or a pattern where `*` matches
+///   any characters and `?` a single one, e.g. `<*>` or `<ver_*>`
+/// - `selector` is optional and picks which matches of the target to edit
+/// - `guard` is optional and only edits matches whose value equals it
 /// - `operation` is the transformation to apply
 /// - `parameter` is optional depending on the operation
 ///
+/// Several operations can be chained with `|`, e.g. `<name>:trim|lower|prepend:img_`,
+/// and are applied left to right to the same match.
+///
+/// Numeric guards can be appended to an operator with `?if-gt:N`, `?if-lt:N`,
+/// `?if-ge:N` and `?if-le:N`, so it only edits numbers in a given range.
+///
+/// A limit can be appended with `@max=N`, making processing fail if the
+/// operator would edit more than `N` matches. Guards and the limit can come in
+/// any order.
+///
+/// A parameter extends up to the next `|`, `?if-` or `@max=`, so it can
+/// contain colons, e.g. `<url>:rep:http://example.com`. To include those or
+/// leading and trailing spaces, quote the parameter with `'...'`, taken
+/// literally, or `"..."`, or escape single characters with a backslash, e.g.
+/// `\|` or `\<`. Quoted and escaped parameters are always literal text,
+/// never numbers or captures. A backslash before any other character is kept
+/// as it is.
+///
+/// Unless it is single-quoted, a parameter is a template when it contains
+/// `{name}`, replaced by the value of capture `name` as chosen by
+/// [`Options::value_match`], or `${old}`, replaced by the original value of the target, e.g.
+/// `<full>:rep:{major}.{minor}.0-${old}`. Escape braces and dollar signs with
+/// a backslash to keep them, e.g. `\{name}`. See [`Param::Template`].
+///
+/// An operator is `Send` and `Sync`, so it can be shared across threads.
+///
 /// # Examples
 ///
 /// ```
-/// use regop::Operator;
+/// use regop::{Operator, Param};
 /// use std::str::FromStr;
 ///
 /// let op = Operator::from_str("<version>:inc:5").unwrap();
 /// let swap = Operator::from_str("<major>:swap:<minor>").unwrap();
+/// let second = Operator::from_str("<version>[2]:inc").unwrap();
+/// let last = Operator::from_str("<version>[last]:rep:9").unwrap();
+/// let guarded = Operator::from_str("<env>?=staging:rep:production").unwrap();
+/// let capped = Operator::from_str("<replicas>:inc?if-lt:10").unwrap();
+/// let pipeline = Operator::from_str("<name>:trim|lower|prepend:img_").unwrap();
+/// let limited = Operator::from_str("<ver>:inc@max=3").unwrap();
+/// assert_eq!(limited.max, Some(3));
+/// let url = Operator::from_str("<url>:rep:http://example.com|append:' | <b> '").unwrap();
+/// assert_eq!(format!("{:?}", url.then[0].value), r#"String(" | <b> ")"#);
+/// let template = Operator::from_str("<full>:rep:{major}.{minor}.0-${old}").unwrap();
+/// assert!(matches!(template.value, Param::Template(_)));
 /// ```
 #[derive(Debug, Clone)]
 pub struct Operator {
     /// The name of the capture group to operate on
     pub target: String,
+    /// Which matches of the target to operate on
+    pub select: Selector,
+    /// Predicates the target value must satisfy for the operator to apply
+    pub guards: Vec<Predicate>,
     /// The operation to perform
     pub op: Operation,
     /// The parameter for the operation
     pub value: Param,
+    /// Further steps applied to the result, in order
+    pub then: Vec<Step>,
+    /// Maximum number of matches the operator may edit
+    pub max: Option<usize>,
 }
 
-/// Available operations for transforming captured values.
+/// A single step of an operator pipeline.
 #[derive(Debug, Clone)]
-pub enum Operation {
-    /// Increment a number (default: by 1)
-    Inc,
-    /// Decrement a number (default: by 1)
-    Dec,
-    /// Replace with a new value
-    Replace,
-    /// Delete the captured value
-    Del,
-    /// Swap with another capture group
-    Swap,
-    /// Multiply a number
-    Mul,
-    /// Divide a number
-    Div,
-    /// Append text to the end
-    Append,
-    /// Prepend text to the beginning
-    Prepend,
-    /// Convert to uppercase
-    Upper,
-    /// Convert to lowercase
-    Lower,
+pub struct Step {
+    /// The operation to perform
+    pub op: Operation,
+    /// The parameter for the operation
+    pub value: Param,
 }
 
-/// Parameter types for operations.
-#[derive(Debug, Clone)]
-pub enum Param {
-    /// An integer parameter
-    Int(isize),
-    /// A string parameter
-    String(String),
-    /// A reference to another capture group
-    Capture(String),
+/// Selects which matches of a capture group an operator is applied to.
+///
+/// Matches are counted from `1` in the order they appear in the content.
+/// Ranges are inclusive and either bound can be left out, so `2..5` selects
+/// the second through fifth match and `..3` the first three.
+///
+/// # Examples
+///
+/// ```
+/// use regop::Selector;
+///
+/// let matches = ["a", "b", "c"];
+/// assert_eq!(Selector::All.select(&matches), &["a", "b", "c"]);
+/// assert_eq!(Selector::Index(2).select(&matches), &["b"]);
+/// assert_eq!(Selector::Last.select(&matches), &["c"]);
+/// assert_eq!("2..".parse::<Selector>().unwrap().select(&matches), &["b", "c"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Selector {
+    /// Every match
+    #[default]
+    All,
+    /// The n-th match, counting from `1`
+    Index(usize),
+    /// The last match
+    Last,
+    /// An inclusive range of matches, counting from `1`, without an upper
+    /// bound if `end` is `None`
+    Range {
+        /// The first match in the range
+        start: usize,
+        /// The last match in the range
+        end: Option<usize>,
+    },
 }
 
-#[allow(clippy::unwrap_used)]
-impl From<&str> for Param {
-    fn from(value: &str) -> Self {
-        value.parse::<isize>().map_or_else(
-            |_| {
-                let re = Regex::new(r"<([^>]+)>").unwrap();
-                re.captures(value).map_or_else(
-                    || Self::String(value.to_string()),
-                    |m| Self::Capture(m.get(1).unwrap().as_str().to_string()),
-                )
-            },
-            Self::Int,
-        )
+impl Selector {
+    /// Narrow a list of matches down to the selected ones.
+    #[must_use]
+    pub fn select<'a, T>(&self, matches: &'a [T]) -> &'a [T] {
+        match self {
+            Self::All => matches,
+            Self::Index(n) => n
+                .checked_sub(1)
+                .and_then(|i| matches.get(i..*n))
+                .unwrap_or_default(),
+            Self::Last => matches.len().checked_sub(1).map_or(&[], |i| &matches[i..]),
+            Self::Range { start, end } => {
+                let end = end.map_or(matches.len(), |e| e.min(matches.len()));
+                start
+                    .checked_sub(1)
+                    .and_then(|i| matches.get(i..end))
+                    .unwrap_or_default()
+            }
+        }
     }
 }
 
-impl FromStr for Operator {
-    type Err = anyhow::Error;
+impl FromStr for Selector {
+    type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let re = Regex::new(r"<([^>]+)>:([^:]+):?([^:]+)?")?;
-        let m = re
-            .captures(s)
-            .ok_or_else(|| anyhow!(format!("'{s}' not a valid operator format")))?;
-        ensure!(m.len() == 4, format!("'{s}' not a valid operator format"));
+        let parse = |i: &str| -> Result<usize> {
+            let n = i
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| Error::Parse(format!("'{s}' is not a valid match selector")))?;
+            if n == 0 {
+                return Err(Error::Parse("match selector counts from 1".to_string()));
+            }
+            Ok(n)
+        };
+        let bound = |i: &str| (!i.trim().is_empty()).then(|| parse(i)).transpose();
+
+        if s.trim() == "last" {
+            Ok(Self::Last)
+        } else if let Some((start, end)) = s.split_once("..") {
+            let start = bound(start)?.unwrap_or(1);
+            let end = bound(end)?;
+            if end.is_some_and(|e| e < start) {
+                return Err(Error::Parse(format!("'{s}' is an empty match range")));
+            }
+            Ok(Self::Range { start, end })
+        } else {
+            Ok(Self::Index(parse(s)?))
+        }
+    }
+}
 
-        let target = m
-            .get(1)
-            .ok_or_else(|| anyhow!("no target in operator"))?
-            .as_str()
-            .to_string();
+/// A test on a captured value.
+#[derive(Debug, Clone)]
+pub enum Predicate {
+    /// The value equals the given string
+    Equals(String),
+    /// The value matches the given regex
+    Matches(Regex),
+    /// The value is a number greater than the given one
+    Gt(isize),
+    /// The value is a number less than the given one
+    Lt(isize),
+    /// The value is a number greater than or equal to the given one
+    Ge(isize),
+    /// The value is a number less than or equal to the given one
+    Le(isize),
+}
 
-        let param = m.get(3).map(|p| Param::from(p.as_str()));
+impl Predicate {
+    /// Test a captured value against this predicate.
+    #[must_use]
+    pub fn test(&self, value: &str) -> bool {
+        match self {
+            Self::Equals(s) => value == s,
+            Self::Matches(re) => re.is_match(value),
+            Self::Gt(n) => parse_value(value).is_ok_and(|v| v > int(*n)),
+            Self::Lt(n) => parse_value(value).is_ok_and(|v| v < int(*n)),
+            Self::Ge(n) => parse_value(value).is_ok_and(|v| v >= int(*n)),
+            Self::Le(n) => parse_value(value).is_ok_and(|v| v <= int(*n)),
+        }
+    }
+}
 
-        Ok(
-            match m
-                .get(2)
-                .ok_or_else(|| anyhow!("no operation in operator"))?
-                .as_str()
-            {
-                "inc" => Self {
-                    target,
-                    op: Operation::Inc,
-                    value: param.unwrap_or(Param::Int(1)),
-                },
-                "dec" => Self {
-                    target,
-                    op: Operation::Dec,
-                    value: param.unwrap_or(Param::Int(1)),
-                },
-                "rep" => Self {
-                    target,
-                    op: Operation::Replace,
-                    value: param.ok_or_else(|| anyhow!("parameter required in 'rep' operator"))?,
-                },
-                "del" => Self {
-                    target,
-                    op: Operation::Del,
-                    value: Param::Int(0),
-                },
-                "swap" => Self {
-                    target,
-                    op: Operation::Swap,
-                    value: param.ok_or_else(|| anyhow!("parameter required in 'swap' operator"))?,
-                },
-                "mul" => Self {
-                    target,
-                    op: Operation::Mul,
-                    value: param.ok_or_else(|| anyhow!("parameter required in 'mul' operator"))?,
-                },
-                "div" => Self {
-                    target,
-                    op: Operation::Div,
-                    value: param.ok_or_else(|| anyhow!("parameter required in 'div' operator"))?,
-                },
-                "append" => Self {
-                    target,
-                    op: Operation::Append,
-                    value: param
-                        .ok_or_else(|| anyhow!("parameter required in 'append' operator"))?,
-                },
-                "prepend" => Self {
-                    target,
-                    op: Operation::Prepend,
-                    value: param
-                        .ok_or_else(|| anyhow!("parameter required in 'prepend' operator"))?,
-                },
-                "upper" => Self {
-                    target,
-                    op: Operation::Upper,
-                    value: Param::Int(0),
-                },
-                "lower" => Self {
-                    target,
-                    op: Operation::Lower,
-                    value: Param::Int(0),
-                },
-                o => {
-                    bail!(format!("'{o}' is not a valid operator"))
-                }
-            },
-        )
+impl FromStr for Predicate {
+    type Err = Error;
+
+    /// Parse a numeric comparison in the format `if-lt:10`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (cmp, value) = s
+            .split_once(':')
+            .ok_or_else(|| Error::Parse(format!("'{s}' not a valid guard format")))?;
+        let value = parse_int(value)?;
+        Ok(match cmp {
+            "if-gt" => Self::Gt(value),
+            "if-lt" => Self::Lt(value),
+            "if-ge" => Self::Ge(value),
+            "if-le" => Self::Le(value),
+            c => return Err(Error::Parse(format!("'{c}' is not a valid guard"))),
+        })
     }
 }
 
-/// Process content with the given captures and operators.
-///
-/// This is the main entry point for applying transformations to text.
-///
-/// # Arguments
-///
-/// * `lines` - If true, process each line independently
-/// * `regex` - List of capture patterns to match
-/// * `ops` - List of operators to apply to captures
-/// * `content` - The text content to process
-///
-/// # Returns
+/// A predicate on the value of a named capture.
 ///
-/// Returns `Some(String)` with transformed content if any changes were made,
-/// or `None` if no matches were found.
+/// Conditions are specified in the format `<target>=value` to test for
+/// equality, or `<target>~regex` to test against a regex. When a condition
+/// is evaluated for a match, the nearest match of `target` is tested.
 ///
 /// # Examples
 ///
 /// ```
-/// use regop::{Capture, Operator, process};
+/// use regop::{Condition, Predicate};
 /// use std::str::FromStr;
 ///
-/// let capture = Capture::from_str("value = (?<num>\\d+)").unwrap();
-/// let op = Operator::from_str("<num>:inc").unwrap();
-/// let content = "value = 42".to_string();
+/// let condition = Condition::from_str("<env>=staging").unwrap();
+/// assert_eq!(condition.target, "env");
+/// assert!(matches!(condition.predicate, Predicate::Equals(v) if v == "staging"));
 ///
-/// let result = process(false, &[capture], &[op], content).unwrap();
-/// assert_eq!(result, Some("value = 43".to_string()));
+/// let condition = Condition::from_str(r"<version>~^0\.").unwrap();
+/// assert!(condition.predicate.test("0.4.1"));
+/// assert!(!condition.predicate.test("1.0.0"));
 /// ```
-pub fn process(
-    lines: bool,
-    regex: &[Capture],
-    ops: &[Operator],
-    mut content: String,
-) -> anyhow::Result<Option<String>> {
-    if lines {
-        let mut change = false;
-
-        for line in content.clone().lines() {
-            if let Some(new_line) = regop(regex, ops, line.to_string())? {
-                change = true;
-                let start = content
-                    .find(line)
-                    .ok_or_else(|| anyhow!("unable to find line index"))?;
-                content.replace_range(start..start + line.len(), &new_line);
-            }
-        }
+#[derive(Debug, Clone)]
+pub struct Condition {
+    /// The name of the capture group to test
+    pub target: String,
+    /// The predicate the capture value must satisfy
+    pub predicate: Predicate,
+}
 
-        if change { Ok(Some(content)) } else { Ok(None) }
-    } else {
-        regop(regex, ops, content)
+impl FromStr for Condition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let re = compile(r"^<([^>]+)>([=~])(.*)$")?;
+        let m = re
+            .captures(s)
+            .ok_or_else(|| Error::Parse(format!("'{s}' not a valid condition format")))?;
+        let target = m
+            .get(1)
+            .ok_or_else(|| Error::Parse("no target in condition".to_string()))?
+            .as_str()
+            .to_string();
+        let value = m
+            .get(3)
+            .ok_or_else(|| Error::Parse("no value in condition".to_string()))?
+            .as_str();
+        let predicate = match m.get(2).map(|o| o.as_str()) {
+            Some("~") => Predicate::Matches(compile(value)?),
+            _ => Predicate::Equals(value.to_string()),
+        };
+        Ok(Self { target, predicate })
     }
 }
 
-/// Apply regex captures and operators to content.
+/// An expected number of matches for a named capture.
 ///
-/// This function handles the core logic of finding matches and applying transformations.
-/// Unlike `process`, this always treats the content as a single unit.
+/// Expectations are specified in the format `<target>=N` for an exact count,
+/// or `<target>=MIN..MAX` for an inclusive range where either bound can be
+/// left out.
 ///
-/// # Arguments
+/// # Examples
 ///
-/// * `regex` - List of capture patterns to match
-/// * `ops` - List of operators to apply to captures
-/// * `content` - The text content to process
+/// ```
+/// use regop::Expectation;
+/// use std::str::FromStr;
 ///
-/// # Returns
+/// let exact = Expectation::from_str("<version>=1").unwrap();
+/// assert!(exact.check(1).is_ok());
+/// assert!(exact.check(2).is_err());
 ///
-/// Returns `Some(String)` with transformed content if any changes were made,
-/// or `None` if no matches were found.
-pub fn regop(
-    regex: &[Capture],
-    ops: &[Operator],
-    mut content: String,
-) -> anyhow::Result<Option<String>> {
-    let captures = collect_all_captures(regex, &content);
+/// let range = Expectation::from_str("<dep>=2..").unwrap();
+/// assert!(range.check(5).is_ok());
+/// assert!(range.check(1).is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expectation {
+    /// The name of the capture group to count
+    pub target: String,
+    /// The minimum number of matches
+    pub min: usize,
+    /// The maximum number of matches, unbounded if `None`
+    pub max: Option<usize>,
+}
 
-    // Validate that all captures used as values exist
-    for op in ops {
-        if let Param::Capture(name) = &op.value
-            && !matches!(op.op, Operation::Swap)
-        {
-            ensure!(
-                captures.contains_key(name),
-                format!("'<{name}>' used as value but not found")
-            );
+impl Expectation {
+    /// Check a number of matches against this expectation.
+    pub fn check(&self, count: usize) -> Result<()> {
+        if count >= self.min && self.max.is_none_or(|max| count <= max) {
+            return Ok(());
         }
+        let expected = match self.max {
+            Some(max) if max == self.min => format!("{max}"),
+            Some(max) => format!("{}..{max}", self.min),
+            None => format!("{}..", self.min),
+        };
+        Err(Error::Expectation {
+            target: self.target.clone(),
+            expected,
+            found: count,
+        })
     }
+}
 
-    let mut edits = collect_edits(ops, &captures)?;
-
-    apply_edits(&mut content, &mut edits)?;
+impl FromStr for Expectation {
+    type Err = Error;
 
-    if edits.is_empty() {
-        Ok(None)
-    } else {
-        Ok(Some(content))
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let re = compile(r"^<([^>]+)>=(\d*)(\.\.)?(\d*)$")?;
+        let m = re
+            .captures(s)
+            .ok_or_else(|| Error::Parse(format!("'{s}' not a valid expectation format")))?;
+        let target = m
+            .get(1)
+            .ok_or_else(|| Error::Parse("no target in expectation".to_string()))?
+            .as_str()
+            .to_string();
+        let bound = |i: usize| {
+            m.get(i)
+                .map(|b| b.as_str())
+                .filter(|b| !b.is_empty())
+                .map(str::parse::<usize>)
+                .transpose()
+                .map_err(|_| Error::Parse(format!("'{s}' not a valid expectation format")))
+        };
+        let (min, max) = if m.get(3).is_some() {
+            (bound(2)?.unwrap_or(0), bound(4)?)
+        } else {
+            let n =
+                bound(2)?.ok_or_else(|| Error::Parse(format!("'{s}' has no expected count")))?;
+            (n, Some(n))
+        };
+        if max.is_some_and(|max| max < min) {
+            return Err(Error::Parse(format!("'{s}' is an empty range")));
+        }
+        Ok(Self { target, min, max })
     }
 }
 
-/// Collect all named captures from the provided regexes.
-fn collect_all_captures<'a>(regex: &[Capture], content: &'a str) -> CapturesMap<'a> {
-    let mut captures: CapturesMap = HashMap::new();
+/// An expected type for the values of a named capture.
+///
+/// Type checks are specified in the format `<target>:type`, optionally
+/// followed by an inclusive range `:MIN..MAX` where either bound can be left
+/// out. The only supported type is `int`.
+///
+/// # Examples
+///
+/// ```
+/// use regop::TypeCheck;
+/// use std::str::FromStr;
+///
+/// let port = TypeCheck::from_str("<port>:int:1..65535").unwrap();
+/// assert!(port.check("8080").is_ok());
+/// assert!(port.check("0").is_err());
+/// assert!(port.check("http").is_err());
+///
+/// let offset = TypeCheck::from_str("<offset>:int").unwrap();
+/// assert!(offset.check("-3").is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeCheck {
+    /// The name of the capture group to check
+    pub target: String,
+    /// The type all values of the capture must have
+    pub kind: ValueType,
+}
+
+/// Types a capture value can be checked against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueType {
+    /// An integer within an inclusive range, unbounded where `None`
+    Int {
+        /// The smallest allowed value
+        min: Option<isize>,
+        /// The largest allowed value
+        max: Option<isize>,
+    },
+}
 
-    for cap in regex {
-        for m in cap.regex.captures_iter(content) {
-            for name in &cap.names {
-                if let Some(m) = m.name(name) {
-                    captures.entry(name.clone()).or_default().push((
-                        m.start(),
-                        m.end(),
-                        m.as_str(),
-                    ));
+impl TypeCheck {
+    /// Check that a value of the capture has the expected type.
+    pub fn check(&self, value: &str) -> Result<()> {
+        let mismatch = |expected: String| Error::Type {
+            target: self.target.clone(),
+            value: value.to_string(),
+            expected,
+        };
+        match self.kind {
+            ValueType::Int { min, max } => {
+                let n = parse_value(value).map_err(|_| mismatch("an int".to_string()))?;
+                if !(min.is_none_or(|min| n >= int(min)) && max.is_none_or(|max| n <= int(max))) {
+                    return Err(mismatch(format!(
+                        "in range {}..{}",
+                        min.map(|m| m.to_string()).unwrap_or_default(),
+                        max.map(|m| m.to_string()).unwrap_or_default(),
+                    )));
                 }
             }
         }
+        Ok(())
     }
-
-    captures
 }
 
-/// Collect all edit operations to be applied to the content.
-///
-/// This processes all operators and regex matches to build a list of
-/// text transformations to apply.
-fn collect_edits(ops: &[Operator], captures: &CapturesMap) -> anyhow::Result<Vec<Edit>> {
-    let mut edits = Vec::new();
+impl FromStr for TypeCheck {
+    type Err = Error;
 
-    for op in ops {
-        if matches!(op.op, Operation::Swap) {
-            collect_swap_edits(op, captures, &mut edits)?;
-        } else {
-            collect_regular_edits(op, captures, &mut edits)?;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let re = compile(r"^<([^>]+)>:([^:]+)(?::(-?\d*)\.\.(-?\d*))?$")?;
+        let m = re
+            .captures(s)
+            .ok_or_else(|| Error::Parse(format!("'{s}' not a valid type format")))?;
+        let target = m
+            .get(1)
+            .ok_or_else(|| Error::Parse("no target in type".to_string()))?
+            .as_str()
+            .to_string();
+        let bound = |i: usize| {
+            m.get(i)
+                .map(|b| b.as_str())
+                .filter(|b| !b.is_empty())
+                .map(parse_int)
+                .transpose()
+        };
+        let kind = match m.get(2).map(|t| t.as_str()) {
+            Some("int") => ValueType::Int {
+                min: bound(3)?,
+                max: bound(4)?,
+            },
+            Some(t) => {
+                return Err(Error::Parse(format!(
+                    "'{t}' is not a supported type, expected 'int'"
+                )));
+            }
+            None => return Err(Error::Parse(format!("no type in '{s}'"))),
+        };
+        let ValueType::Int { min, max } = kind;
+        if min.zip(max).is_some_and(|(min, max)| max < min) {
+            return Err(Error::Parse(format!("'{s}' is an empty range")));
         }
+        Ok(Self { target, kind })
     }
+}
 
-    Ok(edits)
+/// Available operations for transforming captured values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    /// Increment a number (default: by 1)
+    Inc,
+    /// Decrement a number (default: by 1)
+    Dec,
+    /// Replace with a new value
+    Replace,
+    /// Delete the captured value
+    Del,
+    /// Swap with another capture group
+    Swap,
+    /// Multiply a number
+    Mul,
+    /// Divide a number
+    Div,
+    /// Append text to the end
+    Append,
+    /// Prepend text to the beginning
+    Prepend,
+    /// Convert to uppercase, optionally using a locale
+    Upper,
+    /// Convert to lowercase, optionally using a locale
+    Lower,
+    /// Convert a number to its ordinal form (`1st`, `2nd`, ...)
+    Ordinal,
+    /// Remove leading and trailing whitespace
+    Trim,
 }
 
-/// Collect edit operations for swap operators.
-///
-/// Swap operations are special because they need to exchange values between
-/// two capture groups, requiring coordinated edits.
-fn collect_swap_edits(
-    op: &Operator,
-    captures: &CapturesMap,
-    edits: &mut Vec<Edit>,
-) -> anyhow::Result<()> {
-    let swap_target = match &op.value {
-        Param::String(s) => s.clone(),
-        Param::Capture(c) => c.clone(),
-        Param::Int(i) => format!("{i}"),
-    };
+impl Operation {
+    /// Metadata about every operation, in the order they are documented.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use regop::{Arity, Operation};
+    ///
+    /// let inc = Operation::all().iter().find(|info| info.name == "inc").unwrap();
+    /// assert_eq!(inc.operation, Operation::Inc);
+    /// assert_eq!(inc.arity, Arity::Optional);
+    /// assert_eq!(inc.default, Some("1"));
+    /// ```
+    #[must_use]
+    pub const fn all() -> &'static [OperationInfo] {
+        OPERATIONS
+    }
 
-    let source_matches = captures.get(&op.target).cloned().unwrap_or_default();
-    let target_matches = captures.get(&swap_target).cloned().unwrap_or_default();
+    /// Metadata about this operation.
+    #[must_use]
+    pub fn info(&self) -> Option<&'static OperationInfo> {
+        OPERATIONS.iter().find(|info| info.operation == *self)
+    }
+}
 
-    ensure!(
-        source_matches.len() == target_matches.len(),
-        format!(
-            "Cannot swap '{}' and '{}': different number of matches ({} vs {})",
-            op.target,
-            swap_target,
-            source_matches.len(),
-            target_matches.len()
-        )
-    );
+/// Metadata about an operation, see [`Operation::all`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperationInfo {
+    /// The operation
+    pub operation: Operation,
+    /// Name of the operation in operators, e.g. `inc`
+    pub name: &'static str,
+    /// Short description of what the operation does
+    pub description: &'static str,
+    /// Whether the operation takes a parameter
+    pub arity: Arity,
+    /// The parameter used when an optional one is left out
+    pub default: Option<&'static str>,
+    /// The kinds of parameters the operation accepts
+    pub params: &'static [ParamType],
+    /// Example operators using the operation
+    pub examples: &'static [&'static str],
+}
 
-    // Create edits for swapping
-    for (source, target) in source_matches.iter().zip(target_matches.iter()) {
-        edits.push(Edit {
-            start: source.0,
-            end: source.1,
-            new: target.2.to_string(),
-        });
-        edits.push(Edit {
-            start: target.0,
-            end: target.1,
-            new: source.2.to_string(),
-        });
+/// Whether an operation takes a parameter, see [`OperationInfo`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    /// The operation takes no parameter, one given is ignored
+    None,
+    /// The parameter can be left out
+    Optional,
+    /// The parameter must be given
+    Required,
+}
+
+/// A kind of parameter an operation accepts, see [`OperationInfo`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamType {
+    /// An integer, e.g. `5`
+    Int,
+    /// Any text
+    String,
+    /// A reference to another capture group, e.g. `<b>`
+    Capture,
+    /// Text interpolating captures and the original value, e.g. `{b}-${old}`
+    Template,
+    /// A locale with special casing rules, e.g. `tr`
+    Locale,
+}
+
+impl std::fmt::Display for ParamType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Int => "int",
+            Self::String => "string",
+            Self::Capture => "<capture>",
+            Self::Template => "template",
+            Self::Locale => "locale",
+        })
     }
+}
 
-    Ok(())
+/// Metadata about every operation, see [`Operation::all`].
+const OPERATIONS: &[OperationInfo] = &[
+    OperationInfo {
+        operation: Operation::Inc,
+        name: "inc",
+        description: "Increment number",
+        arity: Arity::Optional,
+        default: Some("1"),
+        params: &[ParamType::Int, ParamType::Capture],
+        examples: &["<a>:inc", "<a>:inc:5", "<a>:inc:<b>"],
+    },
+    OperationInfo {
+        operation: Operation::Dec,
+        name: "dec",
+        description: "Decrement number",
+        arity: Arity::Optional,
+        default: Some("1"),
+        params: &[ParamType::Int, ParamType::Capture],
+        examples: &["<a>:dec", "<a>:dec:5", "<a>:dec:<b>"],
+    },
+    OperationInfo {
+        operation: Operation::Mul,
+        name: "mul",
+        description: "Multiply number",
+        arity: Arity::Required,
+        default: None,
+        params: &[ParamType::Int, ParamType::Capture],
+        examples: &["<a>:mul:3", "<a>:mul:<b>"],
+    },
+    OperationInfo {
+        operation: Operation::Div,
+        name: "div",
+        description: "Divide number",
+        arity: Arity::Required,
+        default: None,
+        params: &[ParamType::Int, ParamType::Capture],
+        examples: &["<a>:div:2", "<a>:div:<b>"],
+    },
+    OperationInfo {
+        operation: Operation::Replace,
+        name: "rep",
+        description: "Replace",
+        arity: Arity::Required,
+        default: None,
+        params: &[ParamType::String, ParamType::Capture, ParamType::Template],
+        examples: &["<a>:rep:mystring", "<a>:rep:<b>", "<a>:rep:{b}-${old}"],
+    },
+    OperationInfo {
+        operation: Operation::Del,
+        name: "del",
+        description: "Delete",
+        arity: Arity::None,
+        default: None,
+        params: &[],
+        examples: &["<a>:del"],
+    },
+    OperationInfo {
+        operation: Operation::Swap,
+        name: "swap",
+        description: "Swap with another",
+        arity: Arity::Required,
+        default: None,
+        params: &[ParamType::Capture],
+        examples: &["<a>:swap:<b>"],
+    },
+    OperationInfo {
+        operation: Operation::Append,
+        name: "append",
+        description: "Append text",
+        arity: Arity::Required,
+        default: None,
+        params: &[ParamType::String, ParamType::Int, ParamType::Template],
+        examples: &["<a>:append:_suffix", "<a>:append:123", "<a>:append:-{b}"],
+    },
+    OperationInfo {
+        operation: Operation::Prepend,
+        name: "prepend",
+        description: "Prepend text",
+        arity: Arity::Required,
+        default: None,
+        params: &[ParamType::String, ParamType::Int, ParamType::Template],
+        examples: &["<a>:prepend:prefix_", "<a>:prepend:v", "<a>:prepend:{b}/"],
+    },
+    OperationInfo {
+        operation: Operation::Upper,
+        name: "upper",
+        description: "Convert to uppercase",
+        arity: Arity::Optional,
+        default: None,
+        params: &[ParamType::Locale],
+        examples: &["<a>:upper", "<a>:upper:tr"],
+    },
+    OperationInfo {
+        operation: Operation::Lower,
+        name: "lower",
+        description: "Convert to lowercase",
+        arity: Arity::Optional,
+        default: None,
+        params: &[ParamType::Locale],
+        examples: &["<a>:lower", "<a>:lower:tr"],
+    },
+    OperationInfo {
+        operation: Operation::Ordinal,
+        name: "ordinal",
+        description: "Number to ordinal",
+        arity: Arity::None,
+        default: None,
+        params: &[],
+        examples: &["<a>:ordinal"],
+    },
+    OperationInfo {
+        operation: Operation::Trim,
+        name: "trim",
+        description: "Trim whitespace",
+        arity: Arity::None,
+        default: None,
+        params: &[],
+        examples: &["<a>:trim"],
+    },
+];
+
+/// Parameter types for operations.
+#[derive(Debug, Clone)]
+pub enum Param {
+    /// An integer parameter
+    Int(isize),
+    /// A string parameter
+    String(String),
+    /// A reference to another capture group
+    Capture(String),
+    /// Text interpolating the values of other capture groups and the
+    /// original value of the target
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use regop::{Capture, Operator, process};
+    /// use std::str::FromStr;
+    ///
+    /// let capture = Capture::from_str(r"(?<full>(?<major>\d+)\.(?<minor>\d+)\.\d+)").unwrap();
+    /// let op = Operator::from_str("<full>:rep:{major}.{minor}.0-${old}").unwrap();
+    /// let result = process(false, &[capture], &[op], "1.2.3".to_string()).unwrap();
+    /// assert_eq!(result, Some("1.2.0-1.2.3".to_string()));
+    /// ```
+    Template(Vec<Piece>),
 }
 
-/// Collect edit operations for non-swap operators.
-///
-/// Processes standard operators like increment, replace, append, etc.
-fn collect_regular_edits(
-    op: &Operator,
-    captures: &CapturesMap,
-    edits: &mut Vec<Edit>,
-) -> anyhow::Result<()> {
-    if let Some(matches) = captures.get(&op.target) {
-        for (start, end, val) in matches {
-            edits.push(edit(op, *start, *end, val, captures)?);
+/// A piece of a [`Param::Template`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Piece {
+    /// Literal text
+    Text(String),
+    /// The value of a capture group, `{name}`, see [`ValueMatch`]
+    Capture(String),
+    /// The original value of the target, `${old}`
+    Old,
+}
+
+impl std::fmt::Display for Piece {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text(text) => f.write_str(text),
+            Self::Capture(name) => write!(f, "{{{name}}}"),
+            Self::Old => f.write_str("${old}"),
         }
     }
-    Ok(())
 }
 
-/// Apply all collected edits to the content.
-///
-/// Edits are sorted and applied in reverse order to maintain correct positions.
-/// This function also validates that edits don't overlap.
-fn apply_edits(content: &mut String, edits: &mut Vec<Edit>) -> anyhow::Result<()> {
-    edits.sort_by_key(|e| e.start);
-    edits.reverse();
-    for ed in edits.windows(2) {
-        distance(ed[0].start, ed[0].end, ed[1].start, ed[1].end)
-            .ok_or_else(|| anyhow!("edits overlap each other"))?;
+impl Param {
+    /// Names of the capture groups the parameter references.
+    fn captures(&self) -> impl Iterator<Item = &str> {
+        let (capture, pieces) = match self {
+            Self::Capture(name) => (Some(name.as_str()), &[][..]),
+            Self::Template(pieces) => (None, pieces.as_slice()),
+            Self::Int(_) | Self::String(_) => (None, &[][..]),
+        };
+        capture
+            .into_iter()
+            .chain(pieces.iter().filter_map(|piece| match piece {
+                Piece::Capture(name) => Some(name.as_str()),
+                Piece::Text(_) | Piece::Old => None,
+            }))
     }
+}
+
+/// The text of a template as it would be written in an operator.
+fn template_source(pieces: &[Piece]) -> String {
+    pieces.iter().map(ToString::to_string).collect()
+}
 
-    for ed in edits {
-        content.replace_range(ed.start..ed.end, &ed.new);
+/// A template of pieces, or plain text if it references nothing.
+fn template(pieces: impl IntoIterator<Item = Piece>) -> Param {
+    let mut merged = Vec::new();
+    for piece in pieces {
+        match (merged.last_mut(), piece) {
+            (Some(Piece::Text(text)), Piece::Text(more)) => text.push_str(&more),
+            (_, piece) => merged.push(piece),
+        }
     }
+    match merged.as_slice() {
+        [] => Param::String(String::new()),
+        [Piece::Text(text)] => Param::String(text.clone()),
+        _ => Param::Template(merged),
+    }
+}
 
-    Ok(())
+impl From<&str> for Param {
+    fn from(value: &str) -> Self {
+        if let Ok(i) = value.parse::<isize>() {
+            return Self::Int(i);
+        }
+        // The first `<name>` anywhere in the value references a capture
+        value
+            .match_indices('<')
+            .find_map(|(i, _)| {
+                let rest = &value[i + 1..];
+                rest.find('>')
+                    .filter(|&end| end > 0)
+                    .map(|end| &rest[..end])
+            })
+            .map_or_else(
+                || Self::String(value.to_string()),
+                |name| Self::Capture(name.to_string()),
+            )
+    }
 }
 
-/// Represents a single text edit operation.
-///
-/// Edits are applied to the content after all matches are found to ensure
-/// non-overlapping changes.
-pub struct Edit {
-    /// Start position of the text to replace
-    pub start: usize,
-    /// End position of the text to replace
-    pub end: usize,
-    /// The new text to insert
-    pub new: String,
+impl FromStr for Operator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let re = compile(r"<([^>]+)>(?:\[([^\]]+)\])?(?:\?=([^:]*))?:(.)")?;
+        let m = re
+            .captures(s)
+            .filter(|m| m.len() == 5)
+            .ok_or_else(|| Error::Parse(format!("'{s}' not a valid operator format")))?;
+
+        let target = m
+            .get(1)
+            .ok_or_else(|| Error::Parse("no target in operator".to_string()))?
+            .as_str()
+            .to_string();
+
+        let select = m
+            .get(2)
+            .map_or(Ok(Selector::All), |sel| sel.as_str().parse())?;
+
+        let mut guards = m
+            .get(3)
+            .map(|g| Predicate::Equals(g.as_str().to_string()))
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let start = m
+            .get(4)
+            .ok_or_else(|| Error::Parse("no operation in operator".to_string()))?
+            .start();
+        let (steps, mut rest) = parse_steps(&s[start..])?;
+        let mut max = None;
+        while !rest.is_empty() {
+            let suffix = rest[1..].find(['?', '@']).map_or(rest.len(), |end| end + 1);
+            let (suffix, after) = rest.split_at(suffix);
+            if let Some(limit) = suffix.strip_prefix("@max=") {
+                if max.is_some() {
+                    return Err(Error::Parse(format!("more than one match limit in '{s}'")));
+                }
+                max =
+                    Some(limit.parse::<usize>().map_err(|_| {
+                        Error::Parse(format!("'{limit}' is not a valid match limit"))
+                    })?);
+            } else if let Some(guard) = suffix.strip_prefix('?') {
+                guards.push(guard.parse()?);
+            } else {
+                return Err(Error::Parse(format!(
+                    "'{suffix}' not a valid operator suffix"
+                )));
+            }
+            rest = after;
+        }
+
+        let mut steps = steps.into_iter();
+        let Step { op, value } = steps
+            .next()
+            .ok_or_else(|| Error::Parse("no operation in operator".to_string()))?;
+        let then = steps.collect::<Vec<_>>();
+        if std::iter::once(&op)
+            .chain(then.iter().map(|step| &step.op))
+            .any(|op| matches!(op, Operation::Swap))
+            && !then.is_empty()
+        {
+            return Err(Error::Parse(
+                "'swap' operator cannot be used in a pipeline".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            target,
+            select,
+            guards,
+            op,
+            value,
+            then,
+            max,
+        })
+    }
 }
 
-/// Create an edit operation from a regex match and operator.
-///
-/// This function determines what text transformation to apply based on the
-/// operator type and its parameters.
-///
-/// # Arguments
-///
-/// * `op` - The operator to apply
-/// * `start` - Start position of the match
-/// * `end` - End position of the match
-/// * `old` - The original matched text
-/// * `captures` - Map of all captured values (for operations using capture references)
-///
-/// # Returns
-///
-/// Returns an `Edit` struct describing the transformation to apply.
-pub fn edit<'a>(
-    op: &Operator,
-    start: usize,
-    end: usize,
-    old: &'a str,
-    captures: &CapturesMap<'a>,
-) -> anyhow::Result<Edit> {
-    let value = match &op.value {
-        Param::Capture(name) => {
-            let c = captures.get(name).map(|v| {
-                let mut c = v
-                    .iter()
-                    .map(|c| (distance(start, end, c.0, c.1), c.2))
-                    .collect::<Vec<_>>();
-                c.sort_by_key(|c| c.0);
-                #[allow(clippy::unwrap_used)]
-                c.first().unwrap().1 // It is safe to unwrap here
-            });
-            Param::String(
-                c.ok_or_else(|| anyhow!(format!("no capture found named '{name}'")))?
-                    .to_string(),
-            )
+impl Operator {
+    /// Substitute variables into the parameters of the operator.
+    ///
+    /// Wherever `{name}` appears in a template parameter it is replaced by
+    /// the value of the variable `name`. References to names that are not
+    /// variables are left to be interpolated from captures, see
+    /// [`Param::Template`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use regop::Operator;
+    /// use std::collections::HashMap;
+    /// use std::str::FromStr;
+    ///
+    /// let vars = HashMap::from([("new_version".to_string(), "2.0.0".to_string())]);
+    /// let op = Operator::from_str("<version>:rep:v{new_version}").unwrap();
+    /// assert_eq!(format!("{:?}", op.with_vars(&vars).value), r#"String("v2.0.0")"#);
+    /// ```
+    #[must_use]
+    pub fn with_vars<S: BuildHasher>(&self, vars: &HashMap<String, String, S>) -> Self {
+        let substitute = |param: &Param| match param {
+            Param::Template(pieces) => template(pieces.iter().map(|piece| match piece {
+                Piece::Capture(name) if vars.contains_key(name) => Piece::Text(vars[name].clone()),
+                piece => piece.clone(),
+            })),
+            param => param.clone(),
+        };
+        Self {
+            value: substitute(&self.value),
+            then: self
+                .then
+                .iter()
+                .map(|step| Step {
+                    op: step.op.clone(),
+                    value: substitute(&step.value),
+                })
+                .collect(),
+            ..self.clone()
         }
-        v => v.clone(),
+    }
+}
+
+/// Parse a single operation and its parameter.
+fn parse_step(name: &str, param: Option<Param>) -> Result<Step> {
+    let required = |param: Option<Param>| {
+        param.ok_or_else(|| Error::Parse(format!("parameter required in '{name}' operator")))
+    };
+    let info = OPERATIONS
+        .iter()
+        .find(|info| info.name == name)
+        .ok_or_else(|| Error::Parse(format!("'{name}' is not a valid operator")))?;
+    let value = match info.arity {
+        Arity::None => Param::Int(0),
+        Arity::Required => required(param)?,
+        Arity::Optional if info.params.contains(&ParamType::Locale) => locale_param(name, param)?,
+        Arity::Optional => param
+            .or_else(|| info.default.map(Param::from))
+            .unwrap_or(Param::Int(0)),
     };
+    Ok(Step {
+        op: info.operation.clone(),
+        value,
+    })
+}
 
-    let new = match op.op {
-        Operation::Inc => match value {
-            Param::Int(num) => parse_int(old)?.add(num).to_string(),
-            Param::String(num) => parse_int(old)?.add(parse_int(&num)?).to_string(),
-            Param::Capture(_) => bail!("this should not happen"),
-        },
-        Operation::Dec => match value {
-            Param::Int(num) => parse_int(old)?.sub(num).to_string(),
-            Param::String(num) => parse_int(old)?.sub(parse_int(&num)?).to_string(),
-            Param::Capture(_) => bail!("this should not happen"),
-        },
-        Operation::Replace => match value {
-            Param::Int(i) => format!("{i}"),
-            Param::String(s) => s,
-            Param::Capture(_) => bail!("this should not happen"),
-        },
-        Operation::Del => String::new(),
-        Operation::Swap => match value {
-            Param::String(s) => s,
-            Param::Int(i) => format!("{i}"),
-            Param::Capture(_) => bail!("this should not happen"),
-        },
-        Operation::Mul => match value {
-            Param::Int(num) => parse_int(old)?.wrapping_mul(num).to_string(),
-            Param::String(num) => parse_int(old)?.wrapping_mul(parse_int(&num)?).to_string(),
-            Param::Capture(_) => bail!("this should not happen"),
-        },
-        Operation::Div => match value {
-            Param::Int(num) => {
-                ensure!(num != 0, "division by zero");
-                (parse_int(old)? / num).to_string()
-            }
-            Param::String(num) => {
-                let divisor = parse_int(&num)?;
-                ensure!(divisor != 0, "division by zero");
-                (parse_int(old)? / divisor).to_string()
+/// Characters a backslash escapes in the parameters of operators. A backslash
+/// before any other character is taken literally.
+const ESCAPABLE: &[char] = &['\\', ':', '|', '\'', '"', '<', '>', '@', '?', '{', '}', '$'];
+
+/// Whether text starts where a step of an operator ends: at the next step,
+/// a guard, a limit or the end of the operator.
+fn ends_step(s: &str) -> bool {
+    s.is_empty() || s.starts_with('|') || s.starts_with("?if-") || s.starts_with("@max=")
+}
+
+/// Parse the steps of an operator separated by `|`, returning them along
+/// with the guards and limit following them.
+fn parse_steps(s: &str) -> Result<(Vec<Step>, &str)> {
+    let mut steps = Vec::new();
+    let mut rest = s;
+    loop {
+        let end = rest
+            .char_indices()
+            .find(|&(i, c)| c == ':' || ends_step(&rest[i..]))
+            .map_or(rest.len(), |(i, _)| i);
+        let (name, after) = rest.split_at(end);
+        let (param, after) = match after.strip_prefix(':') {
+            Some(after) => parse_param(after)?,
+            None => (None, after),
+        };
+        steps.push(parse_step(name, param)?);
+        match after.strip_prefix('|') {
+            Some(after) => rest = after,
+            None => return Ok((steps, after)),
+        }
+    }
+}
+
+/// Parse the parameter of a step, returning it along with the rest of the
+/// operator. An empty parameter is no parameter, unless it is quoted.
+fn parse_param(s: &str) -> Result<(Option<Param>, &str)> {
+    let quote = s.chars().next().filter(|c| matches!(c, '\'' | '"'));
+    let mut chars = s
+        .char_indices()
+        .skip(usize::from(quote.is_some()))
+        .peekable();
+    let mut pieces = Vec::new();
+    let mut escaped = false;
+    let mut end = None;
+    while let Some((i, c)) = chars.next() {
+        if quote.map_or_else(|| ends_step(&s[i..]), |quote| c == quote) {
+            end = Some(i);
+            break;
+        }
+        if quote == Some('\'') {
+            pieces.push(Piece::Text(c.to_string()));
+        } else if let Some((_, next)) =
+            chars.next_if(|(_, next)| c == '\\' && ESCAPABLE.contains(next))
+        {
+            pieces.push(Piece::Text(next.to_string()));
+            escaped = true;
+        } else if let Some((piece, len)) = reference(&s[i..])? {
+            pieces.push(piece);
+            while chars.next_if(|&(j, _)| j < i + len).is_some() {}
+        } else {
+            pieces.push(Piece::Text(c.to_string()));
+        }
+    }
+
+    let rest = match (quote, end) {
+        (None, end) => &s[end.unwrap_or(s.len())..],
+        (Some(quote), Some(end)) => {
+            let rest = &s[end + quote.len_utf8()..];
+            if !ends_step(rest) {
+                return Err(Error::Parse(format!(
+                    "unexpected '{rest}' after quoted parameter '{s}'"
+                )));
             }
-            Param::Capture(_) => bail!("this should not happen"),
-        },
-        Operation::Append => match value {
-            Param::String(s) => format!("{old}{s}"),
-            Param::Int(i) => format!("{old}{i}"),
-            Param::Capture(_) => bail!("this should not happen"),
-        },
-        Operation::Prepend => match value {
-            Param::String(s) => format!("{s}{old}"),
-            Param::Int(i) => format!("{i}{old}"),
-            Param::Capture(_) => bail!("this should not happen"),
-        },
-        Operation::Upper => old.to_uppercase(),
-        Operation::Lower => old.to_lowercase(),
+            rest
+        }
+        (Some(_), None) => {
+            return Err(Error::Parse(format!(
+                "unterminated quote in parameter '{s}'"
+            )));
+        }
     };
+    let param = match template(pieces) {
+        Param::String(text) if quote.is_none() && !escaped => Some(text)
+            .filter(|text| !text.is_empty())
+            .map(|text| Param::from(text.as_str())),
+        param => Some(param),
+    };
+    Ok((param, rest))
+}
 
-    Ok(Edit { start, end, new })
+/// The reference to a capture, `{name}`, or to the original value, `${old}`,
+/// text starts with, if any, along with its length.
+fn reference(s: &str) -> Result<Option<(Piece, usize)>> {
+    let (dollar, rest) = s.strip_prefix('$').map_or((false, s), |rest| (true, rest));
+    let Some(name) = rest
+        .strip_prefix('{')
+        .and_then(|rest| rest.split_once('}'))
+        .map(|(name, _)| name)
+        .filter(|name| is_name(name))
+    else {
+        return Ok(None);
+    };
+    let len = name.len() + 2 + usize::from(dollar);
+    match (dollar, name) {
+        (false, name) => Ok(Some((Piece::Capture(name.to_string()), len))),
+        (true, "old") => Ok(Some((Piece::Old, len))),
+        (true, name) => Err(Error::Parse(format!(
+            "'${{{name}}}' is not a valid reference, only '${{old}}' is"
+        ))),
+    }
 }
 
-/// Parse a string as an integer.
-///
-/// # Errors
-///
-/// Returns an error if the string cannot be parsed as an integer.
+/// Validate the optional locale parameter of case conversion operators.
+fn locale_param(name: &str, param: Option<Param>) -> Result<Param> {
+    match param {
+        None => Ok(Param::Int(0)),
+        Some(Param::String(locale)) if LOCALES.contains(&locale.as_str()) => {
+            Ok(Param::String(locale))
+        }
+        Some(Param::String(locale)) => Err(Error::Parse(format!(
+            "'{locale}' is not a supported locale in '{name}' operator"
+        ))),
+        Some(Param::Capture(c)) => Ok(Param::Capture(c)),
+        Some(Param::Int(i)) => Err(Error::Parse(format!(
+            "'{i}' is not a valid locale in '{name}' operator"
+        ))),
+        Some(Param::Template(pieces)) => Err(Error::Parse(format!(
+            "'{}' is not a valid locale in '{name}' operator",
+            template_source(&pieces)
+        ))),
+    }
+}
+
+/// Options controlling how content is processed.
 ///
 /// # Examples
 ///
 /// ```
-/// use regop::parse_int;
+/// use regop::{Options, Selector};
 ///
-/// assert_eq!(parse_int("42").unwrap(), 42);
-/// assert_eq!(parse_int("-10").unwrap(), -10);
-/// assert!(parse_int("not_a_number").is_err());
+/// let options = Options {
+///     lines: true,
+///     select: Selector::Index(1),
+///     ..Options::default()
+/// };
 /// ```
-pub fn parse_int(s: &str) -> anyhow::Result<isize> {
-    s.parse::<isize>()
-        .context(format!("cannot parse '{s}' as int"))
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Process each line independently
+    pub lines: bool,
+    /// Which matches to edit for operators without their own selector,
+    /// counted across the whole content
+    pub select: Selector,
+    /// Conditions that must all hold for a match to be edited
+    pub when: Vec<Condition>,
+    /// Leave content untouched if any of these regexes match anywhere in it
+    pub skip_if: Vec<Regex>,
+    /// Maximum number of matches all operators together may edit
+    pub max_matches: Option<usize>,
+    /// How matches are paired up by swap operators
+    pub swap_pairing: SwapPairing,
+    /// Which match of a capture used as a parameter provides its value
+    pub value_match: ValueMatch,
+    /// What to do when edits overlap each other
+    pub on_conflict: OnConflict,
+    /// Expected number of matches of captures, checked before any edit
+    pub expect: Vec<Expectation>,
+    /// Expected types of capture values, checked before any edit
+    pub types: Vec<TypeCheck>,
+    /// Only match inside blocks of lines between delimiters
+    pub block: Option<Block>,
+    /// Process content in chunks of lines independently, inside each block
+    /// if there is one
+    pub chunk: Option<Chunk>,
+    /// Only match inside the matches of these regexes, each one matched
+    /// inside the matches of the previous one
+    pub within: Vec<Regex>,
+    /// Never edit inside the matches of these regexes
+    pub skip: Vec<Regex>,
+    /// Fail if an operator edits no matches
+    pub strict: bool,
 }
 
-/// Calculate the distance between two non-overlapping ranges.
+/// Sections of content between a start and an end delimiter line.
 ///
-/// Returns `None` if the ranges overlap, otherwise returns the distance
-/// between them.
+/// A block begins after a line matching `start` and ends before the next
+/// line matching `end`, or at the end of the content. The delimiter lines
+/// are not part of the block, and a line ending a block can start a new one.
 ///
 /// # Examples
 ///
 /// ```
-/// use regop::distance;
+/// use regex::Regex;
+/// use regop::Block;
 ///
-/// // Non-overlapping ranges
-/// assert_eq!(distance(0, 5, 10, 15), Some(5));
-/// assert_eq!(distance(10, 15, 0, 5), Some(5));
+/// let block = Block {
+///     start: Regex::new(r"^\[dependencies\]").unwrap(),
+///     end: Regex::new(r"^\[").unwrap(),
+/// };
+/// let content = "[package]\nversion = 1\n[dependencies]\nregex = 1\n[features]\n";
+/// assert_eq!(block.split(content), vec![(37, "regex = 1\n")]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Block {
+    /// Regex matching the line a block starts after
+    pub start: Regex,
+    /// Regex matching the line a block ends before
+    pub end: Regex,
+}
+
+impl Block {
+    /// Find all blocks in the content along with their byte offsets.
+    #[must_use]
+    pub fn split<'a>(&self, content: &'a str) -> Vec<(usize, &'a str)> {
+        let mut blocks = Vec::new();
+        let mut current = None;
+        for (offset, line) in lines(content) {
+            if let Some(start) = current
+                && self.end.is_match(line)
+            {
+                blocks.push((start, &content[start..offset]));
+                current = None;
+            }
+            if current.is_none() && self.start.is_match(line) {
+                current = Some(
+                    content[offset..]
+                        .find('\n')
+                        .map_or(content.len(), |i| offset + i + 1),
+                );
+            }
+        }
+        if let Some(start) = current {
+            blocks.push((start, &content[start..]));
+        }
+        blocks
+    }
+}
+
+/// Windows of lines that content is split into and processed in
+/// independently, see [`Options::chunk`].
+///
+/// Matches never span two chunks, which lets
+/// [`Processor::process_stream`] rewrite inputs of any size while keeping
+/// only one chunk in memory.
+///
+/// # Examples
 ///
-/// // Overlapping ranges
-/// assert_eq!(distance(0, 10, 5, 15), None);
 /// ```
-#[must_use]
-pub const fn distance(start_a: usize, end_a: usize, start_b: usize, end_b: usize) -> Option<usize> {
-    if end_a <= start_b {
-        Some(start_b - end_a)
-    } else if end_b <= start_a {
-        Some(start_a - end_b)
+/// use regop::Chunk;
+///
+/// let chunk: Chunk = "paragraphs".parse().unwrap();
+/// assert_eq!(chunk.split("a\nb\n\nc\n"), vec![(0, "a\nb\n\n"), (5, "c\n")]);
+/// assert_eq!(Chunk::Lines(2).split("a\nb\nc"), vec![(0, "a\nb\n"), (4, "c")]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chunk {
+    /// At most this many lines at a time
+    Lines(usize),
+    /// Paragraphs of lines separated by blank lines, each ending with the
+    /// blank lines after it
+    Paragraphs,
+}
+
+impl Chunk {
+    /// Split content into chunks along with their byte offsets.
+    #[must_use]
+    pub fn split(self, content: &str) -> Vec<(usize, &str)> {
+        let mut chunks = Vec::new();
+        let (mut start, mut count, mut blank) = (0, 0, false);
+        for (offset, line) in lines(content) {
+            if count > 0 && self.breaks(count, blank, line) {
+                chunks.push((start, &content[start..offset]));
+                (start, count) = (offset, 0);
+            }
+            count += 1;
+            blank = line.trim().is_empty();
+        }
+        if start < content.len() {
+            chunks.push((start, &content[start..]));
+        }
+        chunks
+    }
+
+    /// Whether a line starts a new chunk, given the number of lines in the
+    /// current chunk and whether the last of them is blank.
+    fn breaks(self, count: usize, blank: bool, line: &str) -> bool {
+        match self {
+            Self::Lines(max) => count >= max.max(1),
+            Self::Paragraphs => blank && !line.trim().is_empty(),
+        }
+    }
+}
+
+impl FromStr for Chunk {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "paragraph" | "paragraphs" => Ok(Self::Paragraphs),
+            n => match n.parse() {
+                Ok(lines) if lines > 0 => Ok(Self::Lines(lines)),
+                _ => Err(Error::Parse(format!(
+                    "'{n}' is not a valid chunk, expected a number of lines or 'paragraphs'"
+                ))),
+            },
+        }
+    }
+}
+
+/// Strategy for pairing up matches of the two captures in a swap operator.
+///
+/// # Examples
+///
+/// ```
+/// use regop::SwapPairing;
+///
+/// assert_eq!("nearest".parse::<SwapPairing>().unwrap(), SwapPairing::Nearest);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SwapPairing {
+    /// Pair matches in order, failing if the number of matches differ
+    #[default]
+    Error,
+    /// Pair matches in order across the whole content, even in lines mode,
+    /// leaving extra matches untouched
+    Index,
+    /// Pair each match with the closest match of the other capture, leaving
+    /// extra matches untouched
+    Nearest,
+    /// Pair matches in order, leaving extra matches untouched
+    Truncate,
+}
+
+impl FromStr for SwapPairing {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "error" => Self::Error,
+            "index" => Self::Index,
+            "nearest" => Self::Nearest,
+            "truncate" => Self::Truncate,
+            p => return Err(Error::Parse(format!("'{p}' is not a valid swap pairing"))),
+        })
+    }
+}
+
+/// Which match of a capture used as a parameter provides its value.
+///
+/// This applies to captures like `<b>` in `<a>:inc:<b>` or `{b}` in a
+/// template that matched more than once. Matches are counted in the content,
+/// or in the line in lines mode.
+///
+/// # Examples
+///
+/// ```
+/// use regop::ValueMatch;
+///
+/// assert_eq!("same-match".parse::<ValueMatch>().unwrap(), ValueMatch::SameMatch);
+/// assert_eq!("index:2".parse::<ValueMatch>().unwrap(), ValueMatch::Index(2));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValueMatch {
+    /// The match from the same regex match as the target, or else the
+    /// closest match to the target
+    #[default]
+    Nearest,
+    /// Only the match from the same regex match as the target
+    SameMatch,
+    /// The first match
+    First,
+    /// The `N`th match, counting from `1`
+    Index(usize),
+}
+
+impl FromStr for ValueMatch {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "nearest" => Self::Nearest,
+            "same-match" => Self::SameMatch,
+            "first" => Self::First,
+            v => match v.strip_prefix("index:").map(str::parse) {
+                Some(Ok(n)) if n > 0 => Self::Index(n),
+                _ => return Err(Error::Parse(format!("'{v}' is not a valid value match"))),
+            },
+        })
+    }
+}
+
+/// What to do when edits of different operators overlap each other.
+///
+/// # Examples
+///
+/// ```
+/// use regop::OnConflict;
+///
+/// assert_eq!("first-wins".parse::<OnConflict>().unwrap(), OnConflict::FirstWins);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Fail with [`Error::Overlap`]
+    #[default]
+    Error,
+    /// Keep the edit that starts first in the content, skipping later ones
+    /// overlapping it
+    SkipLater,
+    /// Keep the edit of the operator given first
+    FirstWins,
+    /// Keep the edit of the operator given last
+    LastWins,
+}
+
+impl FromStr for OnConflict {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "error" => Self::Error,
+            "skip-later" => Self::SkipLater,
+            "first-wins" => Self::FirstWins,
+            "last-wins" => Self::LastWins,
+            c => {
+                return Err(Error::Parse(format!(
+                    "'{c}' is not a valid conflict strategy"
+                )));
+            }
+        })
+    }
+}
+
+/// Process content with the given captures and operators.
+///
+/// This is the main entry point for applying transformations to text.
+///
+/// # Arguments
+///
+/// * `lines` - If true, process each line independently
+/// * `regex` - List of capture patterns to match
+/// * `ops` - List of operators to apply to captures
+/// * `content` - The text content to process
+///
+/// # Returns
+///
+/// Returns `Some(String)` with transformed content if any changes were made,
+/// or `None` if no matches were found. To process borrowed content without
+/// copying it, see [`Processor::process_str`].
+///
+/// # Examples
+///
+/// ```
+/// use regop::{Capture, Operator, process};
+/// use std::str::FromStr;
+///
+/// let capture = Capture::from_str("value = (?<num>\\d+)").unwrap();
+/// let op = Operator::from_str("<num>:inc").unwrap();
+/// let content = "value = 42".to_string();
+///
+/// let result = process(false, &[capture], &[op], content).unwrap();
+/// assert_eq!(result, Some("value = 43".to_string()));
+/// ```
+pub fn process(
+    lines: bool,
+    regex: &[Capture],
+    ops: &[Operator],
+    content: String,
+) -> Result<Option<String>> {
+    let options = Options {
+        lines,
+        ..Options::default()
+    };
+    process_with(&options, regex, ops, content)
+}
+
+/// Process content with the given captures, operators and options.
+///
+/// Same as [`process`], but allows configuring processing through [`Options`].
+///
+/// # Examples
+///
+/// ```
+/// use regop::{Capture, Operator, Options, Selector, process_with};
+/// use std::str::FromStr;
+///
+/// let capture = Capture::from_str("(?<num>\\d+)").unwrap();
+/// let op = Operator::from_str("<num>:inc").unwrap();
+/// let options = Options {
+///     select: Selector::Last,
+///     ..Options::default()
+/// };
+///
+/// let result = process_with(&options, &[capture], &[op], "1 2 3".to_string()).unwrap();
+/// assert_eq!(result, Some("1 2 4".to_string()));
+/// ```
+///
+/// A UTF-8 byte order mark and CRLF line endings are removed before
+/// processing, so patterns can use `^`, `$` and `\n` without accounting for
+/// them, and are restored in the result. Files with mixed line endings are
+/// processed as they are.
+pub fn process_with(
+    options: &Options,
+    regex: &[Capture],
+    ops: &[Operator],
+    content: String,
+) -> Result<Option<String>> {
+    process_filtered(options, regex, ops, content, |_, _| true)
+}
+
+/// Process content with variables substituted into operator parameters.
+///
+/// Same as [`process_with`], but parameters can reference the given
+/// variables as `{name}`, see [`Operator::with_vars`].
+///
+/// # Examples
+///
+/// ```
+/// use regop::{Capture, Operator, Options, process_with_vars};
+/// use std::collections::HashMap;
+/// use std::str::FromStr;
+///
+/// let capture = Capture::from_str(r#"version = "(?<version>[^"]+)""#).unwrap();
+/// let op = Operator::from_str("<version>:rep:{new_version}").unwrap();
+/// let vars = HashMap::from([("new_version".to_string(), "2.0.0".to_string())]);
+///
+/// let content = r#"version = "1.0.0""#.to_string();
+/// let result = process_with_vars(&vars, &Options::default(), &[capture], &[op], content);
+/// assert_eq!(result.unwrap(), Some(r#"version = "2.0.0""#.to_string()));
+/// ```
+pub fn process_with_vars<S: BuildHasher>(
+    vars: &HashMap<String, String, S>,
+    options: &Options,
+    regex: &[Capture],
+    ops: &[Operator],
+    content: String,
+) -> Result<Option<String>> {
+    let ops = ops.iter().map(|op| op.with_vars(vars)).collect::<Vec<_>>();
+    process_with(options, regex, &ops, content)
+}
+
+/// Process content, only applying the edits accepted by a filter.
+///
+/// Same as [`process_with`], but `keep` is called with the content and each
+/// edit, in the order they appear in the content, and only the edits it
+/// returns `true` for are applied. The content passed to `keep` is without a
+/// byte order mark and CRLF line endings, which edit positions refer to.
+///
+/// # Examples
+///
+/// ```
+/// use regop::{Capture, Operator, Options, process_filtered};
+/// use std::str::FromStr;
+///
+/// let capture = Capture::from_str("(?<num>\\d+)").unwrap();
+/// let op = Operator::from_str("<num>:inc").unwrap();
+///
+/// let result = process_filtered(&Options::default(), &[capture], &[op], "1 2 3".to_string(), |_, edit| {
+///     edit.new != "3"
+/// });
+/// assert_eq!(result.unwrap(), Some("2 2 4".to_string()));
+/// ```
+pub fn process_filtered(
+    options: &Options,
+    regex: &[Capture],
+    ops: &[Operator],
+    content: String,
+    mut keep: impl FnMut(&str, &Edit) -> bool,
+) -> Result<Option<String>> {
+    process_normalized(
+        options,
+        &Prepared::new(regex, ops),
+        content,
+        &mut keep,
+        &mut Stats::default(),
+    )
+}
+
+/// Process content, counting matches and edits.
+///
+/// Same as [`process_with`], but also returns [`Stats`] about the matches
+/// found and the edits made by each operator.
+///
+/// # Examples
+///
+/// ```
+/// use regop::{Capture, Operator, Options, process_with_stats};
+/// use std::str::FromStr;
+///
+/// let capture = Capture::from_str("(?<num>\\d+)").unwrap();
+/// let ops = [
+///     Operator::from_str("<num>[1]:inc").unwrap(),
+///     Operator::from_str("<num>[last]:dec").unwrap(),
+/// ];
+///
+/// let (result, stats) =
+///     process_with_stats(&Options::default(), &[capture], &ops, "1 2 3".to_string()).unwrap();
+/// assert_eq!(result, Some("2 2 2".to_string()));
+/// assert_eq!(stats.matches, 3);
+/// assert_eq!(stats.edits, vec![1, 1]);
+/// ```
+pub fn process_with_stats(
+    options: &Options,
+    regex: &[Capture],
+    ops: &[Operator],
+    content: String,
+) -> Result<(Option<String>, Stats)> {
+    process_counted(options, &Prepared::new(regex, ops), content)
+}
+
+/// Process content, counting matches and edits, see [`process_with_stats`].
+fn process_counted(
+    options: &Options,
+    prepared: &Prepared,
+    content: String,
+) -> Result<(Option<String>, Stats)> {
+    let mut stats = Stats {
+        matches: 0,
+        edits: vec![0; prepared.operators.len()],
+    };
+    let result = process_normalized(options, prepared, content, &mut |_, _| true, &mut stats)?;
+    Ok((result, stats))
+}
+
+/// Process content with a byte order mark and CRLF line endings removed, and
+/// restore them in the result.
+fn process_normalized(
+    options: &Options,
+    prepared: &Prepared,
+    content: String,
+    keep: &mut impl FnMut(&str, &Edit) -> bool,
+    stats: &mut Stats,
+) -> Result<Option<String>> {
+    let body = content.strip_prefix(BOM);
+    let bom = body.is_some();
+    let body = body.unwrap_or(&content);
+    let crlf = has_crlf(body);
+    if !bom && !crlf {
+        return process_content(options, prepared, content, keep, stats);
+    }
+
+    let body = if crlf {
+        body.replace("\r\n", "\n")
     } else {
-        None
+        body.to_string()
+    };
+    Ok(
+        process_content(options, prepared, body, keep, stats)?.map(|new| {
+            let new = if crlf { new.replace('\n', "\r\n") } else { new };
+            if bom { format!("{BOM}{new}") } else { new }
+        }),
+    )
+}
+
+/// Check if all lines of content end with `\r\n`.
+fn has_crlf(content: &str) -> bool {
+    content.contains("\r\n") && content.matches('\n').count() == content.matches("\r\n").count()
+}
+
+/// Content decoded to text to match in, along with how to map edits of the
+/// text back to the content, see [`Processor::process_bytes`].
+///
+/// Like [`process_normalized`] the text has no BOM and `\n` line endings if
+/// all of the content's lines end with `\r\n`.
+struct Decoded {
+    /// The content with each invalid UTF-8 sequence replaced by `U+FFFD`
+    text: String,
+    /// Spans of the text replacing invalid UTF-8
+    invalid: Vec<(usize, usize)>,
+    /// Positions in the text and the content from which on the distance
+    /// between them changes, starting at the beginning of the text
+    anchors: Vec<(usize, usize)>,
+    /// Whether `\r\n` line endings were normalized to `\n`
+    crlf: bool,
+}
+
+impl Decoded {
+    fn new(content: &[u8]) -> Self {
+        let body = content
+            .strip_prefix("\u{feff}".as_bytes())
+            .unwrap_or(content);
+        let crlf = body.contains(&b'\n')
+            && !body.starts_with(b"\n")
+            && body.windows(2).all(|w| w[1] != b'\n' || w[0] == b'\r');
+
+        let mut offset = content.len() - body.len();
+        let mut decoded = Self {
+            text: String::with_capacity(body.len()),
+            invalid: Vec::new(),
+            anchors: vec![(0, offset)],
+            crlf,
+        };
+        for chunk in body.utf8_chunks() {
+            for line in chunk.valid().split_inclusive('\n') {
+                offset += line.len();
+                match line.strip_suffix("\r\n").filter(|_| crlf) {
+                    Some(line) => {
+                        decoded.text.push_str(line);
+                        decoded.text.push('\n');
+                        decoded.anchors.push((decoded.text.len(), offset));
+                    }
+                    None => decoded.text.push_str(line),
+                }
+            }
+            if !chunk.invalid().is_empty() {
+                let start = decoded.text.len();
+                decoded.text.push(char::REPLACEMENT_CHARACTER);
+                offset += chunk.invalid().len();
+                decoded.invalid.push((start, decoded.text.len()));
+                decoded.anchors.push((decoded.text.len(), offset));
+            }
+        }
+        decoded
+    }
+
+    /// Position in the content of a position in the text.
+    fn position(&self, position: usize) -> usize {
+        let (text, content) = self.anchors[self.anchors.partition_point(|a| a.0 <= position) - 1];
+        content + position - text
+    }
+
+    /// An edit of the text as an edit of the content.
+    fn restore(&self, edit: Edit) -> Edit {
+        let crlf = |text: String| {
+            if self.crlf {
+                text.replace('\n', "\r\n")
+            } else {
+                text
+            }
+        };
+        Edit {
+            start: self.position(edit.start),
+            end: self.position(edit.end),
+            old: crlf(edit.old),
+            new: crlf(edit.new),
+            ..edit
+        }
+    }
+}
+
+/// Captures and operators along with the state derived from them, which
+/// [`Processor`] derives once instead of for every content.
+struct Prepared<'a> {
+    /// The capture regexes
+    regex: &'a [Capture],
+    /// Groups of each capture regex, starting with the whole match as `0`
+    groups: Cow<'a, [GroupNames]>,
+    /// The operators as given
+    operators: &'a [Operator],
+    /// The operators with wildcard targets expanded
+    ops: Cow<'a, [Operator]>,
+    /// Index of the given operator each expanded operator came from
+    origins: Cow<'a, [usize]>,
+    /// Whether the content starts in an ignored region
+    ignore: IgnoreState,
+    /// Spans of the content standing in for invalid UTF-8, never edited
+    invalid: &'a [(usize, usize)],
+    /// All capture regexes combined, to skip content none of them match
+    prefilter: Option<&'a RegexSet>,
+}
+
+impl<'a> Prepared<'a> {
+    fn new(regex: &'a [Capture], operators: &'a [Operator]) -> Self {
+        let (ops, origins) = expand_wildcards(regex, operators);
+        Self {
+            regex,
+            groups: Cow::Owned(capture_groups(regex)),
+            operators,
+            ops,
+            origins: Cow::Owned(origins),
+            ignore: IgnoreState::default(),
+            invalid: &[],
+            prefilter: None,
+        }
+    }
+}
+
+/// Combine the capture regexes into a set that tells cheaply whether content
+/// matches any of them, so content matching none can be skipped without
+/// looking for captures. `None` if a regex is not compiled with the standard
+/// engine or the set is too large.
+fn prefilter(regex: &[Capture]) -> Option<RegexSet> {
+    let patterns = regex
+        .iter()
+        .map(|capture| match &capture.regex {
+            Pattern::Standard(re) => Some(re.as_str()),
+            #[cfg(feature = "fancy")]
+            Pattern::Fancy(_) => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    RegexSet::new(patterns).ok()
+}
+
+/// Groups of each capture regex, starting with the whole match as `0`.
+fn capture_groups(regex: &[Capture]) -> Vec<GroupNames> {
+    regex
+        .iter()
+        .map(|cap| {
+            std::iter::once((0, "0".to_string()))
+                .chain(group_names(&cap.regex))
+                .collect()
+        })
+        .collect()
+}
+
+/// Size of content from which its units are matched in parallel.
+const PARALLEL_THRESHOLD: usize = 1 << 20;
+
+/// Find the captures in each unit of the content, without the ones in
+/// excluded regions.
+///
+/// Units of large content, like the lines or blocks of a big file, are
+/// matched in parallel.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(len = content.len()))
+)]
+fn find_captures<'a>(
+    options: &'a Options,
+    prepared: &Prepared,
+    content: &'a str,
+) -> Result<Vec<CapturesMap<'a>>> {
+    let find = |&(offset, unit): &(usize, &'a str)| {
+        if prepared.prefilter.is_some_and(|set| !set.is_match(unit)) {
+            return Ok(CapturesMap::new());
+        }
+        collect_all_captures(prepared, unit, offset)
+    };
+    let units = units(options, content).collect::<Vec<_>>();
+    let mut units = if content.len() >= PARALLEL_THRESHOLD && units.len() > 1 {
+        units.par_iter().map(find).collect::<Result<Vec<_>>>()?
+    } else {
+        units.iter().map(find).collect::<Result<Vec<_>>>()?
+    };
+
+    let excluded = options
+        .skip
+        .iter()
+        .flat_map(|re| re.find_iter(content))
+        .map(|m| (m.start(), m.end()))
+        .chain(ignored_regions(content, prepared.ignore))
+        .chain(prepared.invalid.iter().copied())
+        .collect::<Vec<_>>();
+    if !excluded.is_empty() {
+        for captures in &mut units {
+            for matches in captures.values_mut() {
+                matches.retain(|m| !excluded.iter().any(|ex| overlaps(*ex, (m.0, m.1))));
+            }
+        }
+    }
+    Ok(units)
+}
+
+/// A match of a named capture group, see [`find_matches`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamedMatch {
+    /// Name of the capture group
+    pub name: String,
+    /// Start position of the match
+    pub start: usize,
+    /// End position of the match
+    pub end: usize,
+    /// The matched text
+    pub value: String,
+}
+
+/// Find all matches of named capture groups in content, without applying any
+/// operators.
+///
+/// Matches are found the same way as when processing content, within the
+/// units given by the options and outside of excluded regions, and are
+/// returned in the order they appear in the content. Content skipped by
+/// [`Options::skip_if`] has no matches.
+///
+/// # Examples
+///
+/// ```
+/// use regop::{Capture, Options, find_matches};
+/// use std::str::FromStr;
+///
+/// let capture = Capture::from_str("(?<key>\\w+)=(?<value>\\d+)").unwrap();
+/// let matches = find_matches(&Options::default(), &[capture], "a=1 b=2").unwrap();
+///
+/// let found = matches
+///     .iter()
+///     .map(|m| format!("{}:{}", m.name, m.value))
+///     .collect::<Vec<_>>();
+/// assert_eq!(found, vec!["key:a", "value:1", "key:b", "value:2"]);
+/// ```
+pub fn find_matches(
+    options: &Options,
+    regex: &[Capture],
+    content: &str,
+) -> Result<Vec<NamedMatch>> {
+    prepared_matches(options, &Prepared::new(regex, &[]), content)
+}
+
+/// Find all matches of named capture groups in content, see [`find_matches`].
+fn prepared_matches(
+    options: &Options,
+    prepared: &Prepared,
+    content: &str,
+) -> Result<Vec<NamedMatch>> {
+    if options.skip_if.iter().any(|re| re.is_match(content)) {
+        return Ok(Vec::new());
+    }
+    Ok(named_matches(&find_captures(options, prepared, content)?))
+}
+
+/// All matches of named capture groups in the units, in the order they
+/// appear.
+fn named_matches(units: &[CapturesMap]) -> Vec<NamedMatch> {
+    let mut matches = units
+        .iter()
+        .flat_map(|captures| captures.iter())
+        .filter(|(name, _)| *name != "0")
+        .flat_map(|(name, matches)| {
+            matches.iter().map(|(start, end, value, _)| NamedMatch {
+                name: name.clone(),
+                start: *start,
+                end: *end,
+                value: (*value).to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+    matches.sort_by(|a, b| (a.start, a.end, &a.name).cmp(&(b.start, b.end, &b.name)));
+    matches.dedup();
+    matches
+}
+
+/// A match of a whole regex along with the named capture groups matched in
+/// it, see [`find_full_matches`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FullMatch {
+    /// Start position of the match
+    pub start: usize,
+    /// End position of the match
+    pub end: usize,
+    /// The matched text
+    pub value: String,
+    /// Named capture groups that matched, in the order they appear
+    pub groups: Vec<NamedMatch>,
+}
+
+impl FullMatch {
+    /// The value of a named capture group in the match.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.groups
+            .iter()
+            .find(|m| m.name == name)
+            .map(|m| m.value.as_str())
+    }
+}
+
+/// Find all matches of the regular expressions in content, each with the
+/// named capture groups matched in it, without applying any operators.
+///
+/// Matches are found the same way as with [`find_matches`], and are returned
+/// in the order they appear in the content.
+///
+/// # Examples
+///
+/// ```
+/// use regop::{Capture, Options, find_full_matches};
+/// use std::str::FromStr;
+///
+/// let capture = Capture::from_str("(?<key>\\w+)=(?<value>\\d+)").unwrap();
+/// let matches = find_full_matches(&Options::default(), &[capture], "a=1 b=2").unwrap();
+///
+/// assert_eq!(matches.len(), 2);
+/// assert_eq!(matches[1].value, "b=2");
+/// assert_eq!(matches[1].get("key"), Some("b"));
+/// assert_eq!(matches[1].get("value"), Some("2"));
+/// ```
+pub fn find_full_matches(
+    options: &Options,
+    regex: &[Capture],
+    content: &str,
+) -> Result<Vec<FullMatch>> {
+    if options.skip_if.iter().any(|re| re.is_match(content)) {
+        return Ok(Vec::new());
+    }
+    let prepared = Prepared::new(regex, &[]);
+    let units = find_captures(options, &prepared, content)?;
+    let named = named_matches(&units);
+    let mut matches = units
+        .iter()
+        .filter_map(|captures| captures.get("0"))
+        .flatten()
+        .map(|(start, end, value, _)| FullMatch {
+            start: *start,
+            end: *end,
+            value: (*value).to_string(),
+            groups: named
+                .iter()
+                .filter(|m| m.start >= *start && m.end <= *end)
+                .cloned()
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+    matches.sort_by_key(|m| (m.start, m.end));
+    Ok(matches)
+}
+
+/// Edits that processing content would make, see [`plan`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EditPlan {
+    /// The edits, sorted by their start
+    pub edits: Vec<Edit>,
+}
+
+impl EditPlan {
+    /// Apply all edits to the content the plan was made for.
+    ///
+    /// Returns the inverse plan, which puts back the text the edits replaced
+    /// when applied to the edited content.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use regop::{Capture, Operator, plan};
+    /// use std::str::FromStr;
+    ///
+    /// let capture = Capture::from_str(r"(?<num>\d+)").unwrap();
+    /// let op = Operator::from_str("<num>:rep:10").unwrap();
+    ///
+    /// let mut content = "1 2 3".to_string();
+    /// let undo = plan(&[capture], &[op], &content).unwrap().apply(&mut content).unwrap();
+    /// assert_eq!(content, "10 10 10");
+    ///
+    /// undo.apply(&mut content).unwrap();
+    /// assert_eq!(content, "1 2 3");
+    /// ```
+    pub fn apply(&self, content: &mut String) -> Result<Self> {
+        self.apply_subset(content, |_| true)
+    }
+
+    /// Apply the edits `keep` returns true for to the content the plan was
+    /// made for, e.g. leaving out edits a user rejected.
+    ///
+    /// The edits are checked not to overlap each other and to still match the
+    /// content, so edits added to the plan are validated as well. Returns the
+    /// inverse plan of the applied edits, see [`EditPlan::apply`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use regop::{Capture, Operator, plan};
+    /// use std::str::FromStr;
+    ///
+    /// let capture = Capture::from_str(r"(?<num>\d+)").unwrap();
+    /// let op = Operator::from_str("<num>:inc").unwrap();
+    ///
+    /// let mut content = "1 2 3".to_string();
+    /// let plan = plan(&[capture], &[op], &content).unwrap();
+    /// plan.apply_subset(&mut content, |edit| edit.old != "2").unwrap();
+    /// assert_eq!(content, "2 2 4");
+    /// ```
+    pub fn apply_subset(
+        &self,
+        content: &mut String,
+        mut keep: impl FnMut(&Edit) -> bool,
+    ) -> Result<Self> {
+        let mut edits = self
+            .edits
+            .iter()
+            .filter(|edit| keep(edit))
+            .cloned()
+            .collect::<Vec<_>>();
+        if let Some(edit) = edits
+            .iter()
+            .find(|edit| content.get(edit.start..edit.end) != Some(edit.old.as_str()))
+        {
+            return Err(Error::Stale {
+                start: edit.start,
+                end: edit.end,
+            });
+        }
+        apply_edits(content, &mut edits)?;
+        Ok(Self::inverse(edits))
+    }
+
+    /// The edits putting back what applied edits, sorted by their start,
+    /// replaced.
+    fn inverse(edits: Vec<Edit>) -> Self {
+        let (mut added, mut removed) = (0, 0);
+        let edits = edits
+            .into_iter()
+            .map(|edit| {
+                let start = edit.start + added - removed;
+                added += edit.new.len();
+                removed += edit.end - edit.start;
+                Edit {
+                    start,
+                    end: start + edit.new.len(),
+                    old: edit.new,
+                    new: edit.old,
+                    ..edit
+                }
+            })
+            .collect();
+        Self { edits }
+    }
+}
+
+/// Plan the edits the operators would make to content, without applying them.
+///
+/// Matches are validated the same way as when processing content, the edits
+/// are sorted by their start and don't overlap each other. Each edit holds the
+/// name of the capture it edits along with its old and new value, so they can
+/// be inspected, filtered or logged before being applied.
+///
+/// # Examples
+///
+/// ```
+/// use regop::{Capture, Operator, plan};
+/// use std::str::FromStr;
+///
+/// let capture = Capture::from_str(r"(?<major>\d+)\.(?<minor>\d+)").unwrap();
+/// let op = Operator::from_str("<minor>:inc").unwrap();
+///
+/// let plan = plan(&[capture], &[op], "1.2").unwrap();
+/// assert_eq!(plan.edits.len(), 1);
+/// assert_eq!(plan.edits[0].target, "minor");
+/// assert_eq!((plan.edits[0].start, plan.edits[0].end), (2, 3));
+/// assert_eq!(plan.edits[0].old, "2");
+/// assert_eq!(plan.edits[0].new, "3");
+/// ```
+pub fn plan(regex: &[Capture], ops: &[Operator], content: &str) -> Result<EditPlan> {
+    plan_with(&Options::default(), regex, ops, content)
+}
+
+/// Plan the edits the operators would make to content with custom options.
+///
+/// Same as [`plan`], but with [`Options`] like [`process_with`]. Positions
+/// are in the content as given, which is planned without the byte order mark
+/// and line ending handling of [`process_with`]. Content skipped by
+/// [`Options::skip_if`] has no edits.
+pub fn plan_with(
+    options: &Options,
+    regex: &[Capture],
+    ops: &[Operator],
+    content: &str,
+) -> Result<EditPlan> {
+    plan_prepared(options, &Prepared::new(regex, ops), content)
+}
+
+/// Plan the edits to content, see [`plan_with`].
+fn plan_prepared(options: &Options, prepared: &Prepared, content: &str) -> Result<EditPlan> {
+    let edits =
+        plan_content(options, prepared, content, &mut Stats::default())?.unwrap_or_default();
+    check_overlaps(&edits)?;
+    Ok(EditPlan { edits })
+}
+
+/// Captures, operators and options compiled once and reused to process any
+/// number of contents.
+///
+/// State derived from the captures and operators, like the operators that
+/// wildcard targets expand to, is computed once when the processor is
+/// created instead of for every content.
+///
+/// A processor is `Send` and `Sync`, so one processor can be shared by
+/// worker threads, e.g. in an [`Arc`](std::sync::Arc), without cloning or
+/// compiling its regexes again.
+///
+/// # Examples
+///
+/// ```
+/// use regop::{Capture, Operator, Options, Processor};
+/// use std::str::FromStr;
+///
+/// let capture = Capture::from_str(r"version = (?<v>\d+)").unwrap();
+/// let op = Operator::from_str("<v>:inc").unwrap();
+/// let processor = Processor::new(Options::default(), vec![capture], vec![op]);
+///
+/// for (content, expected) in [("version = 1", "version = 2"), ("version = 9", "version = 10")] {
+///     assert_eq!(processor.process(content).unwrap(), Some(expected.to_string()));
+/// }
+///
+/// std::thread::scope(|scope| {
+///     for content in ["version = 1", "version = 2"] {
+///         scope.spawn(|| processor.process(content));
+///     }
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct Processor {
+    captures: Vec<Capture>,
+    ops: Vec<Operator>,
+    options: Options,
+    /// Groups of each capture regex
+    groups: Vec<GroupNames>,
+    /// The operators with wildcard targets expanded
+    expanded: Vec<Operator>,
+    /// Index of the operator each expanded operator came from
+    origins: Vec<usize>,
+    /// All capture regexes combined, see [`prefilter`]
+    prefilter: Option<RegexSet>,
+}
+
+// Processors are shared across threads, e.g. by `process_files`, so they and
+// what they are made of must stay `Send` and `Sync`
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Capture>();
+    assert_send_sync::<Operator>();
+    assert_send_sync::<Options>();
+    assert_send_sync::<Processor>();
+};
+
+/// A [`Processor`] built with [`Regop::builder`].
+///
+/// # Examples
+///
+/// ```
+/// use regop::Regop;
+///
+/// let regop = Regop::builder()
+///     .capture(r"version = (?<v>\d+)")
+///     .op("<v>:inc")
+///     .lines(true)
+///     .strict(true)
+///     .build()
+///     .unwrap();
+///
+/// let result = regop.process("version = 1\nname = x\n").unwrap();
+/// assert_eq!(result, Some("version = 2\nname = x\n".to_string()));
+/// assert!(regop.process("name = x\n").is_err());
+/// ```
+pub type Regop = Processor;
+
+impl Processor {
+    /// Create a processor, deriving the state shared by all contents.
+    #[must_use]
+    pub fn new(options: Options, captures: Vec<Capture>, ops: Vec<Operator>) -> Self {
+        let (expanded, origins) = expand_wildcards(&captures, &ops);
+        let expanded = expanded.into_owned();
+        Self {
+            groups: capture_groups(&captures),
+            prefilter: prefilter(&captures),
+            captures,
+            ops,
+            options,
+            expanded,
+            origins,
+        }
+    }
+
+    /// Start building a [`Processor`].
+    #[must_use]
+    pub fn builder() -> RegopBuilder {
+        RegopBuilder::default()
+    }
+
+    /// The compiled capture regexes.
+    #[must_use]
+    pub fn regex(&self) -> &[Capture] {
+        &self.captures
+    }
+
+    /// The parsed operators.
+    #[must_use]
+    pub fn ops(&self) -> &[Operator] {
+        &self.ops
+    }
+
+    /// The processing options.
+    #[must_use]
+    pub const fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// The captures and operators along with their derived state.
+    fn prepared(&self) -> Prepared<'_> {
+        Prepared {
+            regex: &self.captures,
+            groups: Cow::Borrowed(&self.groups),
+            operators: &self.ops,
+            ops: Cow::Borrowed(&self.expanded),
+            origins: Cow::Borrowed(&self.origins),
+            ignore: IgnoreState::default(),
+            invalid: &[],
+            prefilter: self.prefilter.as_ref(),
+        }
+    }
+
+    /// Process content, see [`process_with`].
+    pub fn process(&self, content: &str) -> Result<Option<String>> {
+        Ok(self.process_str(content)?.map(Cow::into_owned))
+    }
+
+    /// Process content, borrowing it instead of copying it if edits leave it
+    /// as it was.
+    ///
+    /// Returns `None` if there are no edits. Otherwise the content is copied
+    /// once with all edits applied, or borrowed if every edit keeps the value
+    /// it replaces.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use regop::Regop;
+    /// use std::borrow::Cow;
+    ///
+    /// let processor = Regop::builder().capture(r"(?<n>\d+)").op("<n>:mul:1").build().unwrap();
+    /// assert!(matches!(processor.process_str("1 2").unwrap(), Some(Cow::Borrowed("1 2"))));
+    /// assert_eq!(processor.process_str("none").unwrap(), None);
+    /// ```
+    pub fn process_str<'a>(&self, content: &'a str) -> Result<Option<Cow<'a, str>>> {
+        if self.unmatched(content) {
+            return Ok(None);
+        }
+        let body = content.strip_prefix(BOM).unwrap_or(content);
+        if has_crlf(body) {
+            return Ok(self.process_filtered(content, |_, _| true)?.map(Cow::Owned));
+        }
+
+        let Some(mut edits) =
+            plan_content(&self.options, &self.prepared(), body, &mut Stats::default())?
+        else {
+            return Ok(None);
+        };
+        if edits.is_empty() {
+            return Ok(None);
+        }
+        check_overlaps(&edits)?;
+        if edits.iter().all(|edit| edit.new == edit.old) {
+            return Ok(Some(Cow::Borrowed(content)));
+        }
+
+        let offset = content.len() - body.len();
+        for edit in &mut edits {
+            edit.start += offset;
+            edit.end += offset;
+        }
+        Ok(Some(Cow::Owned(splice(content, &edits))))
+    }
+
+    /// Process content, only applying the edits `keep` returns true for, see
+    /// [`process_filtered`].
+    pub fn process_filtered(
+        &self,
+        content: &str,
+        mut keep: impl FnMut(&str, &Edit) -> bool,
+    ) -> Result<Option<String>> {
+        process_normalized(
+            &self.options,
+            &self.prepared(),
+            content.to_string(),
+            &mut keep,
+            &mut Stats::default(),
+        )
+    }
+
+    /// Process content, counting matches and edits, see [`process_with_stats`].
+    pub fn process_with_stats(&self, content: &str) -> Result<(Option<String>, Stats)> {
+        if self.unmatched(content) {
+            let stats = Stats {
+                matches: 0,
+                edits: vec![0; self.ops.len()],
+            };
+            return Ok((None, stats));
+        }
+        process_counted(&self.options, &self.prepared(), content.to_string())
+    }
+
+    /// Find all matches of named capture groups in content, in the order they
+    /// appear, see [`find_matches`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use regop::Regop;
+    ///
+    /// let processor = Regop::builder().capture(r"(?<key>\w+)=(?<value>\d+)").build().unwrap();
+    /// let found = processor
+    ///     .captures("a=1 b=2")
+    ///     .unwrap()
+    ///     .map(|m| format!("{}@{}..{}:{}", m.name, m.start, m.end, m.value))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(found, ["key@0..1:a", "value@2..3:1", "key@4..5:b", "value@6..7:2"]);
+    /// ```
+    pub fn captures(&self, content: &str) -> Result<impl Iterator<Item = NamedMatch> + use<>> {
+        Ok(prepared_matches(&self.options, &self.prepared(), content)?.into_iter())
+    }
+
+    /// Plan the edits to content without applying them, see [`plan_with`].
+    pub fn plan(&self, content: &str) -> Result<EditPlan> {
+        plan_prepared(&self.options, &self.prepared(), content)
+    }
+
+    /// Process content read from `reader`, writing the result to `writer`.
+    /// Returns whether anything changed.
+    ///
+    /// In lines, chunk and block mode the content is read and processed a
+    /// line, a chunk or a block at a time, so it never has to fit in memory as
+    /// a whole. Options that need to see all of the content first, like
+    /// selecting matches, expectations, limits, [`Options::skip_if`],
+    /// [`Options::skip`], [`Options::within`] without a block or chunk,
+    /// [`Options::strict`] and [`SwapPairing::Index`], fall back to reading
+    /// all of it.
+    ///
+    /// Whatever was written before processing fails is left in the writer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use regop::Regop;
+    ///
+    /// let processor = Regop::builder()
+    ///     .capture(r"^(?<n>\d+)")
+    ///     .op("<n>:inc")
+    ///     .lines(true)
+    ///     .build()
+    ///     .unwrap();
+    /// let mut output = Vec::new();
+    /// let changed = processor.process_stream("1 a\n2 b\n".as_bytes(), &mut output).unwrap();
+    /// assert!(changed);
+    /// assert_eq!(output, b"2 a\n3 b\n");
+    /// ```
+    pub fn process_stream(&self, mut reader: impl BufRead, mut writer: impl Write) -> Result<bool> {
+        if !self.streams() {
+            let mut content = String::new();
+            reader.read_to_string(&mut content)?;
+            let new = self.process(&content)?;
+            writer.write_all(new.as_deref().unwrap_or(&content).as_bytes())?;
+            writer.flush()?;
+            return Ok(new.is_some());
+        }
+
+        let options = Options {
+            block: None,
+            ..self.options.clone()
+        };
+        let mut changed = false;
+        let mut ignore = IgnoreState::default();
+        let mut current: Option<(String, IgnoreState)> = None;
+        let (mut count, mut blank) = (0, false);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            let text = line.strip_suffix('\n').unwrap_or(&line);
+            let text = text.strip_suffix('\r').unwrap_or(text);
+            match (&self.options.block, self.options.chunk) {
+                (None, Some(chunk)) if !self.options.lines => {
+                    if let Some((content, state)) =
+                        current.take_if(|_| chunk.breaks(count, blank, text))
+                    {
+                        changed |= self.process_part(&options, &content, state, &mut writer)?;
+                        count = 0;
+                    }
+                    let (content, _) = current.get_or_insert_with(|| (String::new(), ignore));
+                    content.push_str(&line);
+                    count += 1;
+                    blank = text.trim().is_empty();
+                }
+                (None, _) => changed |= self.process_part(&options, &line, ignore, &mut writer)?,
+                (Some(block), _) => {
+                    if let Some((content, state)) = current.take_if(|_| block.end.is_match(text)) {
+                        changed |= self.process_part(&options, &content, state, &mut writer)?;
+                    }
+                    if let Some((content, _)) = &mut current {
+                        content.push_str(&line);
+                    } else {
+                        writer.write_all(line.as_bytes())?;
+                        if block.start.is_match(text) {
+                            current = Some((String::new(), ignore.after(text)));
+                        }
+                    }
+                }
+            }
+            ignore = ignore.after(text);
+        }
+        if let Some((content, state)) = current {
+            changed |= self.process_part(&options, &content, state, &mut writer)?;
+        }
+        writer.flush()?;
+        Ok(changed)
+    }
+
+    /// Process content that may not be valid UTF-8.
+    ///
+    /// The regexes see each invalid UTF-8 sequence as a replacement character
+    /// `U+FFFD`, and matches touching one are never edited, so the invalid
+    /// bytes are written back exactly as they were.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use regop::Regop;
+    ///
+    /// let processor = Regop::builder().capture(r"v(?<v>\d+)").op("<v>:inc").build().unwrap();
+    /// let result = processor.process_bytes(b"v1 \xff v2").unwrap();
+    /// assert_eq!(result, Some(b"v2 \xff v3".to_vec()));
+    /// ```
+    pub fn process_bytes(&self, content: &[u8]) -> Result<Option<Vec<u8>>> {
+        if let Ok(content) = std::str::from_utf8(content) {
+            return Ok(self.process(content)?.map(String::into_bytes));
+        }
+
+        let decoded = Decoded::new(content);
+        let prepared = Prepared {
+            invalid: &decoded.invalid,
+            ..self.prepared()
+        };
+        let Some(edits) = plan_content(
+            &self.options,
+            &prepared,
+            &decoded.text,
+            &mut Stats::default(),
+        )?
+        else {
+            return Ok(None);
+        };
+        check_overlaps(&edits)?;
+        if edits.is_empty() {
+            return Ok(None);
+        }
+
+        let mut new = Vec::with_capacity(content.len());
+        let mut last = 0;
+        for edit in edits.into_iter().map(|edit| decoded.restore(edit)) {
+            new.extend_from_slice(&content[last..edit.start]);
+            new.extend_from_slice(edit.new.as_bytes());
+            last = edit.end;
+        }
+        new.extend_from_slice(&content[last..]);
+        Ok(Some(new))
+    }
+
+    /// Process content, reporting every change made along with the result.
+    ///
+    /// Positions of changes refer to the content as given, including a byte
+    /// order mark and `\r\n` line endings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use regop::Regop;
+    ///
+    /// let processor = Regop::builder().capture(r"v(?<v>\d+)").op("<v>:inc").build().unwrap();
+    /// let report = processor.report("a\nb v1").unwrap();
+    /// assert_eq!(report.content.as_deref(), Some("a\nb v2"));
+    ///
+    /// let change = &report.changes[0];
+    /// assert_eq!((change.target.as_str(), change.old.as_str(), change.new.as_str()), ("v", "1", "2"));
+    /// assert_eq!((change.from.line, change.from.column, change.to.column), (2, 4, 5));
+    /// ```
+    pub fn report(&self, content: &str) -> Result<Report> {
+        let decoded = Decoded::new(content.as_bytes());
+        let Some(edits) = plan_content(
+            &self.options,
+            &self.prepared(),
+            &decoded.text,
+            &mut Stats::default(),
+        )?
+        else {
+            return Ok(Report::default());
+        };
+        check_overlaps(&edits)?;
+        let edits = edits
+            .into_iter()
+            .map(|edit| decoded.restore(edit))
+            .collect::<Vec<_>>();
+
+        let mut changes = Vec::with_capacity(edits.len());
+        let mut cursor = Cursor::default();
+        for edit in &edits {
+            changes.push(Change {
+                target: edit.target.clone(),
+                operators: edit.operators.to_vec(),
+                old: edit.old.clone(),
+                new: edit.new.clone(),
+                start: edit.start,
+                end: edit.end,
+                from: cursor.advance(content, edit.start),
+                to: cursor.advance(content, edit.end),
+            });
+        }
+        Ok(Report {
+            content: (!edits.is_empty()).then(|| splice(content, &edits)),
+            changes,
+        })
+    }
+
+    /// Whether content can be processed a line or a block at a time, see
+    /// [`Processor::process_stream`].
+    fn streams(&self) -> bool {
+        let options = &self.options;
+        (options.lines || options.block.is_some() || options.chunk.is_some())
+            && options.select == Selector::All
+            && self
+                .ops
+                .iter()
+                .all(|op| op.select == Selector::All && op.max.is_none())
+            && options.max_matches.is_none()
+            && options.expect.is_empty()
+            && options.skip_if.is_empty()
+            && options.skip.is_empty()
+            && (options.within.is_empty() || options.block.is_some() || options.chunk.is_some())
+            && !options.strict
+            && options.swap_pairing != SwapPairing::Index
+    }
+
+    /// Whether content is left unchanged because none of the captures match
+    /// anywhere in it, which the prefilter tells in a single pass without
+    /// planning any edits. Only content matched as a whole qualifies, and only
+    /// if processing content without matches can't fail.
+    fn unmatched(&self, content: &str) -> bool {
+        let options = &self.options;
+        let body = content.strip_prefix(BOM).unwrap_or(content);
+        !options.lines
+            && options.block.is_none()
+            && options.chunk.is_none()
+            && options.within.is_empty()
+            && options.expect.is_empty()
+            && !options.strict
+            && validate_captures(&self.expanded, &CapturesMap::new()).is_ok()
+            && !has_crlf(body)
+            && self
+                .prefilter
+                .as_ref()
+                .is_some_and(|set| !set.is_match(body))
+    }
+
+    /// Process a line or a block of content starting in the given ignore
+    /// state, writing the result.
+    fn process_part(
+        &self,
+        options: &Options,
+        content: &str,
+        ignore: IgnoreState,
+        writer: &mut impl Write,
+    ) -> Result<bool> {
+        let prepared = Prepared {
+            ignore,
+            ..self.prepared()
+        };
+        let new = process_normalized(
+            options,
+            &prepared,
+            content.to_string(),
+            &mut |_, _| true,
+            &mut Stats::default(),
+        )?;
+        writer.write_all(new.as_deref().unwrap_or(content).as_bytes())?;
+        Ok(new.is_some())
+    }
+}
+
+/// The result of processing content along with every change made, see
+/// [`Processor::report`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// The processed content, `None` if there were no edits
+    pub content: Option<String>,
+    /// The changes made, in the order they appear in the content
+    pub changes: Vec<Change>,
+}
+
+/// A change made to a match of a capture, see [`Report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// Name of the capture the changed text matched
+    pub target: String,
+    /// Indices of the operators that made the change, see [`Edit::operators`]
+    pub operators: Vec<usize>,
+    /// The text before the change
+    pub old: String,
+    /// The text after the change
+    pub new: String,
+    /// Start position of the changed text in the content
+    pub start: usize,
+    /// End position of the changed text in the content
+    pub end: usize,
+    /// Line and column of the start of the changed text
+    pub from: Position,
+    /// Line and column of the end of the changed text
+    pub to: Position,
+}
+
+/// A line and column in content.
+///
+/// # Examples
+///
+/// ```
+/// use regop::Position;
+///
+/// assert_eq!(Position::of("ab\nçd", 5), Position { line: 2, column: 2 });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    /// Line, counting from `1`
+    pub line: usize,
+    /// Column in characters, counting from `1`
+    pub column: usize,
+}
+
+impl Position {
+    /// Find the line and column of a byte offset in content.
+    ///
+    /// Offsets past the end of the content are clamped to its end, and
+    /// offsets inside a character to the start of it.
+    ///
+    /// ```
+    /// use regop::Position;
+    ///
+    /// assert_eq!(Position::of("ab", 10), Position { line: 1, column: 3 });
+    /// assert_eq!(Position::of("ç", 1), Position { line: 1, column: 1 });
+    /// ```
+    #[must_use]
+    pub fn of(content: &str, offset: usize) -> Self {
+        Cursor::default().advance(content, content.floor_char_boundary(offset))
+    }
+}
+
+/// Finds the positions of increasing offsets in content without counting
+/// lines from the start of the content for each of them.
+#[derive(Debug, Default)]
+struct Cursor {
+    /// Lines before the offset
+    lines: usize,
+    /// Offset of the start of the line
+    line_start: usize,
+    /// The last offset
+    offset: usize,
+}
+
+impl Cursor {
+    /// Move to an offset at or after the last one.
+    fn advance(&mut self, content: &str, offset: usize) -> Position {
+        for (i, _) in content[self.offset..offset].match_indices('\n') {
+            self.lines += 1;
+            self.line_start = self.offset + i + 1;
+        }
+        self.offset = offset;
+        Position {
+            line: self.lines + 1,
+            column: content[self.line_start..offset].chars().count() + 1,
+        }
+    }
+}
+
+/// Builder for a [`Processor`], see [`Regop::builder`].
+///
+/// Captures and operators are given as strings and compiled by
+/// [`RegopBuilder::build`], which fails if any of them is not valid.
+#[derive(Debug, Clone, Default)]
+pub struct RegopBuilder {
+    captures: Vec<String>,
+    ops: Vec<String>,
+    vars: HashMap<String, String>,
+    regex_options: RegexOptions,
+    options: Options,
+}
+
+impl RegopBuilder {
+    /// Add a capture regex.
+    #[must_use]
+    pub fn capture(mut self, regex: &str) -> Self {
+        self.captures.push(regex.to_string());
+        self
+    }
+
+    /// Add an operator, e.g. `<v>:inc`.
+    #[must_use]
+    pub fn op(mut self, op: &str) -> Self {
+        self.ops.push(op.to_string());
+        self
+    }
+
+    /// Define a variable that operator parameters can reference as `{name}`,
+    /// see [`Operator::with_vars`].
+    #[must_use]
+    pub fn var(mut self, name: &str, value: &str) -> Self {
+        self.vars.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Process each line independently, see [`Options::lines`].
+    #[must_use]
+    pub const fn lines(mut self, lines: bool) -> Self {
+        self.options.lines = lines;
+        self
+    }
+
+    /// Process content in chunks of lines, see [`Options::chunk`].
+    #[must_use]
+    pub const fn chunk(mut self, chunk: Chunk) -> Self {
+        self.options.chunk = Some(chunk);
+        self
+    }
+
+    /// Fail if an operator edits no matches, see [`Options::strict`].
+    #[must_use]
+    pub const fn strict(mut self, strict: bool) -> Self {
+        self.options.strict = strict;
+        self
+    }
+
+    /// Which matches to edit, see [`Options::select`].
+    #[must_use]
+    pub const fn select(mut self, select: Selector) -> Self {
+        self.options.select = select;
+        self
+    }
+
+    /// Maximum number of matches to edit, see [`Options::max_matches`].
+    #[must_use]
+    pub const fn max_matches(mut self, max: usize) -> Self {
+        self.options.max_matches = Some(max);
+        self
+    }
+
+    /// Which match of a capture used as a parameter provides its value, see
+    /// [`Options::value_match`].
+    #[must_use]
+    pub const fn value_match(mut self, value_match: ValueMatch) -> Self {
+        self.options.value_match = value_match;
+        self
+    }
+
+    /// What to do when edits overlap, see [`Options::on_conflict`].
+    #[must_use]
+    pub const fn on_conflict(mut self, on_conflict: OnConflict) -> Self {
+        self.options.on_conflict = on_conflict;
+        self
+    }
+
+    /// Match letters case insensitively, see [`RegexOptions::ignore_case`].
+    #[must_use]
+    pub const fn ignore_case(mut self, ignore_case: bool) -> Self {
+        self.regex_options.ignore_case = ignore_case;
+        self
+    }
+
+    /// Compile captures with these options.
+    #[must_use]
+    pub fn regex_options(mut self, options: RegexOptions) -> Self {
+        self.regex_options = options;
+        self
+    }
+
+    /// Process content with these options.
+    #[must_use]
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Compile the captures and parse the operators.
+    pub fn build(self) -> Result<Processor> {
+        let captures = self
+            .captures
+            .iter()
+            .map(|regex| Capture::with_options(regex, &self.regex_options))
+            .collect::<Result<Vec<_>>>()?;
+        let ops = self
+            .ops
+            .iter()
+            .map(|op| op.parse().map(|op: Operator| op.with_vars(&self.vars)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Processor::new(self.options, captures, ops))
+    }
+}
+
+/// The UTF-8 byte order mark.
+const BOM: char = '\u{feff}';
+
+/// Process content without line ending or byte order mark handling.
+fn process_content(
+    options: &Options,
+    prepared: &Prepared,
+    mut content: String,
+    keep: &mut impl FnMut(&str, &Edit) -> bool,
+    stats: &mut Stats,
+) -> Result<Option<String>> {
+    let Some(mut edits) = plan_content(options, prepared, &content, stats)? else {
+        return Ok(None);
+    };
+    edits.retain(|edit| keep(&content, edit));
+
+    apply_edits(&mut content, &mut edits)?;
+
+    if edits.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(content))
+    }
+}
+
+/// Find the edits operators make to content, sorted by their start, or
+/// `None` if the content is skipped.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(len = content.len()))
+)]
+fn plan_content(
+    options: &Options,
+    prepared: &Prepared,
+    content: &str,
+    stats: &mut Stats,
+) -> Result<Option<Vec<Edit>>> {
+    if options.skip_if.iter().any(|re| re.is_match(content)) {
+        return Ok(None);
+    }
+
+    let ops = prepared.ops.as_ref();
+    let units = find_captures(options, prepared, content)?;
+
+    for expectation in &options.expect {
+        let count = units
+            .iter()
+            .filter_map(|captures| captures.get(&expectation.target))
+            .map(Vec::len)
+            .sum();
+        expectation.check(count)?;
+    }
+
+    for check in &options.types {
+        for captures in &units {
+            for (_, _, value, _) in captures.get(&check.target).into_iter().flatten() {
+                check.check(value)?;
+            }
+        }
+    }
+
+    for captures in &units {
+        validate_captures(ops, captures)?;
+    }
+
+    let (mut edits, counts) = collect_edits(options, ops, &units)?;
+    stats.matches += units
+        .iter()
+        .filter_map(|captures| captures.get("0"))
+        .map(Vec::len)
+        .sum::<usize>();
+    let mut edited = vec![0; prepared.operators.len()];
+    for (origin, count) in prepared.origins.iter().zip(counts) {
+        edited[*origin] += count;
+    }
+    for edit in &mut edits {
+        for index in &mut edit.operators {
+            *index = prepared.origins[*index];
+        }
+    }
+    edits.sort_by_key(|e| e.start);
+    for edit in resolve_conflicts(options.on_conflict, &mut edits) {
+        for &i in &edit.operators {
+            if !matches!(prepared.operators[i].op, Operation::Swap) {
+                edited[i] -= 1;
+            }
+        }
+    }
+    if options.strict
+        && let Some(i) = edited.iter().position(|count| *count == 0)
+    {
+        return Err(Error::NoEdits(prepared.operators[i].target.clone()));
+    }
+    for (edits, count) in stats.edits.iter_mut().zip(edited) {
+        *edits += count;
+    }
+    Ok(Some(edits))
+}
+
+/// Remove edits, sorted by their start, that overlap others as the strategy
+/// says, returning the removed ones.
+fn resolve_conflicts(on_conflict: OnConflict, edits: &mut Vec<Edit>) -> Vec<Edit> {
+    let mut order = (0..edits.len()).collect::<Vec<_>>();
+    match on_conflict {
+        OnConflict::Error => return Vec::new(),
+        OnConflict::SkipLater => {}
+        OnConflict::FirstWins => order.sort_by_key(|&i| edits[i].operators.iter().min()),
+        OnConflict::LastWins => {
+            order.sort_by_key(|&i| std::cmp::Reverse(edits[i].operators.iter().max()));
+        }
+    }
+
+    // Kept edits don't overlap, so an edit overlaps one of them only if it
+    // overlaps the one right before or after it
+    let mut kept = BTreeSet::new();
+    for i in order {
+        let key = (edits[i].start, edits[i].end, i);
+        let overlaps = |other: Option<&(usize, usize, usize)>| {
+            other.is_some_and(|&(start, end, _)| distance(key.0, key.1, start, end).is_none())
+        };
+        if !overlaps(kept.range(..key).next_back()) && !overlaps(kept.range(key..).next()) {
+            kept.insert(key);
+        }
+    }
+    if kept.len() == edits.len() {
+        return Vec::new();
+    }
+
+    let (keep, removed) = std::mem::take(edits)
+        .into_iter()
+        .enumerate()
+        .partition::<Vec<_>, _>(|(i, edit)| kept.contains(&(edit.start, edit.end, *i)));
+    *edits = keep.into_iter().map(|(_, edit)| edit).collect();
+    removed.into_iter().map(|(_, edit)| edit).collect()
+}
+
+/// Apply regex captures and operators to content.
+///
+/// This function handles the core logic of finding matches and applying transformations.
+/// Unlike `process`, this always treats the content as a single unit.
+///
+/// # Arguments
+///
+/// * `regex` - List of capture patterns to match
+/// * `ops` - List of operators to apply to captures
+/// * `content` - The text content to process
+///
+/// # Returns
+///
+/// Returns `Some(String)` with transformed content if any changes were made,
+/// or `None` if no matches were found.
+pub fn regop(regex: &[Capture], ops: &[Operator], content: String) -> Result<Option<String>> {
+    process_with(&Options::default(), regex, ops, content)
+}
+
+/// Whether content starts in a region opted out of editing, when content is
+/// processed in parts, see [`Processor::process_stream`].
+#[derive(Debug, Clone, Copy, Default)]
+struct IgnoreState {
+    /// The previous line contains `regop-ignore-next-line`
+    next_line: bool,
+    /// A `regop-ignore-start` line hasn't been ended yet
+    inside: bool,
+}
+
+impl IgnoreState {
+    /// The state after a line.
+    fn after(self, line: &str) -> Self {
+        Self {
+            next_line: line.contains("regop-ignore-next-line"),
+            inside: (self.inside || line.contains("regop-ignore-start"))
+                && !line.contains("regop-ignore-end"),
+        }
+    }
+}
+
+/// Find regions opted out of editing by directives in the content.
+///
+/// A line containing `regop-ignore-next-line` excludes the line after it,
+/// and everything from a line containing `regop-ignore-start` to a line
+/// containing `regop-ignore-end`, or the end of the content, is excluded.
+fn ignored_regions(content: &str, state: IgnoreState) -> Vec<(usize, usize)> {
+    let mut regions = Vec::new();
+    let mut start = state.inside.then_some(0);
+    let mut next_line = state.next_line;
+    for (offset, line) in lines(content) {
+        if next_line {
+            regions.push((offset, offset + line.len()));
+        }
+        next_line = line.contains("regop-ignore-next-line");
+        if start.is_none() && line.contains("regop-ignore-start") {
+            start = Some(offset);
+        }
+        if let Some(s) = start
+            && line.contains("regop-ignore-end")
+        {
+            regions.push((s, offset + line.len()));
+            start = None;
+        }
+    }
+    if let Some(s) = start {
+        regions.push((s, content.len()));
+    }
+    regions
+}
+
+/// Check if a match touches an excluded span. Empty matches count as
+/// touching the span they are inside of.
+fn overlaps(excluded: (usize, usize), span: (usize, usize)) -> bool {
+    span.0 < excluded.1 && excluded.0 < span.1.max(span.0 + 1)
+}
+
+/// Split content into the units captures are matched in, along with the
+/// byte offset of each unit.
+///
+/// With a block only the content inside blocks is matched, each block being
+/// a unit. With a chunk each block, or the content, is split into chunks of
+/// lines. Each `within` regex further narrows units down to its matches. In
+/// lines mode each line (without its line ending) of the content,
+/// or of each block, is a unit.
+fn units<'a>(
+    options: &'a Options,
+    content: &'a str,
+) -> Box<dyn Iterator<Item = (usize, &'a str)> + 'a> {
+    let mut sections: Box<dyn Iterator<Item = (usize, &str)>> = match &options.block {
+        Some(block) => Box::new(block.split(content).into_iter()),
+        None => Box::new(std::iter::once((0, content))),
+    };
+    if let Some(chunk) = options.chunk {
+        sections = Box::new(sections.flat_map(move |(offset, section)| {
+            chunk
+                .split(section)
+                .into_iter()
+                .map(move |(start, chunk)| (offset + start, chunk))
+        }));
+    }
+    for re in &options.within {
+        sections = Box::new(sections.flat_map(move |(offset, section)| {
+            re.find_iter(section)
+                .map(move |m| (offset + m.start(), m.as_str()))
+        }));
+    }
+    if options.lines {
+        Box::new(sections.flat_map(|(offset, section)| {
+            lines(section).map(move |(start, line)| (offset + start, line))
+        }))
+    } else {
+        sections
+    }
+}
+
+/// Split content into lines without their line endings, along with the byte
+/// offset of each line.
+fn lines(content: &str) -> impl Iterator<Item = (usize, &str)> {
+    content.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        Some((start, line))
+    })
+}
+
+/// Replace operators with wildcard targets by one operator per matching
+/// capture group name, along with the index of the operator each one came
+/// from.
+fn expand_wildcards<'a>(
+    regex: &[Capture],
+    ops: &'a [Operator],
+) -> (Cow<'a, [Operator]>, Vec<usize>) {
+    if !ops.iter().any(|op| op.target.contains(['*', '?'])) {
+        return (Cow::Borrowed(ops), (0..ops.len()).collect());
+    }
+
+    let mut names = regex
+        .iter()
+        .flat_map(|cap| cap.names.iter())
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+
+    let (ops, origins) = ops
+        .iter()
+        .enumerate()
+        .flat_map(|(i, op)| {
+            if op.target.contains(['*', '?']) {
+                names
+                    .iter()
+                    .filter(|name| glob_match(&op.target, name))
+                    .map(|name| {
+                        let op = Operator {
+                            target: (*name).clone(),
+                            ..op.clone()
+                        };
+                        (op, i)
+                    })
+                    .collect()
+            } else {
+                vec![(op.clone(), i)]
+            }
+        })
+        .unzip::<_, _, Vec<_>, _>();
+    (Cow::Owned(ops), origins)
+}
+
+/// Match a name against a pattern where `*` matches any number of characters
+/// and `?` matches a single character.
+///
+/// # Examples
+///
+/// ```
+/// use regop::glob_match;
+///
+/// assert!(glob_match("*", "version"));
+/// assert!(glob_match("ver_*", "ver_major"));
+/// assert!(glob_match("ver_?", "ver_1"));
+/// assert!(!glob_match("ver_*", "major"));
+/// ```
+#[must_use]
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bn)) => {
+                    p = bp + 1;
+                    n = bn + 1;
+                    backtrack = Some((bp, bn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Check that operators can be used with the captures before processing any
+/// content.
+///
+/// Finds every issue instead of stopping at the first one: operators
+/// targeting no capture group, captures used as values that don't exist,
+/// swaps with a plain value instead of a capture, swaps in pipelines and
+/// parameters an operation can't use.
+///
+/// # Examples
+///
+/// ```
+/// use regop::{Capture, Operator, ValidationIssue, validate};
+/// use std::str::FromStr;
+///
+/// let capture = Capture::from_str(r"(?<v>\d+)").unwrap();
+/// assert!(validate(&[capture.clone()], &[Operator::from_str("<v>:inc").unwrap()]).is_ok());
+///
+/// let ops = [Operator::from_str("<x>:swap:v").unwrap()];
+/// let issues = validate(&[capture], &ops).unwrap_err();
+/// assert!(matches!(issues[0], ValidationIssue::UnknownTarget { operator: 0, .. }));
+/// assert!(matches!(issues[1], ValidationIssue::SwapValue { operator: 0, .. }));
+/// ```
+pub fn validate(regex: &[Capture], ops: &[Operator]) -> Result<(), Vec<ValidationIssue>> {
+    let known = |name: &str| name == "0" || regex.iter().any(|cap| cap.names.contains(name));
+    let mut issues = Vec::new();
+    for (operator, op) in ops.iter().enumerate() {
+        let target = op.target.clone();
+        let found = if op.target.contains(['*', '?']) {
+            regex
+                .iter()
+                .flat_map(|cap| &cap.names)
+                .any(|name| glob_match(&op.target, name))
+        } else {
+            known(&op.target)
+        };
+        if !found {
+            issues.push(ValidationIssue::UnknownTarget {
+                operator,
+                target: target.clone(),
+            });
+        }
+
+        let steps = std::iter::once((&op.op, &op.value))
+            .chain(op.then.iter().map(|step| (&step.op, &step.value)));
+        for (operation, value) in steps {
+            let target = target.clone();
+            let unknown = value.captures().find(|name| !known(name));
+            let issue = match (operation, value) {
+                _ if let Some(name) = unknown => Some(ValidationIssue::UnknownCapture {
+                    operator,
+                    target,
+                    name: name.to_string(),
+                }),
+                (Operation::Swap, _) if !op.then.is_empty() => {
+                    Some(ValidationIssue::SwapInPipeline { operator, target })
+                }
+                (Operation::Swap, Param::Int(value)) => Some(ValidationIssue::SwapValue {
+                    operator,
+                    target,
+                    value: value.to_string(),
+                }),
+                (Operation::Swap, Param::String(value)) => Some(ValidationIssue::SwapValue {
+                    operator,
+                    target,
+                    value: value.clone(),
+                }),
+                (Operation::Swap, Param::Template(pieces)) => Some(ValidationIssue::SwapValue {
+                    operator,
+                    target,
+                    value: template_source(pieces),
+                }),
+                (
+                    Operation::Inc | Operation::Dec | Operation::Mul | Operation::Div,
+                    Param::String(value),
+                ) if !is_number(value) => Some(ValidationIssue::NotAnInt {
+                    operator,
+                    target,
+                    value: value.clone(),
+                }),
+                (Operation::Div, Param::Int(0)) => {
+                    Some(ValidationIssue::DivisionByZero { operator, target })
+                }
+                (Operation::Upper | Operation::Lower, Param::String(locale))
+                    if !LOCALES.contains(&locale.as_str()) =>
+                {
+                    Some(ValidationIssue::Locale {
+                        operator,
+                        target,
+                        locale: locale.clone(),
+                    })
+                }
+                _ => None,
+            };
+            issues.extend(issue);
+        }
+    }
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(issues)
+    }
+}
+
+/// Validate that all captures used as values exist.
+fn validate_captures(ops: &[Operator], captures: &CapturesMap) -> Result<()> {
+    for op in ops {
+        let values = std::iter::once(&op.value)
+            .filter(|_| !matches!(op.op, Operation::Swap))
+            .chain(op.then.iter().map(|step| &step.value));
+        if let Some(name) = values
+            .flat_map(Param::captures)
+            .find(|name| !captures.contains_key(*name))
+        {
+            return Err(Error::MissingCapture(name.to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// Collect all named captures from the provided regexes.
+///
+/// Positions are shifted by `offset` so they refer to the full content.
+fn collect_all_captures<'a>(
+    prepared: &Prepared,
+    content: &'a str,
+    offset: usize,
+) -> Result<CapturesMap<'a>> {
+    let mut captures: CapturesMap = HashMap::new();
+    let mut id = 0;
+
+    for (cap, groups) in prepared.regex.iter().zip(prepared.groups.iter()) {
+        for spans in cap.regex.spans(content)? {
+            id += 1;
+            if let Some((start, end)) = spans[0] {
+                log::debug!(
+                    "Regex '{}' matched '{}' at {}..{}",
+                    cap.regex.as_str(),
+                    &content[start..end],
+                    offset + start,
+                    offset + end
+                );
+            }
+            for (i, name) in groups {
+                if let Some((start, end)) = spans[*i] {
+                    let m = (offset + start, offset + end, &content[start..end], id);
+                    match captures.get_mut(name) {
+                        Some(matches) => matches.push(m),
+                        None => {
+                            captures.insert(name.clone(), vec![m]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    for matches in captures.values_mut() {
+        matches.sort_by_key(|m| m.0);
+        matches.dedup_by_key(|m| (m.0, m.1));
+    }
+
+    Ok(captures)
+}
+
+/// Collect all edit operations to be applied to the content.
+///
+/// This processes all operators and regex matches to build a list of
+/// text transformations to apply. Each unit contributes its own captures,
+/// and selectors are applied across all units.
+///
+/// Operators editing a match that an earlier operator already edited are
+/// composed, i.e. applied to the result of the earlier operator.
+///
+/// Returns the edits along with the number of matches each operator edited.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(operators = ops.len()))
+)]
+fn collect_edits(
+    options: &Options,
+    ops: &[Operator],
+    units: &[CapturesMap],
+) -> Result<(Vec<Edit>, Vec<usize>)> {
+    let mut edits = Vec::new();
+    let mut counts = Vec::new();
+    let mut spans = HashMap::new();
+    let mut total = 0;
+
+    for (index, op) in ops.iter().enumerate() {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("operator", index, target = %op.target).entered();
+        let select = if op.select == Selector::All {
+            &options.select
+        } else {
+            &op.select
+        };
+        let collected = edits.len();
+        let count = if matches!(op.op, Operation::Swap) {
+            collect_swap_edits(options, (index, op), select, units, &mut edits, &spans)?
+        } else {
+            collect_regular_edits(options, (index, op), select, units, &mut edits, &spans)?
+        };
+        if let Some(max) = op.max
+            && count > max
+        {
+            return Err(Error::OperatorLimit {
+                target: op.target.clone(),
+                count,
+                max,
+            });
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(count, "collected edits");
+        total += count;
+        counts.push(count);
+        for (i, e) in edits.iter().enumerate().skip(collected) {
+            spans.entry((e.start, e.end)).or_insert(i);
+        }
+    }
+
+    if let Some(max) = options.max_matches
+        && total > max
+    {
+        return Err(Error::MatchLimit { count: total, max });
+    }
+
+    Ok((edits, counts))
+}
+
+/// Collect edit operations for swap operators.
+///
+/// Swap operations are special because they need to exchange values between
+/// two capture groups, requiring coordinated edits. Matches already edited,
+/// as recorded in `spans`, are swapped with their new values.
+///
+/// Returns the number of swapped pairs.
+fn collect_swap_edits(
+    options: &Options,
+    (index, op): (usize, &Operator),
+    select: &Selector,
+    units: &[CapturesMap],
+    edits: &mut Vec<Edit>,
+    spans: &HashMap<(usize, usize), usize>,
+) -> Result<usize> {
+    let swap_target = match &op.value {
+        Param::String(s) => s.clone(),
+        Param::Capture(c) => c.clone(),
+        Param::Int(i) => format!("{i}"),
+        Param::Template(pieces) => template_source(pieces),
+    };
+
+    let mut pairs = Vec::new();
+    if options.swap_pairing == SwapPairing::Index {
+        let matches = |name: &String| {
+            units
+                .iter()
+                .flat_map(|captures| {
+                    captures
+                        .get(name)
+                        .into_iter()
+                        .flatten()
+                        .map(move |m| (*m, captures))
+                })
+                .collect::<Vec<_>>()
+        };
+        pairs.extend(
+            matches(&op.target)
+                .into_iter()
+                .zip(matches(&swap_target))
+                .filter(|((source, captures), _)| applies(options, op, source, captures))
+                .map(|((source, _), (target, _))| (source, target)),
+        );
+    } else {
+        for captures in units {
+            let source_matches = captures.get(&op.target).map_or(&[][..], Vec::as_slice);
+            let target_matches = captures.get(&swap_target).map_or(&[][..], Vec::as_slice);
+
+            if options.swap_pairing == SwapPairing::Error
+                && source_matches.len() != target_matches.len()
+            {
+                return Err(Error::SwapMismatch {
+                    target: op.target.clone(),
+                    other: swap_target,
+                    found: source_matches.len(),
+                    other_found: target_matches.len(),
+                });
+            }
+
+            let unit_pairs = if options.swap_pairing == SwapPairing::Nearest {
+                pair_nearest(source_matches, target_matches)
+            } else {
+                source_matches
+                    .iter()
+                    .copied()
+                    .zip(target_matches.iter().copied())
+                    .collect()
+            };
+
+            pairs.extend(
+                unit_pairs
+                    .into_iter()
+                    .filter(|(source, _)| applies(options, op, source, captures)),
+            );
+        }
+    }
+
+    // Create edits for swapping
+    let pairs = select.select(&pairs);
+    for (source, target) in pairs {
+        let current = |m: &Match| {
+            spans
+                .get(&(m.0, m.1))
+                .map_or_else(|| m.2.to_string(), |&i| edits[i].new.clone())
+        };
+        let (source_value, target_value) = (current(source), current(target));
+        log::debug!(
+            "Operator on '<{}>' swapped '{source_value}' at {}..{} with '{target_value}' at {}..{}",
+            op.target,
+            source.0,
+            source.1,
+            target.0,
+            target.1
+        );
+        for (m, name, new) in [
+            (source, &op.target, target_value),
+            (target, &swap_target, source_value),
+        ] {
+            if let Some(&i) = spans.get(&(m.0, m.1)) {
+                edits[i].new = new;
+                edits[i].operators.push(index);
+            } else {
+                edits.push(Edit {
+                    start: m.0,
+                    end: m.1,
+                    target: name.clone(),
+                    old: m.2.to_string(),
+                    new,
+                    operators: smallvec![index],
+                });
+            }
+        }
+    }
+
+    Ok(pairs.len())
+}
+
+/// Pair source and target matches closest to each other first, skipping
+/// overlapping ones. Matches left without a partner are dropped.
+fn pair_nearest<'a>(sources: &[Match<'a>], targets: &[Match<'a>]) -> Vec<(Match<'a>, Match<'a>)> {
+    let mut candidates = sources
+        .iter()
+        .enumerate()
+        .flat_map(|(si, s)| {
+            targets
+                .iter()
+                .enumerate()
+                .filter_map(move |(ti, t)| distance(s.0, s.1, t.0, t.1).map(|d| (d, si, ti)))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_unstable();
+
+    let mut paired_sources = HashSet::new();
+    let mut paired_targets = HashSet::new();
+    let mut pairs = Vec::new();
+    for (_, si, ti) in candidates {
+        if !paired_sources.contains(&si) && !paired_targets.contains(&ti) {
+            paired_sources.insert(si);
+            paired_targets.insert(ti);
+            pairs.push((sources[si], targets[ti]));
+        }
+    }
+    pairs.sort_by_key(|(source, _)| source.0);
+    pairs
+}
+
+/// Collect edit operations for non-swap operators.
+///
+/// Processes standard operators like increment, replace, append, etc. If a
+/// match was already edited, as recorded in `spans`, that edit is updated
+/// in place with the operator applied to its new value.
+///
+/// Returns the number of edited matches.
+fn collect_regular_edits(
+    options: &Options,
+    (index, op): (usize, &Operator),
+    select: &Selector,
+    units: &[CapturesMap],
+    edits: &mut Vec<Edit>,
+    spans: &HashMap<(usize, usize), usize>,
+) -> Result<usize> {
+    let matches = units
+        .iter()
+        .flat_map(|captures| {
+            captures
+                .get(&op.target)
+                .into_iter()
+                .flatten()
+                .filter(|m| applies(options, op, m, captures))
+                .map(move |m| (m, captures))
+        })
+        .collect::<Vec<_>>();
+
+    let matches = select.select(&matches);
+    for (m @ (start, end, val, _), captures) in matches {
+        let lookup = |name: &str| capture_value(captures, name, m, options.value_match);
+        let i = if let Some(&i) = spans.get(&(*start, *end)) {
+            let new = resolve(op, &edits[i].new, lookup)?;
+            log::debug!(
+                "Operator on '<{}>' changed '{}' to '{new}' at {start}..{end}",
+                op.target,
+                edits[i].new
+            );
+            edits[i].new = new;
+            i
+        } else {
+            let edit = Edit {
+                start: *start,
+                end: *end,
+                target: op.target.clone(),
+                old: (*val).to_string(),
+                new: resolve(op, val, lookup)?,
+                operators: SmallVec::new(),
+            };
+            log::debug!(
+                "Operator on '<{}>' changed '{val}' to '{}' at {start}..{end}",
+                op.target,
+                edit.new
+            );
+            edits.push(edit);
+            edits.len() - 1
+        };
+        edits[i].operators.push(index);
+    }
+    Ok(matches.len())
+}
+
+/// Check the operator guards and global conditions for a single match.
+fn applies(
+    options: &Options,
+    op: &Operator,
+    (start, end, value, _): &Match,
+    captures: &CapturesMap,
+) -> bool {
+    op.guards.iter().all(|g| g.test(value))
+        && options.when.iter().all(|c| {
+            nearest_capture(captures, &c.target, *start, *end).is_some_and(|v| c.predicate.test(v))
+        })
+}
+
+/// Find the value of the match of capture `name` nearest to the given range.
+fn nearest_capture<'a>(
+    captures: &CapturesMap<'a>,
+    name: &str,
+    start: usize,
+    end: usize,
+) -> Option<&'a str> {
+    captures.get(name).and_then(|v| {
+        v.iter()
+            .map(|c| (distance(start, end, c.0, c.1), c.2))
+            .min_by_key(|c| c.0)
+            .map(|c| c.1)
+    })
+}
+
+/// Find the value of capture `name` used as a parameter of an operator
+/// editing a match, see [`ValueMatch`].
+fn capture_value<'a>(
+    captures: &CapturesMap<'a>,
+    name: &str,
+    (start, end, _, id): &Match,
+    value_match: ValueMatch,
+) -> Option<&'a str> {
+    let matches = captures.get(name)?;
+    let same = || matches.iter().find(|m| m.3 == *id).map(|m| m.2);
+    match value_match {
+        ValueMatch::Nearest => same().or_else(|| nearest_capture(captures, name, *start, *end)),
+        ValueMatch::SameMatch => same(),
+        ValueMatch::First => matches.first().map(|m| m.2),
+        ValueMatch::Index(n) => matches.get(n.checked_sub(1)?).map(|m| m.2),
+    }
+}
+
+/// Apply all collected edits to the content.
+///
+/// Edits are sorted and spliced into a copy of the content in a single pass.
+/// This function also validates that edits don't overlap.
+fn apply_edits(content: &mut String, edits: &mut [Edit]) -> Result<()> {
+    edits.sort_by_key(|e| e.start);
+    check_overlaps(edits)?;
+
+    if !edits.is_empty() {
+        *content = splice(content, edits);
+    }
+
+    Ok(())
+}
+
+/// Copy content with edits, sorted by their start and not overlapping, in
+/// place of what they replace.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(edits = edits.len()))
+)]
+fn splice(content: &str, edits: &[Edit]) -> String {
+    let len = edits.iter().fold(content.len(), |len, edit| {
+        len + edit.new.len() - (edit.end - edit.start)
+    });
+    let mut new = String::with_capacity(len);
+    let mut last = 0;
+    for edit in edits {
+        new.push_str(&content[last..edit.start]);
+        new.push_str(&edit.new);
+        last = edit.end;
+    }
+    new.push_str(&content[last..]);
+    new
+}
+
+/// Check that no edits, sorted by their start, overlap each other.
+fn check_overlaps(edits: &[Edit]) -> Result<()> {
+    for ed in edits.windows(2) {
+        distance(ed[0].start, ed[0].end, ed[1].start, ed[1].end).ok_or(Error::Overlap)?;
+    }
+    Ok(())
+}
+
+/// Statistics about processing content, see [`process_with_stats`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Stats {
+    /// Number of regex matches found
+    pub matches: usize,
+    /// Number of matches edited by each operator, by the index of the
+    /// operator, where a swapped pair counts once
+    pub edits: Vec<usize>,
+}
+
+impl Stats {
+    /// Add the statistics of processing other content to these.
+    pub fn add(&mut self, other: &Self) {
+        self.matches += other.matches;
+        if self.edits.len() < other.edits.len() {
+            self.edits.resize(other.edits.len(), 0);
+        }
+        for (edits, other) in self.edits.iter_mut().zip(&other.edits) {
+            *edits += other;
+        }
+    }
+}
+
+/// Represents a single text edit operation.
+///
+/// Edits are applied to the content after all matches are found to ensure
+/// non-overlapping changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    /// Start position of the text to replace
+    pub start: usize,
+    /// End position of the text to replace
+    pub end: usize,
+    /// Name of the capture the edited text matched
+    pub target: String,
+    /// The text to replace
+    pub old: String,
+    /// The new text to insert
+    pub new: String,
+    /// Indices of the operators that made the edit, see [`Edit::operators`]
+    operators: SmallVec<[usize; 2]>,
+}
+
+impl Edit {
+    /// Indices of the operators that made the edit, in the order they were
+    /// applied, more than one if operators edited the same match.
+    #[must_use]
+    pub fn operators(&self) -> &[usize] {
+        &self.operators
+    }
+}
+
+/// Create an edit operation from a regex match and operator.
+///
+/// This function determines what text transformation to apply based on the
+/// operator type and its parameters.
+///
+/// # Arguments
+///
+/// * `op` - The operator to apply
+/// * `start` - Start position of the match
+/// * `end` - End position of the match
+/// * `old` - The original matched text
+/// * `captures` - Map of all captured values (for operations using capture references)
+///
+/// # Returns
+///
+/// Returns an `Edit` struct describing the transformation to apply.
+pub fn edit<'a>(
+    op: &Operator,
+    start: usize,
+    end: usize,
+    old: &'a str,
+    captures: &CapturesMap<'a>,
+) -> Result<Edit> {
+    let new = resolve(op, old, |name| nearest_capture(captures, name, start, end))?;
+    Ok(Edit {
+        start,
+        end,
+        target: op.target.clone(),
+        old: old.to_string(),
+        new,
+        operators: SmallVec::new(),
+    })
+}
+
+/// The new text of an edit like [`edit`] makes, with the values of captures
+/// used as parameters looked up by name.
+fn resolve<'a>(
+    op: &Operator,
+    old: &'a str,
+    lookup: impl Fn(&str) -> Option<&'a str>,
+) -> Result<String> {
+    let mut new = Cow::Borrowed(old);
+    for (operation, value) in
+        std::iter::once((&op.op, &op.value)).chain(op.then.iter().map(|s| (&s.op, &s.value)))
+    {
+        let value = match value {
+            Param::Capture(name) => Param::String(
+                lookup(name)
+                    .ok_or_else(|| Error::MissingCapture(name.clone()))?
+                    .to_string(),
+            ),
+            Param::Template(pieces) => Param::String(render(pieces, old, &lookup)?),
+            v => v.clone(),
+        };
+        new = Cow::Owned(operate(operation, value, &new)?);
+    }
+    Ok(new.into_owned())
+}
+
+/// Render a template with the values of captures, looked up by name, and the
+/// original value of the match.
+fn render<'a>(
+    pieces: &[Piece],
+    old: &str,
+    lookup: impl Fn(&str) -> Option<&'a str>,
+) -> Result<String> {
+    let mut rendered = String::new();
+    for piece in pieces {
+        rendered.push_str(match piece {
+            Piece::Text(text) => text,
+            Piece::Capture(name) => {
+                lookup(name).ok_or_else(|| Error::MissingCapture(name.clone()))?
+            }
+            Piece::Old => old,
+        });
+    }
+    Ok(rendered)
+}
+
+/// Apply a single operation with a resolved parameter to a value.
+fn operate(op: &Operation, value: Param, old: &str) -> Result<String> {
+    #[cfg(feature = "decimal")]
+    if let Some(new) = operate_decimal(op, &value, old)? {
+        return Ok(new);
+    }
+    let new = match op {
+        Operation::Inc | Operation::Dec | Operation::Mul | Operation::Div => {
+            arithmetic(op, parse_value(old)?, int_param(value)?)?.to_string()
+        }
+        Operation::Replace | Operation::Swap => text_param(value)?,
+        Operation::Del => String::new(),
+        Operation::Append => format!("{old}{}", text_param(value)?),
+        Operation::Prepend => format!("{}{old}", text_param(value)?),
+        Operation::Upper => match value {
+            Param::String(locale) => to_upper(old, Some(&locale))?,
+            _ => to_upper(old, None)?,
+        },
+        Operation::Lower => match value {
+            Param::String(locale) => to_lower(old, Some(&locale))?,
+            _ => to_lower(old, None)?,
+        },
+        Operation::Ordinal => {
+            let num = parse_value(old)?.to_string();
+            let digits = num.trim_start_matches('-');
+            let last = digits[digits.len().saturating_sub(2)..]
+                .parse()
+                .unwrap_or_default();
+            format!("{num}{}", ordinal_suffix(last))
+        }
+        Operation::Trim => old.trim().to_string(),
+    };
+
+    Ok(new)
+}
+
+/// An integer operated on, of arbitrary precision with the `bigint` feature.
+#[cfg(feature = "bigint")]
+type Int = num_bigint::BigInt;
+
+/// An integer operated on, limited to an `isize` without the `bigint`
+/// feature.
+#[cfg(not(feature = "bigint"))]
+type Int = isize;
+
+/// Convert an int to the integer type operated on.
+#[allow(clippy::useless_conversion)]
+fn int(num: isize) -> Int {
+    Int::from(num)
+}
+
+/// Parse a value operated on as an integer, see [`parse_int`]. With the
+/// `bigint` feature, values too large for an `isize` are parsed as well.
+fn parse_value(s: &str) -> Result<Int> {
+    #[cfg(feature = "bigint")]
+    if let Err(Error::Int { source, .. }) = parse_int(s)
+        && matches!(
+            source.kind(),
+            std::num::IntErrorKind::PosOverflow | std::num::IntErrorKind::NegOverflow
+        )
+    {
+        return s.parse().map_err(|_| Error::Int {
+            value: s.to_string(),
+            source,
+        });
+    }
+    parse_int(s).map(int)
+}
+
+/// The parameter of an arithmetic operation as an integer.
+fn int_param(value: Param) -> Result<Int> {
+    match value {
+        Param::Int(num) => Ok(int(num)),
+        Param::String(num) => parse_value(&num),
+        value => Err(unresolved(&value)),
+    }
+}
+
+/// The parameter of a text operation as text.
+fn text_param(value: Param) -> Result<String> {
+    match value {
+        Param::Int(i) => Ok(format!("{i}")),
+        Param::String(s) => Ok(s),
+        value => Err(unresolved(&value)),
+    }
+}
+
+/// The error for a parameter referencing captures that wasn't resolved.
+fn unresolved(value: &Param) -> Error {
+    let name = value.captures().next().unwrap_or_default().to_string();
+    Error::MissingCapture(name)
+}
+
+/// Apply an arithmetic operation to two integers, failing if the result
+/// overflows.
+#[cfg(not(feature = "bigint"))]
+fn arithmetic(op: &Operation, a: Int, b: Int) -> Result<Int> {
+    let result = match op {
+        Operation::Inc => a.checked_add(b),
+        Operation::Dec => a.checked_sub(b),
+        Operation::Mul => a.checked_mul(b),
+        _ if b == 0 => return Err(Error::DivisionByZero),
+        _ => a.checked_div(b),
+    };
+    result.ok_or_else(|| Error::Overflow(a.to_string()))
+}
+
+/// Apply an arithmetic operation to decimal numbers, or `None` if the value
+/// and parameter are both ints or either is not a number.
+///
+/// The result is rounded half away from zero to the scale of the value, so
+/// `19.99` multiplied by `1.5` is `29.99`.
+#[cfg(feature = "decimal")]
+fn operate_decimal(op: &Operation, value: &Param, old: &str) -> Result<Option<String>> {
+    use rust_decimal::{Decimal, RoundingStrategy};
+
+    if !matches!(
+        op,
+        Operation::Inc | Operation::Dec | Operation::Mul | Operation::Div
+    ) {
+        return Ok(None);
+    }
+    let param = match value {
+        Param::Int(_) if parse_value(old).is_ok() => return Ok(None),
+        Param::String(s) if parse_value(old).is_ok() && parse_value(s).is_ok() => return Ok(None),
+        Param::Int(i) => Decimal::from(*i),
+        Param::String(s) => match s.parse::<Decimal>() {
+            Ok(param) => param,
+            Err(_) => return Ok(None),
+        },
+        Param::Capture(_) | Param::Template(_) => return Ok(None),
+    };
+    let Ok(number) = old.parse::<Decimal>() else {
+        return Ok(None);
+    };
+    let new = match op {
+        Operation::Inc => number.checked_add(param),
+        Operation::Dec => number.checked_sub(param),
+        Operation::Mul => number.checked_mul(param),
+        _ if param.is_zero() => return Err(Error::DivisionByZero),
+        _ => number.checked_div(param),
+    };
+    let mut new = new
+        .ok_or_else(|| Error::Overflow(old.to_string()))?
+        .round_dp_with_strategy(number.scale(), RoundingStrategy::MidpointAwayFromZero);
+    new.rescale(number.scale());
+    Ok(Some(new.to_string()))
+}
+
+/// Whether a parameter of an arithmetic operation is a number.
+fn is_number(s: &str) -> bool {
+    #[cfg(feature = "decimal")]
+    if s.parse::<rust_decimal::Decimal>().is_ok() {
+        return true;
+    }
+    parse_value(s).is_ok()
+}
+
+/// Apply an arithmetic operation to two integers of arbitrary precision.
+#[cfg(feature = "bigint")]
+fn arithmetic(op: &Operation, a: Int, b: Int) -> Result<Int> {
+    Ok(match op {
+        Operation::Inc => a + b,
+        Operation::Dec => a - b,
+        Operation::Mul => a * b,
+        _ if b == Int::default() => return Err(Error::DivisionByZero),
+        _ => a / b,
+    })
+}
+
+/// Parse a string as an integer.
+///
+/// # Errors
+///
+/// Returns an error if the string cannot be parsed as an integer.
+///
+/// # Examples
+///
+/// ```
+/// use regop::parse_int;
+///
+/// assert_eq!(parse_int("42").unwrap(), 42);
+/// assert_eq!(parse_int("-10").unwrap(), -10);
+/// assert!(parse_int("not_a_number").is_err());
+/// ```
+pub fn parse_int(s: &str) -> Result<isize> {
+    s.parse::<isize>().map_err(|e| Error::Int {
+        value: s.to_string(),
+        source: e,
+    })
+}
+
+/// Locales with special casing rules supported by `upper` and `lower`.
+const LOCALES: &[&str] = &["tr", "az"];
+
+/// Convert a string to uppercase, optionally using locale specific rules.
+///
+/// Conversion is done on the whole string so combining marks stay attached
+/// to their base characters. For `tr` and `az` the dotted and dotless `i`
+/// are mapped according to Turkic rules.
+///
+/// # Examples
+///
+/// ```
+/// use regop::to_upper;
+///
+/// assert_eq!(to_upper("istanbul", None).unwrap(), "ISTANBUL");
+/// assert_eq!(to_upper("istanbul", Some("tr")).unwrap(), "İSTANBUL");
+/// ```
+pub fn to_upper(s: &str, locale: Option<&str>) -> Result<String> {
+    match locale {
+        None => Ok(s.to_uppercase()),
+        Some("tr" | "az") => Ok(s.replace('i', "İ").to_uppercase()),
+        Some(l) => Err(Error::Locale(l.to_string())),
+    }
+}
+
+/// Convert a string to lowercase, optionally using locale specific rules.
+///
+/// Conversion is done on the whole string so combining marks stay attached
+/// to their base characters and context sensitive mappings (like the Greek
+/// final sigma) are respected. For `tr` and `az` the dotted and dotless `I`
+/// are mapped according to Turkic rules, including `I` followed by a
+/// combining dot above.
+///
+/// # Examples
+///
+/// ```
+/// use regop::to_lower;
+///
+/// assert_eq!(to_lower("ISTANBUL", None).unwrap(), "istanbul");
+/// assert_eq!(to_lower("DİYARBAKIR", Some("tr")).unwrap(), "diyarbakır");
+/// ```
+pub fn to_lower(s: &str, locale: Option<&str>) -> Result<String> {
+    match locale {
+        None => Ok(s.to_lowercase()),
+        Some("tr" | "az") => {
+            let mut mapped = String::with_capacity(s.len());
+            let mut chars = s.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    'I' if chars.peek() == Some(&'\u{307}') => {
+                        chars.next();
+                        mapped.push('i');
+                    }
+                    'I' => mapped.push('ı'),
+                    'İ' => mapped.push('i'),
+                    c => mapped.push(c),
+                }
+            }
+            Ok(mapped.to_lowercase())
+        }
+        Some(l) => Err(Error::Locale(l.to_string())),
+    }
+}
+
+/// Format an integer with its English ordinal suffix.
+///
+/// # Examples
+///
+/// ```
+/// use regop::ordinal;
+///
+/// assert_eq!(ordinal(1), "1st");
+/// assert_eq!(ordinal(12), "12th");
+/// assert_eq!(ordinal(23), "23rd");
+/// ```
+#[must_use]
+pub fn ordinal(num: isize) -> String {
+    format!("{num}{}", ordinal_suffix(num.unsigned_abs() % 100))
+}
+
+/// The ordinal suffix of a number given by its last two digits.
+const fn ordinal_suffix(n: usize) -> &'static str {
+    match (n % 10, n) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    }
+}
+
+/// Calculate the distance between two non-overlapping ranges.
+///
+/// Returns `None` if the ranges overlap, otherwise returns the distance
+/// between them.
+///
+/// # Examples
+///
+/// ```
+/// use regop::distance;
+///
+/// // Non-overlapping ranges
+/// assert_eq!(distance(0, 5, 10, 15), Some(5));
+/// assert_eq!(distance(10, 15, 0, 5), Some(5));
+///
+/// // Overlapping ranges
+/// assert_eq!(distance(0, 10, 5, 15), None);
+/// ```
+#[must_use]
+pub const fn distance(start_a: usize, end_a: usize, start_b: usize, end_b: usize) -> Option<usize> {
+    if end_a <= start_b {
+        Some(start_b - end_a)
+    } else if end_b <= start_a {
+        Some(start_a - end_b)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Helper function to create a capture from a string
+    fn capture(s: &str) -> Capture {
+        s.parse().unwrap()
+    }
+
+    // Helper function to create an operator from a string
+    fn operator(s: &str) -> Operator {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_inc_operation() {
+        let captures = vec![capture(r"version = (?<version>\d+)")];
+        let operators = vec![operator("<version>:inc")];
+        let content = "version = 5".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("version = 6".to_string()));
+    }
+
+    #[test]
+    fn test_inc_operation_with_value() {
+        let captures = vec![capture(r"version = (?<version>\d+)")];
+        let operators = vec![operator("<version>:inc:10")];
+        let content = "version = 5".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("version = 15".to_string()));
+    }
+
+    #[test]
+    fn test_dec_operation() {
+        let captures = vec![capture(r"version = (?<version>\d+)")];
+        let operators = vec![operator("<version>:dec")];
+        let content = "version = 10".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("version = 9".to_string()));
+    }
+
+    #[test]
+    fn test_dec_operation_with_value() {
+        let captures = vec![capture(r"version = (?<version>\d+)")];
+        let operators = vec![operator("<version>:dec:3")];
+        let content = "version = 10".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("version = 7".to_string()));
+    }
+
+    #[test]
+    fn test_replace_operation() {
+        let captures = vec![capture(r"name = (?<name>\w+)")];
+        let operators = vec![operator("<name>:rep:new_name")];
+        let content = "name = old_name".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("name = new_name".to_string()));
+    }
+
+    #[test]
+    fn test_replace_operation_with_number() {
+        let captures = vec![capture(r"count = (?<count>\d+)")];
+        let operators = vec![operator("<count>:rep:42")];
+        let content = "count = 10".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("count = 42".to_string()));
+    }
+
+    #[test]
+    fn test_del_operation() {
+        let captures = vec![capture(r"temp = (?<temp>\w+)")];
+        let operators = vec![operator("<temp>:del")];
+        let content = "temp = value".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("temp = ".to_string()));
+    }
+
+    #[test]
+    fn test_swap_operation() {
+        let captures = vec![
+            capture(r"first = (?<first>\w+)"),
+            capture(r"second = (?<second>\w+)"),
+        ];
+        let operators = vec![operator("<first>:swap:<second>")];
+        let content = "first = A\nsecond = B".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("first = B\nsecond = A".to_string()));
+    }
+
+    #[test]
+    fn test_swap_operation_same_regex() {
+        let captures = vec![capture(r"(?<major>\d+)\.(?<minor>\d+)\.(?<patch>\d+)")];
+        let operators = vec![operator("<major>:swap:<patch>")];
+        let content = "1.2.3".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("3.2.1".to_string()));
+    }
+
+    #[test]
+    fn test_multiple_operations() {
+        let captures = vec![capture(
+            r"version = (?<major>\d+)\.(?<minor>\d+)\.(?<patch>\d+)",
+        )];
+        let operators = vec![
+            operator("<major>:inc"),
+            operator("<minor>:dec:2"),
+            operator("<patch>:rep:0"),
+        ];
+        let content = "version = 1.5.9".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("version = 2.3.0".to_string()));
+    }
+
+    #[test]
+    fn test_capture_as_value() {
+        let captures = vec![capture(r"(?<major>\d+)\.(?<minor>\d+)\.(?<patch>\d+)")];
+        let operators = vec![operator("<major>:rep:<patch>")];
+        let content = "1.2.3".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("3.2.3".to_string()));
+    }
+
+    #[test]
+    fn test_no_matches() {
+        let captures = vec![capture(r"version = (?<version>\d+)")];
+        let operators = vec![operator("<version>:inc")];
+        let content = "no matches here".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_multiple_matches() {
+        let captures = vec![capture(r"(?<num>\d+)")];
+        let operators = vec![operator("<num>:inc")];
+        let content = "1 and 2 and 3".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("2 and 3 and 4".to_string()));
+    }
+
+    #[test]
+    fn test_process_lines_mode() {
+        let captures = vec![capture(r"value: (?<num>\d+)")];
+        let operators = vec![operator("<num>:inc")];
+        let content = "value: 5".to_string();
+
+        let result = process(true, &captures, &operators, content).unwrap();
+        assert_eq!(result, Some("value: 6".to_string()));
+    }
+
+    #[test]
+    fn test_process_lines_mode_repeated_lines() {
+        let captures = vec![capture(r"^v(?<num>\d+)$")];
+        let operators = vec![operator("<num>[3]:inc")];
+        let options = Options {
+            lines: true,
+            ..Options::default()
+        };
+        let content = "v1\nv1\r\nv1\n".to_string();
+
+        let result = process_with(&options, &captures, &operators, content).unwrap();
+        assert_eq!(result, Some("v1\nv1\r\nv2\n".to_string()));
+    }
+
+    #[test]
+    fn test_process_first_option() {
+        let captures = vec![capture(r"(?<num>\d+)")];
+        let operators = vec![operator("<num>:inc")];
+        let options = Options {
+            lines: true,
+            select: Selector::Index(1),
+            ..Options::default()
+        };
+        let content = "a\n1 2\n3".to_string();
+
+        let result = process_with(&options, &captures, &operators, content).unwrap();
+        assert_eq!(result, Some("a\n2 2\n3".to_string()));
+    }
+
+    #[test]
+    fn test_process_last_option() {
+        let captures = vec![capture(r"(?<num>\d+)")];
+        let operators = vec![operator("<num>:inc"), operator("<num>[1]:dec")];
+        let options = Options {
+            lines: true,
+            select: Selector::Last,
+            ..Options::default()
+        };
+        let content = "1 2\n3\nb".to_string();
+
+        let result = process_with(&options, &captures, &operators, content).unwrap();
+        assert_eq!(result, Some("0 2\n4\nb".to_string()));
+    }
+
+    #[test]
+    fn test_invalid_operator_format() {
+        let result = "invalid".parse::<Operator>();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_regex_options() {
+        let options = RegexOptions {
+            ignore_case: true,
+            multi_line: true,
+            ..RegexOptions::default()
+        };
+        let captures = vec![Capture::with_options(r"^version = (?<v>\d+)$", &options).unwrap()];
+        let operators = vec![operator("<v>:inc")];
+        let content = "name = x\nVersion = 1\n".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("name = x\nVersion = 2\n".to_string()));
+    }
+
+    #[test]
+    fn test_regex_options_dot_all_extended() {
+        let options = RegexOptions {
+            dot_all: true,
+            extended: true,
+            ..RegexOptions::default()
+        };
+        let captures =
+            vec![Capture::with_options(r"start .* (?<v>\d+)  # last number", &options).unwrap()];
+        let operators = vec![operator("<v>:inc")];
+        let content = "start\n1\n2".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("start\n1\n3".to_string()));
+    }
+
+    #[test]
+    fn test_fragments() {
+        let options = RegexOptions {
+            fragments: vec![
+                "num=\\d+".parse().unwrap(),
+                "ver={num}\\.{num}".parse().unwrap(),
+            ],
+            ..RegexOptions::default()
+        };
+        let captures =
+            vec![Capture::with_options(r"(?<v>{ver}) \d{2} (?<n>{num})", &options).unwrap()];
+        let operators = vec![operator("<v>:rep:2.0"), operator("<n>:inc")];
+        let content = "1.2 34 5".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("2.0 34 6".to_string()));
+
+        assert!("1num=x".parse::<Fragment>().is_err());
+        assert!("num".parse::<Fragment>().is_err());
+    }
+
+    #[test]
+    fn test_fixed_strings() {
+        let options = RegexOptions {
+            fixed_strings: true,
+            ..RegexOptions::default()
+        };
+        let captures = vec![
+            Capture::with_options("foo.bar=(?<v>*)", &options).unwrap(),
+            Capture::with_options("[(?<a>*)](*)", &options).unwrap(),
+        ];
+        let operators = vec![operator("<v>:inc"), operator("<a>:upper")];
+        let content = "fooxbar=1\nfoo.bar=1\n[x](*)\n".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("fooxbar=1\nfoo.bar=2\n[X](*)\n".to_string()));
+    }
+
+    #[test]
+    fn test_word_regexp() {
+        let options = RegexOptions {
+            word_regexp: true,
+            ..RegexOptions::default()
+        };
+        let captures = vec![
+            Capture::with_options(r"(?<name>count)", &options).unwrap(),
+            Capture::with_options(r#""(?<v>\d+)""#, &options).unwrap(),
+        ];
+        let operators = vec![operator("<name>:upper"), operator("<v>:inc")];
+        let content = r#"count discount counter "1" x"2""#.to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(
+            result,
+            Some(r#"COUNT discount counter "2" x"2""#.to_string())
+        );
+
+        let options = RegexOptions {
+            extended: true,
+            ..options
+        };
+        let capture = Capture::with_options("(?<n>\\d+) # number", &options).unwrap();
+        let result = regop(&[capture], &[operator("<n>:inc")], "1 a2".to_string()).unwrap();
+        assert_eq!(result, Some("2 a2".to_string()));
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn test_wasm() {
+        use crate::wasm::{JsCapture, JsOperator, js_process};
+
+        let capture = JsCapture::new(r"(?<key>\w+)=(\d+)").unwrap();
+        assert_eq!(capture.names(), vec!["2", "key"]);
+        assert_eq!(JsOperator::new("<key>:upper").unwrap().target(), "key");
+
+        let captures = vec![r"(?<v>\d+)".to_string()];
+        let operators = vec!["<v>:inc".to_string()];
+        let result = js_process(captures, operators, "a1\nb2".to_string(), true).unwrap();
+        assert_eq!(result, Some("a2\nb3".to_string()));
+    }
+
+    #[cfg(feature = "fancy")]
+    #[test]
+    fn test_fancy_engine_lookbehind() {
+        let options = RegexOptions {
+            ignore_case: true,
+            engine: Engine::Fancy,
+            ..RegexOptions::default()
+        };
+        let captures =
+            vec![Capture::with_options(r"(?<!# )version = (?<v>\d+)", &options).unwrap()];
+        let operators = vec![operator("<v>:inc")];
+        let content = "# version = 1\nVERSION = 1\n".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("# version = 1\nVERSION = 2\n".to_string()));
+    }
+
+    #[cfg(not(feature = "fancy"))]
+    #[test]
+    fn test_fancy_engine_without_feature() {
+        let options = RegexOptions {
+            engine: Engine::Fancy,
+            ..RegexOptions::default()
+        };
+        let result = Capture::with_options(r"(?<v>\d+)", &options);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        let result = "[invalid".parse::<Capture>();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_missing_parameter_for_replace() {
+        let result = "<test>:rep".parse::<Operator>();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_missing_parameter_for_swap() {
+        let result = "<test>:swap".parse::<Operator>();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_swap_mismatched_count() {
+        let captures = vec![
+            capture(r"first = (?<first>\w+)"),
+            capture(r"second = (?<second>\w+)"),
+        ];
+        let operators = vec![operator("<first>:swap:<second>")];
+        let content = "first = A\nfirst = B\nsecond = C".to_string();
+
+        let result = regop(&captures, &operators, content);
+        assert!(matches!(
+            result,
+            Err(Error::SwapMismatch {
+                found: 2,
+                other_found: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_swap_pairing_truncate() {
+        let captures = vec![
+            capture(r"first = (?<first>\w+)"),
+            capture(r"second = (?<second>\w+)"),
+        ];
+        let operators = vec![operator("<first>:swap:<second>")];
+        let options = Options {
+            swap_pairing: SwapPairing::Truncate,
+            ..Options::default()
+        };
+        let content = "first = A\nfirst = B\nsecond = C".to_string();
+
+        let result = process_with(&options, &captures, &operators, content).unwrap();
+        assert_eq!(result, Some("first = C\nfirst = B\nsecond = A".to_string()));
+    }
+
+    #[test]
+    fn test_swap_pairing_nearest() {
+        let captures = vec![
+            capture(r"first = (?<first>\w+)"),
+            capture(r"second = (?<second>\w+)"),
+        ];
+        let operators = vec![operator("<first>:swap:<second>")];
+        let options = Options {
+            swap_pairing: SwapPairing::Nearest,
+            ..Options::default()
+        };
+        let content = "first = A\nfirst = B\nsecond = C".to_string();
+
+        let result = process_with(&options, &captures, &operators, content).unwrap();
+        assert_eq!(result, Some("first = A\nfirst = C\nsecond = B".to_string()));
+    }
+
+    #[test]
+    fn test_swap_pairing_index_across_lines() {
+        let captures = vec![
+            capture(r"first = (?<first>\w+)"),
+            capture(r"second = (?<second>\w+)"),
+        ];
+        let operators = vec![operator("<first>:swap:<second>")];
+        let content = "first = A\nsecond = B".to_string();
+
+        let mut options = Options {
+            lines: true,
+            ..Options::default()
+        };
+        let result = process_with(&options, &captures, &operators, content.clone());
+        assert!(result.is_err());
+
+        options.swap_pairing = SwapPairing::Index;
+        let result = process_with(&options, &captures, &operators, content).unwrap();
+        assert_eq!(result, Some("first = B\nsecond = A".to_string()));
+    }
+
+    #[test]
+    fn test_value_match() {
+        let captures = vec![capture(r"(?<a>\d+) (?<b>\d+)"), capture(r"c=(?<c>\d+)")];
+        let content = "1 2 3 4 c=5".to_string();
+        let cases = [
+            (ValueMatch::Nearest, "<a>:rep:<b>", Some("2 2 4 4 c=5")),
+            (ValueMatch::SameMatch, "<a>:rep:{b}", Some("2 2 4 4 c=5")),
+            (ValueMatch::First, "<a>:rep:<b>", Some("2 2 2 4 c=5")),
+            (ValueMatch::Index(2), "<a>:rep:<b>", Some("4 2 4 4 c=5")),
+            (ValueMatch::Nearest, "<a>:rep:<c>", Some("5 2 5 4 c=5")),
+            (ValueMatch::SameMatch, "<a>:rep:<c>", None),
+            (ValueMatch::Index(3), "<a>:rep:<b>", None),
+        ];
+        for (value_match, op, expected) in cases {
+            let options = Options {
+                value_match,
+                ..Options::default()
+            };
+            let result = process_with(&options, &captures, &[operator(op)], content.clone());
+            match expected {
+                Some(expected) => assert_eq!(result.unwrap().as_deref(), Some(expected)),
+                None => assert!(matches!(result, Err(Error::MissingCapture(_)))),
+            }
+        }
+        assert!("index:0".parse::<ValueMatch>().is_err());
+        assert!("closest".parse::<ValueMatch>().is_err());
+    }
+
+    #[test]
+    fn test_expect_count() {
+        let captures = vec![capture(r"version = (?<version>\d+)")];
+        let operators = vec![operator("<version>:inc")];
+        let options = Options {
+            lines: true,
+            expect: vec!["<version>=1".parse().unwrap()],
+            ..Options::default()
+        };
+
+        let result = process_with(&options, &captures, &operators, "version = 1".to_string());
+        assert_eq!(result.unwrap(), Some("version = 2".to_string()));
+
+        let content = "version = 1\nversion = 2".to_string();
+        let result = process_with(&options, &captures, &operators, content);
+        assert!(matches!(result, Err(Error::Expectation { found: 2, .. })));
+    }
+
+    #[test]
+    fn test_expect_range() {
+        let expectation = "<dep>=1..3".parse::<Expectation>().unwrap();
+        assert!(expectation.check(0).is_err());
+        assert!(expectation.check(3).is_ok());
+        assert!(expectation.check(4).is_err());
+
+        let expectation = "<dep>=..2".parse::<Expectation>().unwrap();
+        assert!(expectation.check(0).is_ok());
+        assert!(expectation.check(3).is_err());
+    }
+
+    #[test]
+    fn test_invalid_expectation() {
+        assert!("<dep>=".parse::<Expectation>().is_err());
+        assert!("<dep>=3..1".parse::<Expectation>().is_err());
+        assert!("<dep>=x".parse::<Expectation>().is_err());
+        assert!("dep=1".parse::<Expectation>().is_err());
+    }
+
+    #[test]
+    fn test_block() {
+        let captures = vec![capture(r"(?<name>\w+) = (?<major>\d+)")];
+        let operators = vec![operator("<major>:inc")];
+        let options = Options {
+            block: Some(Block {
+                start: Regex::new(r"^\[(dev-)?dependencies\]").unwrap(),
+                end: Regex::new(r"^\[\w").unwrap(),
+            }),
+            ..Options::default()
+        };
+        let content = "[package]\nversion = 1\n[dependencies]\nregex = 1\n\
+                       [dev-dependencies]\nclap = 4\n[features]\nfancy = 1\n";
+
+        let result = process_with(&options, &captures, &operators, content.to_string());
+        assert_eq!(
+            result.unwrap(),
+            Some(
+                "[package]\nversion = 1\n[dependencies]\nregex = 2\n\
+                 [dev-dependencies]\nclap = 5\n[features]\nfancy = 1\n"
+                    .to_string()
+            )
+        );
+
+        let options = Options {
+            lines: true,
+            ..options
+        };
+        let content = "[dependencies]\nregex = 1\nclap = 4";
+        let result = process_with(&options, &captures, &operators, content.to_string());
+        assert_eq!(
+            result.unwrap(),
+            Some("[dependencies]\nregex = 2\nclap = 5".to_string())
+        );
+    }
+
+    #[test]
+    fn test_within() {
+        let captures = vec![capture(r"(?<v>\d+)")];
+        let operators = vec![operator("<v>:inc"), operator("<0>[1]:append:!")];
+        let options = Options {
+            within: vec![
+                Regex::new(r"(?s)deps \{.*?\}").unwrap(),
+                Regex::new(r"b = \d+").unwrap(),
+            ],
+            ..Options::default()
+        };
+        let content = "a = 1\ndeps {\n  a = 1\n  b = 2\n}\nb = 3\n";
+
+        let result = process_with(&options, &captures, &operators, content.to_string());
+        assert_eq!(
+            result.unwrap(),
+            Some("a = 1\ndeps {\n  a = 1\n  b = 3!\n}\nb = 3\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_skip_regex() {
+        let captures = vec![capture(r"version = (?<v>\d+)")];
+        let operators = vec![operator("<v>:inc")];
+        let options = Options {
+            skip: vec![
+                Regex::new(r"(?m)^#.*").unwrap(),
+                Regex::new(r"(?s)```.*?```").unwrap(),
+            ],
+            ..Options::default()
+        };
+        let content = "# version = 1\n```\nversion = 1\n```\nversion = 1\n";
+
+        let result = process_with(&options, &captures, &operators, content.to_string());
+        assert_eq!(
+            result.unwrap(),
+            Some("# version = 1\n```\nversion = 1\n```\nversion = 2\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_ignore_directives() {
+        let captures = vec![capture(r"v(?<v>\d+)")];
+        let operators = vec![operator("<v>:inc")];
+        let content = "v1\n# regop-ignore-next-line\nv1\nv1\n\
+                       # regop-ignore-start\nv1\nv1 # regop-ignore-end\nv1\n";
+
+        let result = regop(&captures, &operators, content.to_string());
+        assert_eq!(
+            result.unwrap(),
+            Some(
+                "v2\n# regop-ignore-next-line\nv1\nv2\n\
+                 # regop-ignore-start\nv1\nv1 # regop-ignore-end\nv2\n"
+                    .to_string()
+            )
+        );
+
+        let content = "v1\n<!-- regop-ignore-start -->\nv1\n";
+        let result = regop(&captures, &operators, content.to_string());
+        assert_eq!(
+            result.unwrap(),
+            Some("v2\n<!-- regop-ignore-start -->\nv1\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_line_endings_and_bom() {
+        let captures = vec![capture(r"^v = (?<v>\d+)$")];
+        let operators = vec![operator("<v>:inc")];
+        let options = Options {
+            lines: true,
+            ..Options::default()
+        };
+
+        let content = "\u{feff}v = 1\r\nx\r\nv = 2\r\n".to_string();
+        let result = process_with(&options, &captures, &operators, content);
+        assert_eq!(
+            result.unwrap(),
+            Some("\u{feff}v = 2\r\nx\r\nv = 3\r\n".to_string())
+        );
+
+        let captures = vec![capture(r"x\n(?<v>\d+)")];
+        let content = "x\r\n1\r\n".to_string();
+        let result = regop(&captures, &operators, content);
+        assert_eq!(result.unwrap(), Some("x\r\n2\r\n".to_string()));
+
+        let content = "x\r\n1\nx\n1".to_string();
+        let result = regop(&captures, &operators, content);
+        assert_eq!(result.unwrap(), Some("x\r\n1\nx\n2".to_string()));
+    }
+
+    #[test]
+    fn test_process_filtered() {
+        let captures = vec![capture(r"v = (?<v>\d+)")];
+        let operators = vec![operator("<v>:inc")];
+        let content = "\u{feff}v = 1\r\nv = 2\r\nv = 3\r\n".to_string();
+
+        let mut seen = Vec::new();
+        let result = process_filtered(
+            &Options::default(),
+            &captures,
+            &operators,
+            content.clone(),
+            |content, edit| {
+                seen.push(content[edit.start..edit.end].to_string());
+                edit.new != "3"
+            },
+        );
+        assert_eq!(
+            result.unwrap(),
+            Some("\u{feff}v = 2\r\nv = 2\r\nv = 4\r\n".to_string())
+        );
+        assert_eq!(seen, vec!["1", "2", "3"]);
+
+        let result = process_filtered(
+            &Options::default(),
+            &captures,
+            &operators,
+            content,
+            |_, _| false,
+        );
+        assert_eq!(result.unwrap(), None);
+    }
+
+    #[test]
+    fn test_process_with_stats() {
+        let captures = vec![capture(r"(?<a>\d+)-(?<b>\d+)")];
+        let operators = vec![operator("<*>:inc")];
+        let (result, stats) = process_with_stats(
+            &Options::default(),
+            &captures,
+            &operators,
+            "1-2 3-4".to_string(),
+        )
+        .unwrap();
+        assert_eq!(result, Some("2-3 4-5".to_string()));
+        assert_eq!(stats.matches, 2);
+        assert_eq!(stats.edits, vec![4]);
+
+        let mut total = stats;
+        total.add(&Stats {
+            matches: 1,
+            edits: vec![1, 2],
+        });
+        assert_eq!(total.matches, 3);
+        assert_eq!(total.edits, vec![5, 2]);
+    }
+
+    #[test]
+    fn test_find_matches() {
+        let captures = vec![capture(r"(?<key>\w+) = (?<value>\d+)")];
+        let options = Options {
+            lines: true,
+            skip: vec![Regex::new(r"(?m)^#.*").unwrap()],
+            ..Options::default()
+        };
+        let matches = find_matches(&options, &captures, "a = 1\n# b = 2\nc = 3\n").unwrap();
+        assert_eq!(
+            matches,
+            vec![
+                NamedMatch {
+                    name: "key".to_string(),
+                    start: 0,
+                    end: 1,
+                    value: "a".to_string(),
+                },
+                NamedMatch {
+                    name: "value".to_string(),
+                    start: 4,
+                    end: 5,
+                    value: "1".to_string(),
+                },
+                NamedMatch {
+                    name: "key".to_string(),
+                    start: 14,
+                    end: 15,
+                    value: "c".to_string(),
+                },
+                NamedMatch {
+                    name: "value".to_string(),
+                    start: 18,
+                    end: 19,
+                    value: "3".to_string(),
+                },
+            ]
+        );
+
+        let options = Options {
+            skip_if: vec![Regex::new(r"^// @generated\b").unwrap()],
+            ..Options::default()
+        };
+        let matches = find_matches(&options, &captures, "// @generated\na = 1").unwrap();
+        assert!(matches.is_empty());
+    }
+
+    #[test]
+    fn test_find_full_matches() {
+        let captures = vec![capture(r"v(?<major>\d+)(?:\.(?<minor>\d+))?"), capture("x")];
+        let matches = find_full_matches(&Options::default(), &captures, "v1 x v2.3").unwrap();
+        let found = matches
+            .iter()
+            .map(|m| (m.value.as_str(), m.get("major"), m.get("minor")))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                ("v1", Some("1"), None),
+                ("x", None, None),
+                ("v2.3", Some("2"), Some("3"))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_type_check() {
+        let captures = vec![capture(r"port = (?<port>\S+)")];
+        let operators = vec![operator("<port>[1]:inc")];
+        let options = Options {
+            types: vec!["<port>:int:1..65535".parse().unwrap()],
+            ..Options::default()
+        };
+
+        let content = "port = 80\nport = 443".to_string();
+        let result = process_with(&options, &captures, &operators, content);
+        assert_eq!(result.unwrap(), Some("port = 81\nport = 443".to_string()));
+
+        let content = "port = 80\nport = http".to_string();
+        let result = process_with(&options, &captures, &operators, content);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "value 'http' of '<port>' is not an int"
+        );
+
+        let content = "port = 80\nport = 70000".to_string();
+        let result = process_with(&options, &captures, &operators, content);
+        assert!(matches!(result, Err(Error::Type { value, .. }) if value == "70000"));
+    }
+
+    #[test]
+    fn test_invalid_type_check() {
+        assert!("<port>:float".parse::<TypeCheck>().is_err());
+        assert!("<port>:int:10..1".parse::<TypeCheck>().is_err());
+        assert!("<port>".parse::<TypeCheck>().is_err());
+        assert_eq!(
+            "<n>:int:-5..".parse::<TypeCheck>().unwrap().kind,
+            ValueType::Int {
+                min: Some(-5),
+                max: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_numbered_groups() {
+        let captures = vec![capture(r"(\d+)\.(\d+)\.(\d+)")];
+        let operators = vec![operator("<1>:inc"), operator("<3>:rep:<2>")];
+        let content = "1.2.3".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("2.2.2".to_string()));
+    }
+
+    #[test]
+    fn test_numbered_groups_mixed_with_named() {
+        let captures = vec![capture(r"(?<major>\d+)\.(\d+)")];
+        let operators = vec![operator("<major>:inc"), operator("<2>:dec")];
+        let content = "1.2".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("2.1".to_string()));
+    }
+
+    #[test]
+    fn test_whole_match_target() {
+        let captures = vec![capture(r"debug = \w+\n")];
+        let operators = vec![operator("<0>:del")];
+        let content = "a = 1\ndebug = true\nb = 2\n".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("a = 1\nb = 2\n".to_string()));
+    }
+
+    #[test]
+    fn test_whole_match_not_in_wildcard() {
+        let captures = vec![capture(r"(?<key>\w+)=\w+")];
+        let operators = vec![operator("<*>:upper")];
+        let content = "key=value".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("KEY=value".to_string()));
+    }
+
+    #[test]
+    fn test_parse_int_success() {
+        assert_eq!(parse_int("42").unwrap(), 42);
+        assert_eq!(parse_int("-10").unwrap(), -10);
+    }
+
+    #[test]
+    fn test_parse_int_failure() {
+        assert!(parse_int("not_a_number").is_err());
+    }
+
+    #[test]
+    fn test_distance_function() {
+        assert_eq!(distance(0, 5, 10, 15), Some(5));
+        assert_eq!(distance(10, 15, 0, 5), Some(5));
+        assert_eq!(distance(0, 10, 5, 15), None); // Overlapping
+        assert_eq!(distance(5, 15, 0, 10), None); // Overlapping
+    }
+
+    #[test]
+    fn test_param_from_str() {
+        // Test integer parsing
+        let param = Param::from("42");
+        matches!(param, Param::Int(42));
+
+        // Test string parsing
+        let param = Param::from("hello");
+        matches!(param, Param::String(_));
+
+        // Test capture parsing
+        let param = Param::from("<capture>");
+        matches!(param, Param::Capture(_));
+    }
+
+    #[test]
+    fn test_negative_numbers() {
+        let captures = vec![capture(r"value = (?<value>-?\d+)")];
+        let operators = vec![operator("<value>:inc:5")];
+        let content = "value = -10".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("value = -5".to_string()));
+    }
+
+    #[test]
+    fn test_zero_operations() {
+        let captures = vec![capture(r"value = (?<value>\d+)")];
+        let operators = vec![operator("<value>:inc:0")];
+        let content = "value = 5".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("value = 5".to_string()));
+    }
+
+    #[test]
+    fn test_large_numbers() {
+        let captures = vec![capture(r"value = (?<value>\d+)")];
+        let operators = vec![operator("<value>:inc:1000000")];
+        let content = "value = 999999".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("value = 1999999".to_string()));
+    }
+
+    #[test]
+    fn test_empty_string_replacement() {
+        let captures = vec![capture(r"text = (?<text>\w*)")];
+        let operators = vec![operator("<text>:del")];
+        let content = "text = hello".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("text = ".to_string()));
+    }
+
+    #[test]
+    fn test_special_characters_in_replacement() {
+        let captures = vec![capture(r"text = (?<text>\w+)")];
+        let operators = vec![operator("<text>:rep:hello@world.com")];
+        let content = "text = old".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("text = hello@world.com".to_string()));
+    }
+
+    #[test]
+    fn test_unicode_support() {
+        let captures = vec![capture(r"name = (?<name>\w+)")];
+        let operators = vec![operator("<name>:rep:josé")];
+        let content = "name = john".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("name = josé".to_string()));
+    }
+
+    #[test]
+    fn test_mixed_operations_order() {
+        let captures = vec![capture(r"(?<a>\d+) (?<b>\d+) (?<c>\d+)")];
+        let operators = vec![
+            operator("<c>:inc:1"),
+            operator("<a>:dec:1"),
+            operator("<b>:rep:99"),
+        ];
+        let content = "5 10 15".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("4 99 16".to_string()));
+    }
+
+    #[test]
+    fn test_capture_group_not_found() {
+        let captures = vec![capture(r"version = (?<version>\d+)")];
+        let operators = vec![operator("<nonexistent>:inc")];
+        let content = "version = 5".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_multiple_regex_patterns() {
+        let captures = vec![
+            capture(r"version = (?<version>\d+)"),
+            capture(r"count = (?<count>\d+)"),
+        ];
+        let operators = vec![operator("<version>:inc"), operator("<count>:dec")];
+        let content = "version = 1\ncount = 10".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("version = 2\ncount = 9".to_string()));
+    }
+
+    #[test]
+    fn test_overlapping_matches_error() {
+        let captures = vec![capture(r"(?<all>\w+(?<part>\w+))")];
+        let operators = vec![operator("<all>:rep:new"), operator("<part>:rep:part")];
+        let content = "hello".to_string();
+
+        let result = regop(&captures, &operators, content);
+        assert!(matches!(result, Err(Error::Overlap)));
+    }
+
+    #[test]
+    fn test_string_increment_with_capture() {
+        let captures = vec![capture(r"(?<a>\d+) plus (?<b>\d+)")];
+        let operators = vec![operator("<a>:inc:<b>")];
+        let content = "5 plus 3".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("8 plus 3".to_string()));
+    }
+
+    #[test]
+    fn test_dec_with_string_capture() {
+        let captures = vec![capture(r"(?<a>\d+) minus (?<b>\d+)")];
+        let operators = vec![operator("<a>:dec:<b>")];
+        let content = "10 minus 3".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("7 minus 3".to_string()));
+    }
+
+    #[test]
+    fn test_whitespace_handling() {
+        let captures = vec![capture(r"value\s*=\s*(?<value>\d+)")];
+        let operators = vec![operator("<value>:inc")];
+        let content = "value   =   5".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("value   =   6".to_string()));
+    }
+
+    #[test]
+    fn test_case_sensitive_regex() {
+        let captures = vec![capture(r"Version = (?<version>\d+)")];
+        let operators = vec![operator("<version>:inc")];
+        let content = "version = 5".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_replace_with_space() {
+        let captures = vec![capture(r"text = (?<text>\w+)")];
+        let operators = vec![operator("<text>:rep: ")];
+        let content = "text = hello".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("text =  ".to_string()));
+    }
+
+    #[test]
+    fn test_mul_operation() {
+        let captures = vec![capture(r"value = (?<value>\d+)")];
+        let operators = vec![operator("<value>:mul:3")];
+        let content = "value = 5".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("value = 15".to_string()));
+    }
+
+    #[test]
+    fn test_mul_operation_with_capture() {
+        let captures = vec![capture(r"(?<a>\d+) times (?<b>\d+)")];
+        let operators = vec![operator("<a>:mul:<b>")];
+        let content = "4 times 6".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("24 times 6".to_string()));
+    }
+
+    #[test]
+    fn test_div_operation() {
+        let captures = vec![capture(r"value = (?<value>\d+)")];
+        let operators = vec![operator("<value>:div:2")];
+        let content = "value = 10".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("value = 5".to_string()));
+    }
+
+    #[test]
+    fn test_div_operation_with_capture() {
+        let captures = vec![capture(r"(?<a>\d+) divided by (?<b>\d+)")];
+        let operators = vec![operator("<a>:div:<b>")];
+        let content = "20 divided by 4".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("5 divided by 4".to_string()));
+    }
+
+    #[test]
+    fn test_div_by_zero_error() {
+        let captures = vec![capture(r"value = (?<value>\d+)")];
+        let operators = vec![operator("<value>:div:0")];
+        let content = "value = 10".to_string();
+
+        let result = regop(&captures, &operators, content);
+        assert!(matches!(result, Err(Error::DivisionByZero)));
+    }
+
+    #[test]
+    fn test_append_operation() {
+        let captures = vec![capture(r"name = (?<name>\w+)")];
+        let operators = vec![operator("<name>:append:_suffix")];
+        let content = "name = test".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("name = test_suffix".to_string()));
+    }
+
+    #[test]
+    fn test_append_operation_with_number() {
+        let captures = vec![capture(r"version = (?<version>\d+)")];
+        let operators = vec![operator("<version>:append:42")];
+        let content = "version = 1".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("version = 142".to_string()));
+    }
+
+    #[test]
+    fn test_prepend_operation() {
+        let captures = vec![capture(r"name = (?<name>\w+)")];
+        let operators = vec![operator("<name>:prepend:prefix_")];
+        let content = "name = test".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("name = prefix_test".to_string()));
+    }
+
+    #[test]
+    fn test_prepend_operation_with_number() {
+        let captures = vec![capture(r"version = (?<version>\d+)")];
+        let operators = vec![operator("<version>:prepend:v")];
+        let content = "version = 123".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("version = v123".to_string()));
+    }
+
+    #[test]
+    fn test_upper_operation() {
+        let captures = vec![capture(r"text = (?<text>\w+)")];
+        let operators = vec![operator("<text>:upper")];
+        let content = "text = hello".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("text = HELLO".to_string()));
+    }
+
+    #[test]
+    fn test_upper_operation_mixed_case() {
+        let captures = vec![capture(r"name = (?<name>[A-Za-z]+)")];
+        let operators = vec![operator("<name>:upper")];
+        let content = "name = JohnDoe".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("name = JOHNDOE".to_string()));
+    }
+
+    #[test]
+    fn test_lower_operation() {
+        let captures = vec![capture(r"text = (?<text>\w+)")];
+        let operators = vec![operator("<text>:lower")];
+        let content = "text = HELLO".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("text = hello".to_string()));
+    }
+
+    #[test]
+    fn test_lower_operation_mixed_case() {
+        let captures = vec![capture(r"name = (?<name>[A-Za-z]+)")];
+        let operators = vec![operator("<name>:lower")];
+        let content = "name = JohnDoe".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("name = johndoe".to_string()));
+    }
+
+    #[test]
+    fn test_upper_operation_turkish() {
+        let captures = vec![capture(r"city = (?<city>\w+)")];
+        let operators = vec![operator("<city>:upper:tr")];
+        let content = "city = istanbul".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("city = İSTANBUL".to_string()));
+    }
+
+    #[test]
+    fn test_lower_operation_turkish() {
+        let captures = vec![capture(r"city = (?<city>\S+)")];
+        let operators = vec![operator("<city>:lower:tr")];
+        let content = "city = DİYARBAKIR".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("city = diyarbakır".to_string()));
+    }
+
+    #[test]
+    fn test_lower_grapheme_safe() {
+        // Combining dot above is folded into the base letter in Turkish
+        assert_eq!(to_lower("I\u{307}", Some("tr")).unwrap(), "i");
+        // Other combining marks stay attached to their base
+        assert_eq!(to_lower("E\u{301}", None).unwrap(), "e\u{301}");
+        // Final sigma is context sensitive
+        assert_eq!(to_lower("ΟΔΟΣ", None).unwrap(), "οδος");
+    }
+
+    #[test]
+    fn test_invalid_locale() {
+        let result = "<test>:upper:xx".parse::<Operator>();
+        assert!(result.is_err());
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("not a supported locale")
+        );
+    }
+
+    #[test]
+    fn test_multiple_new_operations() {
+        let captures = vec![capture(r"(?<text>\w+) = (?<value>\d+)")];
+        let operators = vec![operator("<text>:upper"), operator("<value>:mul:2")];
+        let content = "count = 5".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("COUNT = 10".to_string()));
+    }
+
+    #[test]
+    fn test_missing_parameter_for_mul() {
+        let result = "<test>:mul".parse::<Operator>();
+        assert!(result.is_err());
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("parameter required in 'mul' operator")
+        );
+    }
+
+    #[test]
+    fn test_missing_parameter_for_div() {
+        let result = "<test>:div".parse::<Operator>();
+        assert!(result.is_err());
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("parameter required in 'div' operator")
+        );
+    }
+
+    #[test]
+    fn test_missing_parameter_for_append() {
+        let result = "<test>:append".parse::<Operator>();
+        assert!(result.is_err());
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("parameter required in 'append' operator")
+        );
+    }
+
+    #[test]
+    fn test_missing_parameter_for_prepend() {
+        let result = "<test>:prepend".parse::<Operator>();
+        assert!(result.is_err());
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("parameter required in 'prepend' operator")
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_select_index() {
+        let captures = vec![capture(r"(?<num>\d+)")];
+        let operators = vec![operator("<num>[2]:inc")];
+        let content = "1 and 2 and 3".to_string();
 
-    // Helper function to create a capture from a string
-    fn capture(s: &str) -> Capture {
-        s.parse().unwrap()
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("1 and 3 and 3".to_string()));
     }
 
-    // Helper function to create an operator from a string
-    fn operator(s: &str) -> Operator {
-        s.parse().unwrap()
+    #[test]
+    fn test_select_last() {
+        let captures = vec![capture(r"(?<num>\d+)")];
+        let operators = vec![operator("<num>[last]:rep:9")];
+        let content = "1 and 2 and 3".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("1 and 2 and 9".to_string()));
     }
 
     #[test]
-    fn test_inc_operation() {
-        let captures = vec![capture(r"version = (?<version>\d+)")];
-        let operators = vec![operator("<version>:inc")];
-        let content = "version = 5".to_string();
+    fn test_select_out_of_range() {
+        let captures = vec![capture(r"(?<num>\d+)")];
+        let operators = vec![operator("<num>[5]:inc")];
+        let content = "1 and 2 and 3".to_string();
 
         let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("version = 6".to_string()));
+        assert_eq!(result, None);
     }
 
     #[test]
-    fn test_inc_operation_with_value() {
-        let captures = vec![capture(r"version = (?<version>\d+)")];
-        let operators = vec![operator("<version>:inc:10")];
-        let content = "version = 5".to_string();
+    fn test_select_swap() {
+        let captures = vec![capture(r"(?<a>\w)=(?<b>\w)")];
+        let operators = vec![operator("<a>[2]:swap:<b>")];
+        let content = "a=b c=d".to_string();
 
         let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("version = 15".to_string()));
+        assert_eq!(result, Some("a=b d=c".to_string()));
     }
 
     #[test]
-    fn test_dec_operation() {
-        let captures = vec![capture(r"version = (?<version>\d+)")];
-        let operators = vec![operator("<version>:dec")];
-        let content = "version = 10".to_string();
+    fn test_select_range() {
+        let captures = vec![capture(r"(?<num>\d+)")];
+        let operators = vec![operator("<num>[2..3]:inc")];
+        let content = "1 2 3 4".to_string();
 
         let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("version = 9".to_string()));
+        assert_eq!(result, Some("1 3 4 4".to_string()));
     }
 
     #[test]
-    fn test_dec_operation_with_value() {
-        let captures = vec![capture(r"version = (?<version>\d+)")];
-        let operators = vec![operator("<version>:dec:3")];
-        let content = "version = 10".to_string();
+    fn test_select_open_ranges() {
+        let captures = vec![capture(r"(?<num>\d+)")];
+        let operators = vec![operator("<num>[..2]:inc"), operator("<num>[4..]:dec")];
+        let content = "1 2 3 4 5".to_string();
 
         let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("version = 7".to_string()));
+        assert_eq!(result, Some("2 3 3 3 4".to_string()));
     }
 
     #[test]
-    fn test_replace_operation() {
-        let captures = vec![capture(r"name = (?<name>\w+)")];
-        let operators = vec![operator(r#"<name>:rep:new_name"#)];
-        let content = "name = old_name".to_string();
+    fn test_select_range_past_end() {
+        let captures = vec![capture(r"(?<num>\d+)")];
+        let operators = vec![operator("<num>[2..10]:inc")];
+        let content = "1 2 3".to_string();
 
         let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("name = new_name".to_string()));
+        assert_eq!(result, Some("1 3 4".to_string()));
     }
 
     #[test]
-    fn test_replace_operation_with_number() {
-        let captures = vec![capture(r"count = (?<count>\d+)")];
-        let operators = vec![operator("<count>:rep:42")];
-        let content = "count = 10".to_string();
+    fn test_invalid_selector() {
+        assert!("<num>[3..2]:inc".parse::<Operator>().is_err());
+        assert!("<num>[0..2]:inc".parse::<Operator>().is_err());
+        assert!("<num>[0]:inc".parse::<Operator>().is_err());
+        assert!("<num>[first]:inc".parse::<Operator>().is_err());
+    }
+
+    #[test]
+    fn test_equality_guard() {
+        let captures = vec![capture(r"env = (?<env>\w+)")];
+        let operators = vec![operator("<env>?=staging:rep:production")];
+        let content = "env = staging\nenv = dev".to_string();
 
         let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("count = 42".to_string()));
+        assert_eq!(result, Some("env = production\nenv = dev".to_string()));
     }
 
     #[test]
-    fn test_del_operation() {
-        let captures = vec![capture(r"temp = (?<temp>\w+)")];
-        let operators = vec![operator("<temp>:del")];
-        let content = "temp = value".to_string();
+    fn test_equality_guard_no_match() {
+        let captures = vec![capture(r"env = (?<env>\w+)")];
+        let operators = vec![operator("<env>?=staging:rep:production")];
+        let content = "env = dev".to_string();
 
         let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("temp = ".to_string()));
+        assert_eq!(result, None);
     }
 
     #[test]
-    fn test_swap_operation() {
-        let captures = vec![
-            capture(r"first = (?<first>\w+)"),
-            capture(r"second = (?<second>\w+)"),
-        ];
-        let operators = vec![operator("<first>:swap:<second>")];
-        let content = "first = A\nsecond = B".to_string();
+    fn test_numeric_guard() {
+        let captures = vec![capture(r"replicas: (?<replicas>\d+)")];
+        let operators = vec![operator("<replicas>:inc:5?if-lt:10")];
+        let content = "replicas: 3\nreplicas: 12".to_string();
 
         let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("first = B\nsecond = A".to_string()));
+        assert_eq!(result, Some("replicas: 8\nreplicas: 12".to_string()));
     }
 
     #[test]
-    fn test_swap_operation_same_regex() {
-        let captures = vec![capture(r"(?<major>\d+)\.(?<minor>\d+)\.(?<patch>\d+)")];
-        let operators = vec![operator("<major>:swap:<patch>")];
-        let content = "1.2.3".to_string();
+    fn test_multiple_numeric_guards() {
+        let captures = vec![capture(r"(?<num>\d+)")];
+        let operators = vec![operator("<num>:rep:x?if-ge:2?if-le:3")];
+        let content = "1 2 3 4".to_string();
 
         let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("3.2.1".to_string()));
+        assert_eq!(result, Some("1 x x 4".to_string()));
     }
 
     #[test]
-    fn test_multiple_operations() {
+    fn test_invalid_numeric_guard() {
+        assert!("<num>:inc?if-eq:3".parse::<Operator>().is_err());
+        assert!("<num>:inc?if-lt:abc".parse::<Operator>().is_err());
+        assert!("<num>:inc?if-lt".parse::<Operator>().is_err());
+    }
+
+    #[test]
+    fn test_templates() {
         let captures = vec![capture(
-            r"version = (?<major>\d+)\.(?<minor>\d+)\.(?<patch>\d+)",
+            r"(?<full>(?<major>\d+)\.(?<minor>\d+)\.\d+)(?: \((?<tag>\w+)\))?",
         )];
-        let operators = vec![
-            operator("<major>:inc"),
-            operator("<minor>:dec:2"),
-            operator("<patch>:rep:0"),
+        let content = "1.2.3 (beta) 4.5.6".to_string();
+        let cases = [
+            (
+                "<full>:rep:{major}.{minor}.0-${old}",
+                "1.2.0-1.2.3 (beta) 4.5.0-4.5.6",
+            ),
+            ("<full>[1]:append:+{tag}", "1.2.3+beta (beta) 4.5.6"),
+            ("<full>[1]:prepend:\"{tag} \"", "beta 1.2.3 (beta) 4.5.6"),
+            ("<full>[1]:rep:'{major}'", "{major} (beta) 4.5.6"),
+            (
+                r"<full>[1]:rep:\{major}-$\{old}",
+                "{major}-${old} (beta) 4.5.6",
+            ),
+            ("<full>[1]:rep:{}|append:{ x }", "{}{ x } (beta) 4.5.6"),
+            ("<major>:inc:{minor}", "3.2.3 (beta) 9.5.6"),
         ];
-        let content = "version = 1.5.9".to_string();
+        for (op, expected) in cases {
+            let result = regop(&captures, &[operator(op)], content.clone()).unwrap();
+            assert_eq!(result.as_deref(), Some(expected), "{op}");
+        }
 
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("version = 2.3.0".to_string()));
+        let missing = regop(
+            &captures,
+            &[operator("<full>:rep:{tag}")],
+            "4.5.6".to_string(),
+        );
+        assert!(matches!(missing, Err(Error::MissingCapture(name)) if name == "tag"));
+        assert!("<full>:rep:${new}".parse::<Operator>().is_err());
+        let issues = validate(&captures, &[operator("<full>:rep:{nope}")]).unwrap_err();
+        assert!(
+            matches!(&issues[0], ValidationIssue::UnknownCapture { name, .. } if name == "nope")
+        );
     }
 
     #[test]
-    fn test_capture_as_value() {
-        let captures = vec![capture(r"(?<major>\d+)\.(?<minor>\d+)\.(?<patch>\d+)")];
-        let operators = vec![operator("<major>:rep:<patch>")];
-        let content = "1.2.3".to_string();
+    fn test_operator_quoting() {
+        let captures = vec![capture(r"url = (?<url>\S+)")];
+        let content = "url = old".to_string();
+        let cases = [
+            ("<url>:rep:http://example.com", "url = http://example.com"),
+            ("<url>:rep:a:b|upper", "url = A:B"),
+            ("<url>:rep:'a|b'|append:!", "url = a|b!"),
+            ("<url>:rep:\"<b> \\\"x\\\"\"", "url = <b> \"x\""),
+            ("<url>:rep:' padded '", "url =  padded "),
+            (r"<url>:append:\<b\>\|c", "url = old<b>|c"),
+            (r"<url>:rep:a\b", r"url = a\b"),
+            ("<url>:rep:x@max=1", "url = x"),
+        ];
+        for (op, expected) in cases {
+            let result = regop(&captures, &[operator(op)], content.clone()).unwrap();
+            assert_eq!(result.as_deref(), Some(expected), "{op}");
+        }
 
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("3.2.3".to_string()));
+        let guarded = operator("<url>:rep:x?if-gt:0");
+        assert!(matches!(&guarded.value, Param::String(v) if v == "x"));
+        assert_eq!(guarded.guards.len(), 1);
+        assert!("<url>:rep:'open".parse::<Operator>().is_err());
+        assert!("<url>:rep:'a'b".parse::<Operator>().is_err());
+        let op = operator("<num>:inc:2|mul:3");
+        assert!(matches!(op.value, Param::Int(2)));
+        assert!(matches!(op.then[0].value, Param::Int(3)));
+        assert!(matches!(operator("<a>:swap:<b>").value, Param::Capture(_)));
+        assert!(matches!(operator("<a>:rep:'5'").value, Param::String(_)));
     }
 
     #[test]
-    fn test_no_matches() {
+    fn test_when_condition() {
+        let captures = vec![capture(r"(?<env>\w+): replicas = (?<replicas>\d+)")];
+        let operators = vec![operator("<replicas>:inc")];
+        let options = Options {
+            lines: true,
+            when: vec!["<env>=staging".parse().unwrap()],
+            ..Options::default()
+        };
+        let content = "dev: replicas = 1\nstaging: replicas = 2".to_string();
+
+        let result = process_with(&options, &captures, &operators, content).unwrap();
+        assert_eq!(
+            result,
+            Some("dev: replicas = 1\nstaging: replicas = 3".to_string())
+        );
+    }
+
+    #[test]
+    fn test_when_regex_condition() {
+        let captures = vec![capture(r#"(?<name>\w+) = "(?<version>[^"]+)""#)];
+        let operators = vec![operator("<name>:prepend:pre_")];
+        let options = Options {
+            lines: true,
+            when: vec![r"<version>~^0\.".parse().unwrap()],
+            ..Options::default()
+        };
+        let content = "anyhow = \"1.0.95\"\nregop = \"0.5.5\"".to_string();
+
+        let result = process_with(&options, &captures, &operators, content).unwrap();
+        assert_eq!(
+            result,
+            Some("anyhow = \"1.0.95\"\npre_regop = \"0.5.5\"".to_string())
+        );
+    }
+
+    #[test]
+    fn test_skip_if() {
         let captures = vec![capture(r"version = (?<version>\d+)")];
         let operators = vec![operator("<version>:inc")];
-        let content = "no matches here".to_string();
+        let options = Options {
+            skip_if: vec![Regex::new(r"^# managed-by: regop-v\d").unwrap()],
+            ..Options::default()
+        };
 
-        let result = regop(&captures, &operators, content).unwrap();
+        let content = "# managed-by: regop-v2\nversion = 1".to_string();
+        let result = process_with(&options, &captures, &operators, content).unwrap();
         assert_eq!(result, None);
+
+        let content = "version = 1".to_string();
+        let result = process_with(&options, &captures, &operators, content).unwrap();
+        assert_eq!(result, Some("version = 2".to_string()));
     }
 
     #[test]
-    fn test_multiple_matches() {
-        let captures = vec![capture(r"(?<num>\d+)")];
-        let operators = vec![operator("<num>:inc")];
-        let content = "1 and 2 and 3".to_string();
-
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("2 and 3 and 4".to_string()));
+    fn test_invalid_condition() {
+        assert!("env=staging".parse::<Condition>().is_err());
+        assert!("<env>~[invalid".parse::<Condition>().is_err());
     }
 
     #[test]
-    fn test_process_lines_mode() {
-        let captures = vec![capture(r"value: (?<num>\d+)")];
-        let operators = vec![operator("<num>:inc")];
-        let content = "value: 5".to_string();
+    fn test_trim_operation() {
+        let captures = vec![capture(r"name =(?<name>[^;]+);")];
+        let operators = vec![operator("<name>:trim")];
+        let content = "name =  test ;".to_string();
 
-        let result = process(true, &captures, &operators, content).unwrap();
-        assert_eq!(result, Some("value: 6".to_string()));
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("name =test;".to_string()));
     }
 
     #[test]
-    fn test_invalid_operator_format() {
-        let result = "invalid".parse::<Operator>();
-        assert!(result.is_err());
+    fn test_pipeline() {
+        let captures = vec![capture(r"name =(?<name>[^;]+);")];
+        let operators = vec![operator("<name>:trim|lower|prepend:img_")];
+        let content = "name = Logo ;".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("name =img_logo;".to_string()));
     }
 
     #[test]
-    fn test_invalid_regex() {
-        let result = "[invalid".parse::<Capture>();
-        assert!(result.is_err());
+    fn test_pipeline_with_capture_param() {
+        let captures = vec![capture(r"(?<a>\d+) (?<b>\d+)")];
+        let operators = vec![operator("<a>:inc:<b>|mul:2")];
+        let content = "1 2".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("6 2".to_string()));
     }
 
     #[test]
-    fn test_missing_parameter_for_replace() {
-        let result = "<test>:rep".parse::<Operator>();
-        assert!(result.is_err());
+    fn test_pipeline_invalid() {
+        assert!("<a>:inc|swap:<b>".parse::<Operator>().is_err());
+        assert!("<a>:inc|nope".parse::<Operator>().is_err());
+        assert!("<a>:inc|rep".parse::<Operator>().is_err());
     }
 
     #[test]
-    fn test_missing_parameter_for_swap() {
-        let result = "<test>:swap".parse::<Operator>();
-        assert!(result.is_err());
+    fn test_compose_operators_on_same_capture() {
+        let captures = vec![capture(r"value = (?<value>\d+)")];
+        let operators = vec![operator("<value>:inc"), operator("<value>:mul:2")];
+        let content = "value = 4".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("value = 10".to_string()));
     }
 
     #[test]
-    fn test_swap_mismatched_count() {
-        let captures = vec![
-            capture(r"first = (?<first>\w+)"),
-            capture(r"second = (?<second>\w+)"),
+    fn test_compose_operators_with_selectors() {
+        let captures = vec![capture(r"(?<num>\d+)")];
+        let operators = vec![
+            operator("<num>:inc"),
+            operator("<num>[2]:mul:10"),
+            operator("<num>[last]:prepend:v"),
         ];
-        let operators = vec![operator("<first>:swap:<second>")];
-        let content = "first = A\nfirst = B\nsecond = C".to_string();
+        let content = "1 2 3".to_string();
 
-        let result = regop(&captures, &operators, content);
-        assert!(result.is_err());
-        assert!(
-            result
-                .unwrap_err()
-                .to_string()
-                .contains("different number of matches")
-        );
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("2 30 v4".to_string()));
     }
 
     #[test]
-    fn test_parse_int_success() {
-        assert_eq!(parse_int("42").unwrap(), 42);
-        assert_eq!(parse_int("-10").unwrap(), -10);
+    fn test_compose_after_swap() {
+        let captures = vec![capture(r"(?<a>\d+)\.(?<b>\d+)")];
+        let operators = vec![operator("<a>:swap:<b>"), operator("<a>:inc")];
+        let content = "1.2".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("3.1".to_string()));
     }
 
     #[test]
-    fn test_parse_int_failure() {
-        assert!(parse_int("not_a_number").is_err());
+    fn test_swap_after_compose() {
+        let captures = vec![capture(r"(?<a>\d+)\.(?<b>\d+)")];
+        let operators = vec![operator("<a>:inc"), operator("<a>:swap:<b>")];
+        let content = "1.5".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("5.2".to_string()));
     }
 
     #[test]
-    fn test_distance_function() {
-        assert_eq!(distance(0, 5, 10, 15), Some(5));
-        assert_eq!(distance(10, 15, 0, 5), Some(5));
-        assert_eq!(distance(0, 10, 5, 15), None); // Overlapping
-        assert_eq!(distance(5, 15, 0, 10), None); // Overlapping
+    fn test_same_capture_in_multiple_regexes() {
+        let captures = vec![capture(r"(?<num>\d+)"), capture(r"v(?<num>\d+)")];
+        let operators = vec![operator("<num>:inc")];
+        let content = "v1".to_string();
+
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("v2".to_string()));
     }
 
     #[test]
-    fn test_param_from_str() {
-        // Test integer parsing
-        let param = Param::from("42");
-        matches!(param, Param::Int(42));
+    fn test_operator_match_limit() {
+        let captures = vec![capture(r"(?<num>\d+)")];
+        let operators = vec![operator("<num>:inc@max=2")];
+
+        let result = regop(&captures, &operators, "1 2".to_string()).unwrap();
+        assert_eq!(result, Some("2 3".to_string()));
+
+        let result = regop(&captures, &operators, "1 2 3".to_string());
+        assert!(matches!(
+            result,
+            Err(Error::OperatorLimit {
+                count: 3,
+                max: 2,
+                ..
+            })
+        ));
+    }
 
-        // Test string parsing
-        let param = Param::from("hello");
-        matches!(param, Param::String(_));
+    #[test]
+    fn test_operator_match_limit_with_guard() {
+        let captures = vec![capture(r"(?<num>\d+)")];
+        for op in [
+            "<num>:inc?if-lt:3@max=2",
+            "<num>:inc@max=2?if-lt:3",
+            "<num>:inc?if-gt:0@max=2?if-lt:3",
+        ] {
+            let operators = vec![operator(op)];
+            let result = regop(&captures, &operators, "1 2 3".to_string()).unwrap();
+            assert_eq!(result, Some("2 3 3".to_string()), "{op}");
+        }
+        assert!("<num>:inc@max=2?if-lt:3@max=3".parse::<Operator>().is_err());
+    }
 
-        // Test capture parsing
-        let param = Param::from("<capture>");
-        matches!(param, Param::Capture(_));
+    #[test]
+    fn test_global_match_limit() {
+        let captures = vec![capture(r"(?<a>\d+)-(?<b>\d+)")];
+        let operators = vec![operator("<a>:inc"), operator("<b>:inc")];
+        let options = Options {
+            max_matches: Some(3),
+            ..Options::default()
+        };
+
+        let result = process_with(&options, &captures, &operators, "1-2".to_string()).unwrap();
+        assert_eq!(result, Some("2-3".to_string()));
+
+        let result = process_with(&options, &captures, &operators, "1-2 3-4".to_string());
+        assert!(matches!(
+            result,
+            Err(Error::MatchLimit { count: 4, max: 3 })
+        ));
     }
 
     #[test]
-    fn test_negative_numbers() {
-        let captures = vec![capture(r"value = (?<value>-?\d+)")];
-        let operators = vec![operator("<value>:inc:5")];
-        let content = "value = -10".to_string();
+    fn test_plan() {
+        let captures = vec![capture(r"(?<a>\d+)-(?<b>\d+)")];
+        let operators = vec![operator("<a>:swap:<b>"), operator("<b>[last]:inc")];
+        let content = "1-2 3-4";
+        let edit_plan = plan(&captures, &operators, content).unwrap();
+        let edits = edit_plan
+            .edits
+            .iter()
+            .map(|e| (e.start, e.target.as_str(), e.old.as_str(), e.new.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            edits,
+            vec![
+                (0, "a", "1", "2"),
+                (2, "b", "2", "1"),
+                (4, "a", "3", "4"),
+                (6, "b", "4", "4"),
+            ]
+        );
+        assert_eq!(
+            process(false, &captures, &operators, content.to_string()).unwrap(),
+            Some("2-1 4-4".to_string())
+        );
 
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("value = -5".to_string()));
+        let options = Options {
+            skip_if: vec![Regex::new(r"\d-2").unwrap()],
+            ..Options::default()
+        };
+        let edit_plan = plan_with(&options, &captures, &operators, content).unwrap();
+        assert!(edit_plan.edits.is_empty());
+
+        let captures = vec![capture(r"(?<a>\d+)"), capture(r"(?<b>\d+-)")];
+        let operators = vec![operator("<a>:inc"), operator("<b>:del")];
+        assert!(matches!(
+            plan(&captures, &operators, content),
+            Err(Error::Overlap)
+        ));
     }
 
     #[test]
-    fn test_zero_operations() {
-        let captures = vec![capture(r"value = (?<value>\d+)")];
-        let operators = vec![operator("<value>:inc:0")];
-        let content = "value = 5".to_string();
+    fn test_plan_apply() {
+        let captures = vec![capture(r"(?<a>\d+)-(?<b>\d+)")];
+        let operators = vec![operator("<a>:swap:<b>")];
+        let content = "1-2 3-4".to_string();
+        let mut edit_plan = plan(&captures, &operators, &content).unwrap();
+
+        let mut applied = content.clone();
+        edit_plan.apply(&mut applied).unwrap();
+        assert_eq!(applied, "2-1 4-3");
+
+        let mut applied = content.clone();
+        edit_plan
+            .apply_subset(&mut applied, |edit| edit.start < 3)
+            .unwrap();
+        assert_eq!(applied, "2-1 3-4");
+
+        let mut applied = "5-2 3-4".to_string();
+        assert!(matches!(
+            edit_plan.apply(&mut applied),
+            Err(Error::Stale { start: 0, end: 1 })
+        ));
+        assert_eq!(applied, "5-2 3-4");
+
+        edit_plan.edits.push(Edit {
+            start: 0,
+            end: 3,
+            target: "0".to_string(),
+            old: "1-2".to_string(),
+            new: String::new(),
+            operators: SmallVec::new(),
+        });
+        let mut applied = content;
+        assert!(matches!(edit_plan.apply(&mut applied), Err(Error::Overlap)));
+        edit_plan
+            .apply_subset(&mut applied, |edit| edit.target == "0")
+            .unwrap();
+        assert_eq!(applied, " 3-4");
+    }
 
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("value = 5".to_string()));
+    #[test]
+    fn test_plan_undo() {
+        let captures = vec![capture(r"(?<word>[a-z]+)(?<num>\d+)")];
+        let operators = vec![operator("<word>:rep:é"), operator("<num>:mul:100")];
+        let content = "ab1 c22 def3\n";
+        let edit_plan = plan(&captures, &operators, content).unwrap();
+
+        let mut applied = content.to_string();
+        let undo = edit_plan.apply(&mut applied).unwrap();
+        assert_eq!(applied, "é100 é2200 é300\n");
+        assert_eq!(undo.edits.len(), 6);
+        let redo = undo.apply(&mut applied).unwrap();
+        assert_eq!(applied, content);
+        redo.apply(&mut applied).unwrap();
+        assert_eq!(applied, "é100 é2200 é300\n");
+
+        let mut applied = content.to_string();
+        let undo = edit_plan
+            .apply_subset(&mut applied, |edit| edit.target == "num")
+            .unwrap();
+        assert_eq!(applied, "ab100 c2200 def300\n");
+        undo.apply(&mut applied).unwrap();
+        assert_eq!(applied, content);
     }
 
     #[test]
-    fn test_large_numbers() {
-        let captures = vec![capture(r"value = (?<value>\d+)")];
-        let operators = vec![operator("<value>:inc:1000000")];
-        let content = "value = 999999".to_string();
+    fn test_on_conflict() {
+        let captures = vec![capture(r"(?<all>\d(?<b>\d))")];
+        let operators = vec![operator("<b>:inc"), operator("<all>:rep:X")];
+        for (on_conflict, expected, edits) in [
+            (OnConflict::SkipLater, "X X", vec![0, 2]),
+            (OnConflict::FirstWins, "13 35", vec![2, 0]),
+            (OnConflict::LastWins, "X X", vec![0, 2]),
+        ] {
+            let options = Options {
+                on_conflict,
+                ..Options::default()
+            };
+            let (result, stats) =
+                process_with_stats(&options, &captures, &operators, "12 34".to_string()).unwrap();
+            assert_eq!(result.as_deref(), Some(expected));
+            assert_eq!(stats.edits, edits);
+        }
 
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("value = 1999999".to_string()));
+        let result = process_with(&Options::default(), &captures, &operators, "12".to_string());
+        assert!(matches!(result, Err(Error::Overlap)));
     }
 
     #[test]
-    fn test_empty_string_replacement() {
-        let captures = vec![capture(r"text = (?<text>\w*)")];
-        let operators = vec![operator("<text>:del")];
-        let content = "text = hello".to_string();
-
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("text = ".to_string()));
+    fn test_strict() {
+        let captures = vec![capture(r"a(?<a>\d)|b(?<b>\d)")];
+        let operators = vec![operator("<a>:inc"), operator("<*>:mul:2")];
+        let options = Options {
+            strict: true,
+            ..Options::default()
+        };
+        let result = process_with(&options, &captures, &operators, "a1".to_string());
+        assert_eq!(result.unwrap(), Some("a4".to_string()));
+
+        let result = process_with(&options, &captures, &operators, "b1".to_string());
+        assert!(matches!(result, Err(Error::NoEdits(target)) if target == "a"));
+
+        let result = process(false, &captures, &operators, "b1".to_string());
+        assert_eq!(result.unwrap(), Some("b2".to_string()));
+
+        let operators = vec![operator("<a>:inc"), operator("<c*>:inc")];
+        let result = process_with(&options, &captures, &operators, "a1".to_string());
+        assert!(matches!(result, Err(Error::NoEdits(target)) if target == "c*"));
     }
 
     #[test]
-    fn test_special_characters_in_replacement() {
-        let captures = vec![capture(r"text = (?<text>\w+)")];
-        let operators = vec![operator(r#"<text>:rep:hello@world.com"#)];
-        let content = "text = old".to_string();
+    fn test_processor() {
+        let captures = vec![capture(r"(?<v_major>\d+)\.(?<v_minor>\d+)")];
+        let operators = vec![operator("<v_*>:inc")];
+        let processor = Processor::new(Options::default(), captures.clone(), operators.clone());
+        assert_eq!(processor.ops().len(), 1);
+
+        for content in ["1.2", "9.9 10.0", "none", "\u{feff}1.2\r\n"] {
+            assert_eq!(
+                processor.process(content).unwrap(),
+                process(false, &captures, &operators, content.to_string()).unwrap()
+            );
+        }
 
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("text = hello@world.com".to_string()));
+        let (result, stats) = processor.process_with_stats("1.2 3.4").unwrap();
+        assert_eq!(result, Some("2.3 4.5".to_string()));
+        assert_eq!(stats.edits, vec![4]);
+
+        let result = processor.process_filtered("1.2", |_, edit| edit.target == "v_minor");
+        assert_eq!(result.unwrap(), Some("1.3".to_string()));
+        assert_eq!(processor.plan("1.2").unwrap().edits.len(), 2);
+
+        let found = processor.captures("1.2 3.4").unwrap().collect::<Vec<_>>();
+        assert_eq!(found.len(), 4);
+        assert_eq!(
+            found,
+            find_matches(&Options::default(), &captures, "1.2 3.4").unwrap()
+        );
     }
 
     #[test]
-    fn test_unicode_support() {
-        let captures = vec![capture(r"name = (?<name>\w+)")];
-        let operators = vec![operator("<name>:rep:josé")];
-        let content = "name = john".to_string();
+    fn test_process_str() {
+        let processor = Processor::new(
+            Options::default(),
+            vec![capture(r"(?<n>\d+)")],
+            vec![operator("<n>:inc:0")],
+        );
+        assert!(matches!(
+            processor.process_str("\u{feff}1 2").unwrap(),
+            Some(Cow::Borrowed("\u{feff}1 2"))
+        ));
+
+        let processor = Processor::new(
+            Options {
+                lines: true,
+                ..Options::default()
+            },
+            vec![capture(r"(?<n>\d+)")],
+            vec![operator("<n>:inc")],
+        );
+        for (content, expected) in [
+            ("1\n2", Some("2\n3")),
+            ("\u{feff}1\nx\n10", Some("\u{feff}2\nx\n11")),
+            ("1\r\n2\r\n", Some("2\r\n3\r\n")),
+            ("x", None),
+        ] {
+            let result = processor.process_str(content).unwrap();
+            assert!(!matches!(result, Some(Cow::Borrowed(_))));
+            assert_eq!(result.as_deref(), expected);
+        }
+    }
 
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("name = josé".to_string()));
+    #[test]
+    fn test_report() {
+        let processor = Processor::new(
+            Options::default(),
+            vec![capture(r"(?<v_major>\d+)\.(?<v_minor>\d+) (?<name>\w+)")],
+            vec![
+                operator("<name>:upper"),
+                operator("<v_*>:inc"),
+                operator("<name>:rep:x"),
+            ],
+        );
+        let position = |line, column| Position { line, column };
+        let content = "\u{feff}é\r\n1.2 ab\r\n";
+        let report = processor.report(content).unwrap();
+        assert_eq!(
+            report.content.as_deref(),
+            processor.process(content).unwrap().as_deref()
+        );
+
+        let changes = report
+            .changes
+            .iter()
+            .map(|c| {
+                let span = &content[c.start..c.end];
+                (
+                    c.target.as_str(),
+                    c.operators.clone(),
+                    span,
+                    c.new.as_str(),
+                    c.from,
+                    c.to,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                ("v_major", vec![1], "1", "2", position(2, 1), position(2, 2)),
+                ("v_minor", vec![1], "2", "3", position(2, 3), position(2, 4)),
+                (
+                    "name",
+                    vec![0, 2],
+                    "ab",
+                    "x",
+                    position(2, 5),
+                    position(2, 7)
+                ),
+            ]
+        );
+        assert_eq!(processor.report("none").unwrap(), Report::default());
     }
 
     #[test]
-    fn test_mixed_operations_order() {
-        let captures = vec![capture(r"(?<a>\d+) (?<b>\d+) (?<c>\d+)")];
+    fn test_process_with_vars() {
+        let vars = HashMap::from([
+            ("a".to_string(), "2".to_string()),
+            ("b".to_string(), "x".to_string()),
+        ]);
+        let captures = vec![capture(r"(?<v>\d+) (?<s>\w+)")];
         let operators = vec![
-            operator("<c>:inc:1"),
-            operator("<a>:dec:1"),
-            operator("<b>:rep:99"),
+            operator("<v>:inc:{a}|mul:{a}"),
+            operator(r"<s>:append:-{b}{b}-\{c}"),
         ];
-        let content = "5 10 15".to_string();
-
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("4 99 16".to_string()));
+        let result = process_with_vars(
+            &vars,
+            &Options::default(),
+            &captures,
+            &operators,
+            "1 s".to_string(),
+        );
+        assert_eq!(result.unwrap(), Some("6 s-xx-{c}".to_string()));
+
+        let processor = Regop::builder()
+            .capture(r"(?<v>\d+)")
+            .op("<v>:rep:{v}")
+            .var("v", "9")
+            .build()
+            .unwrap();
+        assert_eq!(processor.process("1").unwrap(), Some("9".to_string()));
     }
 
     #[test]
-    fn test_capture_group_not_found() {
-        let captures = vec![capture(r"version = (?<version>\d+)")];
-        let operators = vec![operator("<nonexistent>:inc")];
-        let content = "version = 5".to_string();
+    fn test_validate() {
+        let captures = vec![capture(r"(?<v_major>\d+)\.(\d+)")];
+        let valid = [
+            "<v_major>:inc",
+            "<2>:mul:<v_major>",
+            "<v_*>:dec:2",
+            "<0>:upper:tr",
+        ];
+        let operators = valid.map(operator);
+        assert_eq!(validate(&captures, &operators), Ok(()));
+
+        let operators = [
+            "<x_*>:inc",
+            "<2>:swap:2",
+            "<2>:rep:<x>",
+            "<2>:div:0",
+            "<2>:inc:x",
+            "<2>:inc|rep:1",
+        ]
+        .map(operator);
+        let mut swap = operator("<2>:swap:<v_major>");
+        swap.then = operators[5].then.clone();
+        let operators = operators.into_iter().chain([swap]).collect::<Vec<_>>();
+        let issues = validate(&captures, &operators).unwrap_err();
+        assert_eq!(issues.len(), 6);
+        assert!(
+            matches!(&issues[0], ValidationIssue::UnknownTarget { operator: 0, target } if target == "x_*")
+        );
+        assert!(
+            matches!(&issues[1], ValidationIssue::SwapValue { operator: 1, value, .. } if value == "2")
+        );
+        assert!(
+            matches!(&issues[2], ValidationIssue::UnknownCapture { operator: 2, name, .. } if name == "x")
+        );
+        assert!(matches!(
+            &issues[3],
+            ValidationIssue::DivisionByZero { operator: 3, .. }
+        ));
+        assert!(
+            matches!(&issues[4], ValidationIssue::NotAnInt { operator: 4, value, .. } if value == "x")
+        );
+        assert!(matches!(
+            &issues[5],
+            ValidationIssue::SwapInPipeline { operator: 6, .. }
+        ));
+    }
 
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, None);
+    #[test]
+    fn test_builder() {
+        let regop = Regop::builder()
+            .capture(r"(?<num>\d+)")
+            .op("<num>:inc")
+            .select(Selector::Last)
+            .lines(true)
+            .build()
+            .unwrap();
+        assert_eq!(regop.regex().len(), 1);
+        assert_eq!(regop.ops().len(), 1);
+        let result = regop.process("1 2\n3 4").unwrap();
+        assert_eq!(result, Some("1 2\n3 5".to_string()));
+        assert_eq!(regop.plan("1 2").unwrap().edits.len(), 1);
+
+        let (_, stats) = regop.process_with_stats("1 2").unwrap();
+        assert_eq!(stats.edits, vec![1]);
+
+        let regop = Regop::builder()
+            .capture(r"v(?<num>\d+)")
+            .op("<num>:inc")
+            .ignore_case(true)
+            .build()
+            .unwrap();
+        assert_eq!(regop.process("V1").unwrap(), Some("V2".to_string()));
+
+        assert!(matches!(
+            Regop::builder().capture("(").build(),
+            Err(Error::Regex { .. })
+        ));
+        assert!(matches!(
+            Regop::builder().op("<num>:nope").build(),
+            Err(Error::Parse(_))
+        ));
     }
 
     #[test]
-    fn test_multiple_regex_patterns() {
-        let captures = vec![
-            capture(r"version = (?<version>\d+)"),
-            capture(r"count = (?<count>\d+)"),
+    fn test_process_bytes() {
+        let captures = vec![capture(r"(?<v>\d\S*)")];
+        let processor = Processor::new(Options::default(), captures, vec![operator("<v>:inc")]);
+        let cases: [(&[u8], Option<&[u8]>); 5] = [
+            (b"1 \xff 2\xfe 3 ", Some(b"2 \xff 2\xfe 4 ")),
+            (
+                b"\xef\xbb\xbf1\r\n\xc3\r\n2\r\n",
+                Some(b"\xef\xbb\xbf2\r\n\xc3\r\n3\r\n"),
+            ),
+            (b"1\xe2\x82", None),
+            (b"\xff", None),
+            (b"1 2", Some(b"2 3")),
         ];
-        let operators = vec![operator("<version>:inc"), operator("<count>:dec")];
-        let content = "version = 1\ncount = 10".to_string();
+        for (content, expected) in cases {
+            let result = processor.process_bytes(content).unwrap();
+            assert_eq!(result.as_deref(), expected);
+        }
 
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("version = 2\ncount = 9".to_string()));
+        let processor = Processor::new(
+            Options::default(),
+            vec![capture(r"(?<line>.+)")],
+            vec![operator("<line>:upper")],
+        );
+        let result = processor.process_bytes(b"x\r\ny\xff\r\nz\r\n").unwrap();
+        assert_eq!(result.as_deref(), Some(&b"X\r\ny\xff\r\nZ\r\n"[..]));
     }
 
     #[test]
-    fn test_overlapping_matches_error() {
-        let captures = vec![capture(r"(?<all>\w+(?<part>\w+))")];
-        let operators = vec![operator("<all>:rep:new"), operator("<part>:rep:part")];
-        let content = "hello".to_string();
+    fn test_process_files() {
+        let dir = std::env::temp_dir().join(format!("regop-files-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = ["a.txt", "b.txt", "c.txt", "d.txt", "missing.txt"].map(|f| dir.join(f));
+        std::fs::write(&files[0], "v1").unwrap();
+        std::fs::write(&files[1], "none").unwrap();
+        std::fs::write(&files[2], b"v1 \xff").unwrap();
+        std::fs::write(&files[3], "v1 and more").unwrap();
+
+        let processor = Processor::new(
+            Options::default(),
+            vec![capture(r"v(?<v>\d+)")],
+            vec![operator("<v>:inc")],
+        );
+        let options = FileOptions {
+            write: true,
+            max_filesize: Some(5),
+            ..FileOptions::default()
+        };
+        let results = processor.process_files(&files, &options);
+        let written = std::fs::read_to_string(&files[0]).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+        let outcome = |i: usize| results[i].outcome.as_ref();
+
+        assert_eq!(results.len(), 5);
+        assert_eq!(written, "v2");
+        assert_eq!(results[0].path, files[0]);
+        assert_eq!(outcome(0).unwrap(), &FileOutcome::Changed("v2".to_string()));
+        assert_eq!(results[0].stats.edits, vec![1]);
+        assert_eq!(outcome(1).unwrap(), &FileOutcome::Unchanged);
+        assert!(matches!(outcome(2), Err(Error::NotUtf8(_))));
+        assert_eq!(outcome(3).unwrap(), &FileOutcome::Skipped);
+        assert!(matches!(outcome(4), Err(Error::Read { .. })));
+    }
 
-        let result = regop(&captures, &operators, content);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("overlap"));
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_process_files_async() {
+        let dir = std::env::temp_dir().join(format!("regop-async-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = ["a.txt", "b.txt", "missing.txt"].map(|f| dir.join(f));
+        std::fs::write(&files[0], "v1").unwrap();
+        std::fs::write(&files[1], "v1 and more").unwrap();
+
+        let processor = Processor::new(
+            Options::default(),
+            vec![capture(r"v(?<v>\d+)")],
+            vec![operator("<v>:inc")],
+        );
+        let options = FileOptions {
+            write: true,
+            max_filesize: Some(5),
+            ..FileOptions::default()
+        };
+        let results = processor.process_files_async(&files, &options).await;
+        let written = std::fs::read_to_string(&files[0]).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(written, "v2");
+        assert_eq!(
+            results[0].outcome.as_ref().unwrap(),
+            &FileOutcome::Changed("v2".to_string())
+        );
+        assert_eq!(results[1].outcome.as_ref().unwrap(), &FileOutcome::Skipped);
+        assert!(matches!(results[2].outcome, Err(Error::Read { .. })));
     }
 
     #[test]
-    fn test_string_increment_with_capture() {
-        let captures = vec![capture(r"(?<a>\d+) plus (?<b>\d+)")];
-        let operators = vec![operator("<a>:inc:<b>")];
-        let content = "5 plus 3".to_string();
-
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("8 plus 3".to_string()));
+    fn test_process_stream() {
+        let captures = vec![capture(r"(?<v>\d+)")];
+        let operators = vec![operator("<v>:inc")];
+        let block = Block {
+            start: Regex::new(r"^\[deps\]\s*$").unwrap(),
+            end: Regex::new(r"^\[\w").unwrap(),
+        };
+        let content = "1\n# regop-ignore-start\n[deps]\n1\n# regop-ignore-end\n1\n\
+                       [deps]\n# regop-ignore-next-line\n[x]\n1\n[deps]\n1 2\r\n1";
+        for options in [
+            Options {
+                lines: true,
+                ..Options::default()
+            },
+            Options {
+                block: Some(block.clone()),
+                ..Options::default()
+            },
+            Options {
+                lines: true,
+                block: Some(block.clone()),
+                ..Options::default()
+            },
+            Options {
+                chunk: Some(Chunk::Lines(2)),
+                ..Options::default()
+            },
+            Options {
+                chunk: Some(Chunk::Paragraphs),
+                block: Some(block),
+                ..Options::default()
+            },
+            Options {
+                chunk: Some(Chunk::Lines(3)),
+                within: vec![Regex::new(r"\d \d").unwrap()],
+                ..Options::default()
+            },
+            Options {
+                lines: true,
+                select: Selector::Last,
+                ..Options::default()
+            },
+        ] {
+            let processor = Processor::new(options, captures.clone(), operators.clone());
+            let expected = processor.process(content).unwrap();
+            let mut output = Vec::new();
+            let changed = processor
+                .process_stream(content.as_bytes(), &mut output)
+                .unwrap();
+            assert_eq!(changed, expected.is_some());
+            assert_eq!(
+                String::from_utf8(output).unwrap(),
+                expected.unwrap_or_else(|| content.to_string())
+            );
+        }
     }
 
     #[test]
-    fn test_dec_with_string_capture() {
-        let captures = vec![capture(r"(?<a>\d+) minus (?<b>\d+)")];
-        let operators = vec![operator("<a>:dec:<b>")];
-        let content = "10 minus 3".to_string();
+    fn test_unmatched_shortcut() {
+        let build = |op: &str, strict: bool| {
+            Regop::builder()
+                .capture(r"^v(?<v>\d+)")
+                .op(op)
+                .strict(strict)
+                .build()
+                .unwrap()
+        };
+        let processor = build("<v>:inc", false);
+        assert!(processor.unmatched("a v1"));
+        assert!(!processor.unmatched("\u{feff}v1"));
+        assert!(!processor.unmatched("v\r\n"));
+        let (new, stats) = processor.process_with_stats("a v1").unwrap();
+        assert_eq!(new, None);
+        assert_eq!(
+            stats,
+            Stats {
+                matches: 0,
+                edits: vec![0]
+            }
+        );
+        assert_eq!(
+            processor.process("\u{feff}v1").unwrap(),
+            Some("\u{feff}v2".to_string())
+        );
 
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("7 minus 3".to_string()));
+        // Content without matches still fails where processing it would
+        let processor = build("<v>:inc", true);
+        assert!(matches!(processor.process("a v1"), Err(Error::NoEdits(_))));
+        let processor = build("<v>:rep:<w>", false);
+        assert!(matches!(
+            processor.process("a v1"),
+            Err(Error::MissingCapture(_))
+        ));
     }
 
     #[test]
-    fn test_whitespace_handling() {
-        let captures = vec![capture(r"value\s*=\s*(?<value>\d+)")];
-        let operators = vec![operator("<value>:inc")];
-        let content = "value   =   5".to_string();
-
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("value   =   6".to_string()));
+    fn test_regex_limits() {
+        let options = RegexOptions {
+            size_limit: Some(10_000),
+            dfa_size_limit: Some(1 << 16),
+            ..RegexOptions::default()
+        };
+        assert!(Capture::with_options(r"(?<v>\d+)", &options).is_ok());
+        let result = Capture::with_options(r"(?<v>\w{100})", &options);
+        assert!(matches!(
+            result,
+            Err(Error::RegexLimit { limit: 10_000, .. })
+        ));
+        assert!(Capture::from_str(r"(?<v>\w{100})").is_ok());
     }
 
     #[test]
-    fn test_case_sensitive_regex() {
-        let captures = vec![capture(r"Version = (?<version>\d+)")];
-        let operators = vec![operator("<version>:inc")];
-        let content = "version = 5".to_string();
-
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, None);
+    fn test_regex_cache() {
+        let ignore_case = RegexOptions {
+            ignore_case: true,
+            ..RegexOptions::default()
+        };
+        for _ in 0..2 {
+            let capture = Capture::from_str("(?<v>cached)").unwrap();
+            assert!(!capture.regex.is_match("CACHED").unwrap());
+            let capture = Capture::with_options("(?<v>cached)", &ignore_case).unwrap();
+            assert!(capture.regex.is_match("CACHED").unwrap());
+        }
+        assert!(Capture::from_str("(?<v>cached").is_err());
     }
 
     #[test]
-    fn test_replace_with_space() {
-        let captures = vec![capture(r"text = (?<text>\w+)")];
-        let operators = vec![operator("<text>:rep: ")];
-        let content = "text = hello".to_string();
-
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("text =  ".to_string()));
+    fn test_parallel_units() {
+        let captures = vec![capture(r"^v(?<v>\d+)$")];
+        let operators = vec![operator("<v>:inc")];
+        let options = Options {
+            lines: true,
+            select: Selector::Last,
+            ..Options::default()
+        };
+        let content = (0..200_000)
+            .map(|i| format!("v{i}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert!(content.len() >= PARALLEL_THRESHOLD);
+
+        let result = process_with(&options, &captures, &operators, content.clone()).unwrap();
+        let expected = content.replace("v199999", "v200000");
+        assert_eq!(result, Some(expected));
     }
 
     #[test]
-    fn test_mul_operation() {
-        let captures = vec![capture(r"value = (?<value>\d+)")];
-        let operators = vec![operator("<value>:mul:3")];
-        let content = "value = 5".to_string();
+    fn test_chunk() {
+        let captures = vec![capture(r"a (?<v>\d+)\nb")];
+        let operators = vec![operator("<v>:inc")];
+        let content = "a 1\nb\na 2\nb\n\na 3\n\nb\n";
+        for (chunk, expected) in [
+            (Chunk::Lines(2), "a 2\nb\na 3\nb\n\na 3\n\nb\n"),
+            (Chunk::Lines(3), "a 2\nb\na 2\nb\n\na 3\n\nb\n"),
+            (Chunk::Paragraphs, "a 2\nb\na 3\nb\n\na 3\n\nb\n"),
+        ] {
+            let options = Options {
+                chunk: Some(chunk),
+                ..Options::default()
+            };
+            let result = process_with(&options, &captures, &operators, content.to_string());
+            assert_eq!(result.unwrap().as_deref(), Some(expected), "{chunk:?}");
+        }
 
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("value = 15".to_string()));
+        assert_eq!("3".parse::<Chunk>().unwrap(), Chunk::Lines(3));
+        assert_eq!("paragraph".parse::<Chunk>().unwrap(), Chunk::Paragraphs);
+        assert!("0".parse::<Chunk>().is_err());
+        assert!("lines".parse::<Chunk>().is_err());
+        assert_eq!(
+            Chunk::Paragraphs.split("\n\na\n\n\nb"),
+            [(0, "\n\n"), (2, "a\n\n\n"), (6, "b")]
+        );
     }
 
     #[test]
-    fn test_mul_operation_with_capture() {
-        let captures = vec![capture(r"(?<a>\d+) times (?<b>\d+)")];
-        let operators = vec![operator("<a>:mul:<b>")];
-        let content = "4 times 6".to_string();
+    fn test_error_kinds() {
+        let error = "(?<num>".parse::<Capture>().unwrap_err();
+        assert!(matches!(&error, Error::Regex { pattern, .. } if pattern == "(?<num>"));
+        assert!(std::error::Error::source(&error).is_some());
 
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("24 times 6".to_string()));
+        assert!(matches!(
+            "<num>:nope".parse::<Operator>(),
+            Err(Error::Parse(_))
+        ));
+        assert!(matches!(parse_int("x"), Err(Error::Int { value, .. }) if value == "x"));
+
+        let captures = vec![capture(r"(?<num>\d+)")];
+        let operators = vec![operator("<num>:rep:<missing>")];
+        let result = regop(&captures, &operators, "1".to_string());
+        assert!(matches!(result, Err(Error::MissingCapture(name)) if name == "missing"));
     }
 
     #[test]
-    fn test_div_operation() {
-        let captures = vec![capture(r"value = (?<value>\d+)")];
-        let operators = vec![operator("<value>:div:2")];
-        let content = "value = 10".to_string();
-
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("value = 5".to_string()));
+    fn test_invalid_match_limit() {
+        assert!("<num>:inc@max=x".parse::<Operator>().is_err());
     }
 
     #[test]
-    fn test_div_operation_with_capture() {
-        let captures = vec![capture(r"(?<a>\d+) divided by (?<b>\d+)")];
-        let operators = vec![operator("<a>:div:<b>")];
-        let content = "20 divided by 4".to_string();
+    fn test_wildcard_target() {
+        let captures = vec![capture(r"(?<a>\w+)=(?<b>\w+)")];
+        let operators = vec![operator("<*>:upper")];
+        let content = "key=value".to_string();
 
         let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("5 divided by 4".to_string()));
+        assert_eq!(result, Some("KEY=VALUE".to_string()));
     }
 
     #[test]
-    fn test_div_by_zero_error() {
-        let captures = vec![capture(r"value = (?<value>\d+)")];
-        let operators = vec![operator("<value>:div:0")];
-        let content = "value = 10".to_string();
+    fn test_wildcard_target_pattern() {
+        let captures = vec![capture(
+            r"(?<ver_major>\d+)\.(?<ver_minor>\d+)\.(?<patch>\d+)",
+        )];
+        let operators = vec![operator("<ver_*>:inc")];
+        let content = "1.2.3".to_string();
 
-        let result = regop(&captures, &operators, content);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("division by zero"));
+        let result = regop(&captures, &operators, content).unwrap();
+        assert_eq!(result, Some("2.3.3".to_string()));
     }
 
     #[test]
-    fn test_append_operation() {
-        let captures = vec![capture(r"name = (?<name>\w+)")];
-        let operators = vec![operator("<name>:append:_suffix")];
-        let content = "name = test".to_string();
+    fn test_wildcard_target_no_names() {
+        let captures = vec![capture(r"(?<num>\d+)")];
+        let operators = vec![operator("<ver_*>:inc")];
+        let content = "1".to_string();
 
         let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("name = test_suffix".to_string()));
+        assert_eq!(result, None);
     }
 
     #[test]
-    fn test_append_operation_with_number() {
-        let captures = vec![capture(r"version = (?<version>\d+)")];
-        let operators = vec![operator("<version>:append:42")];
-        let content = "version = 1".to_string();
-
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("version = 142".to_string()));
+    fn test_glob_match() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXXbYYc"));
+        assert!(glob_match("*_minor", "ver_minor"));
+        assert!(!glob_match("a*b", "aXXc"));
+        assert!(!glob_match("?", "ab"));
     }
 
     #[test]
-    fn test_prepend_operation() {
-        let captures = vec![capture(r"name = (?<name>\w+)")];
-        let operators = vec![operator("<name>:prepend:prefix_")];
-        let content = "name = test".to_string();
+    fn test_ordinal_operation() {
+        let captures = vec![capture(r"(?<place>\d+) place")];
+        let operators = vec![operator("<place>:ordinal")];
+        let content =
+            "1 place, 2 place, 3 place, 4 place, 11 place, 22 place, 113 place".to_string();
 
         let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("name = prefix_test".to_string()));
+        assert_eq!(
+            result,
+            Some(
+                "1st place, 2nd place, 3rd place, 4th place, 11th place, 22nd place, 113th place"
+                    .to_string()
+            )
+        );
     }
 
+    #[cfg(feature = "bigint")]
     #[test]
-    fn test_prepend_operation_with_number() {
-        let captures = vec![capture(r"version = (?<version>\d+)")];
-        let operators = vec![operator("<version>:prepend:v")];
-        let content = "version = 123".to_string();
+    fn test_bigint() {
+        let big = "123456789012345678901234567890";
+        let captures = vec![capture(r"(?<a>-?\d+) (?<b>-?\d+) (?<c>-?\d+) (?<d>-?\d+)")];
+        let operators = vec![
+            operator("<a>:inc"),
+            operator(&format!("<b>:mul:{big}")),
+            operator("<c>:div:10?if-gt:0"),
+            operator("<d>:ordinal"),
+        ];
+        let content = format!("{big} 10 {big} -{big}1");
 
+        let (inc, div) = (
+            "123456789012345678901234567891",
+            "12345678901234567890123456789",
+        );
         let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("version = v123".to_string()));
+        assert_eq!(result, Some(format!("{inc} {big}0 {div} -{big}1st")));
+        assert!(validate(&captures, &[operator(&format!("<a>:dec:{big}"))]).is_ok());
     }
 
+    #[cfg(feature = "decimal")]
     #[test]
-    fn test_upper_operation() {
-        let captures = vec![capture(r"text = (?<text>\w+)")];
-        let operators = vec![operator("<text>:upper")];
-        let content = "text = hello".to_string();
+    fn test_decimal() {
+        let captures = vec![capture(
+            r"(?<a>\S+) (?<b>\S+) (?<c>\S+) (?<d>\S+) (?<e>\S+)",
+        )];
+        let operators = vec![
+            operator("<a>:mul:1.5"),
+            operator("<b>:div:3"),
+            operator("<c>:inc:0.1"),
+            operator("<d>:dec:1"),
+            operator("<e>:mul:3"),
+        ];
+        let content = "19.99 10.00 0.2 1.50 7".to_string();
 
         let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("text = HELLO".to_string()));
+        assert_eq!(result, Some("29.99 3.33 0.3 0.50 21".to_string()));
+        assert!(validate(&captures, &[operator("<a>:mul:0.5")]).is_ok());
+        assert!(matches!(
+            regop(
+                &captures,
+                &[operator("<a>:div:0.0")],
+                "1.5 x x x x".to_string()
+            ),
+            Err(Error::DivisionByZero)
+        ));
     }
 
+    #[cfg(feature = "tracing")]
     #[test]
-    fn test_upper_operation_mixed_case() {
-        let captures = vec![capture(r"name = (?<name>[A-Za-z]+)")];
-        let operators = vec![operator("<name>:upper")];
-        let content = "name = JohnDoe".to_string();
+    fn test_tracing() {
+        use std::sync::{Arc, Mutex};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
 
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("name = JOHNDOE".to_string()));
-    }
+        /// Records the names of spans as they are created.
+        struct Spans(Arc<Mutex<Vec<&'static str>>>);
 
-    #[test]
-    fn test_lower_operation() {
-        let captures = vec![capture(r"text = (?<text>\w+)")];
-        let operators = vec![operator("<text>:lower")];
-        let content = "text = HELLO".to_string();
+        impl Subscriber for Spans {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let mut spans = self.0.lock().unwrap();
+                spans.push(span.metadata().name());
+                Id::from_u64(spans.len() as u64)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, _: &Event<'_>) {}
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
 
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("text = hello".to_string()));
+        let spans = Arc::new(Mutex::new(Vec::new()));
+        let processor = Processor::new(
+            Options::default(),
+            vec![capture(r"(?<v>\d+)")],
+            vec![operator("<v>:inc"), operator("<v>:mul:2")],
+        );
+        tracing::subscriber::with_default(Spans(spans.clone()), || {
+            assert_eq!(processor.process("1").unwrap(), Some("4".to_string()));
+        });
+        assert_eq!(
+            *spans.lock().unwrap(),
+            [
+                "plan_content",
+                "find_captures",
+                "collect_edits",
+                "operator",
+                "operator",
+                "splice"
+            ]
+        );
     }
 
     #[test]
-    fn test_lower_operation_mixed_case() {
-        let captures = vec![capture(r"name = (?<name>[A-Za-z]+)")];
-        let operators = vec![operator("<name>:lower")];
-        let content = "name = JohnDoe".to_string();
+    fn test_prefilter() {
+        let processor = Regop::builder()
+            .capture(r"^v(?<v>\d+)$")
+            .op("<v>:inc")
+            .ignore_case(true)
+            .lines(true)
+            .build()
+            .unwrap();
+        assert!(processor.prefilter.is_some());
+        assert_eq!(
+            processor.process("a\nV1\nb").unwrap(),
+            Some("a\nV2\nb".to_string())
+        );
+        assert_eq!(processor.process("a\nb").unwrap(), None);
 
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("name = johndoe".to_string()));
+        // Skipped content is checked like content that doesn't match
+        let strict = Processor::new(
+            Options {
+                strict: true,
+                ..Options::default()
+            },
+            vec![capture(r"(?<v>\d+)")],
+            vec![operator("<v>:inc")],
+        );
+        assert!(matches!(strict.process("x"), Err(Error::NoEdits(_))));
+        let missing = Processor::new(
+            Options::default(),
+            vec![capture(r"(?<v>\d+)")],
+            vec![operator("<v>:inc:<w>")],
+        );
+        assert!(matches!(
+            missing.process("x"),
+            Err(Error::MissingCapture(_))
+        ));
     }
 
     #[test]
-    fn test_multiple_new_operations() {
-        let captures = vec![capture(r"(?<text>\w+) = (?<value>\d+)")];
-        let operators = vec![operator("<text>:upper"), operator("<value>:mul:2")];
-        let content = "count = 5".to_string();
-
-        let result = regop(&captures, &operators, content).unwrap();
-        assert_eq!(result, Some("COUNT = 10".to_string()));
+    fn test_processor_across_threads() {
+        let processor = std::sync::Arc::new(Processor::new(
+            Options::default(),
+            vec![capture(r"(?<v>\d+)")],
+            vec![operator("<v>:inc")],
+        ));
+        let mut handles = Vec::new();
+        for i in 0..4 {
+            let processor = std::sync::Arc::clone(&processor);
+            handles.push(std::thread::spawn(move || {
+                processor.process(&i.to_string())
+            }));
+        }
+        for (i, handle) in handles.into_iter().enumerate() {
+            let result = handle.join().unwrap().unwrap();
+            assert_eq!(result, Some((i + 1).to_string()));
+        }
     }
 
     #[test]
-    fn test_missing_parameter_for_mul() {
-        let result = "<test>:mul".parse::<Operator>();
-        assert!(result.is_err());
-        assert!(
-            result
-                .unwrap_err()
-                .to_string()
-                .contains("parameter required in 'mul' operator")
-        );
+    fn test_operation_info() {
+        for info in Operation::all() {
+            assert_eq!(info.operation.info(), Some(info));
+            for example in info.examples {
+                assert_eq!(operator(example).op, info.operation, "{example}");
+            }
+            if let Some(default) = info.default {
+                let op = operator(&format!("<a>:{}", info.name));
+                assert_eq!(
+                    format!("{:?}", op.value),
+                    format!("{:?}", Param::from(default))
+                );
+            }
+        }
+        assert_eq!(Operation::all().len(), 13);
     }
 
     #[test]
-    fn test_missing_parameter_for_div() {
-        let result = "<test>:div".parse::<Operator>();
-        assert!(result.is_err());
-        assert!(
-            result
-                .unwrap_err()
-                .to_string()
-                .contains("parameter required in 'div' operator")
-        );
+    fn test_param_and_fixed_pattern_edge_cases() {
+        for (value, name) in [("a<>b<c>", Some("c")), ("<<a>", Some("<a")), ("<", None)] {
+            let param = Param::from(value);
+            match (param, name) {
+                (Param::Capture(c), Some(name)) => assert_eq!(c, name),
+                (Param::String(s), None) => assert_eq!(s, value),
+                (param, _) => panic!("unexpected {param:?} for '{value}'"),
+            }
+        }
+        assert_eq!(fixed_pattern("(?<1>*)(?<"), r"\(\?<1>\*\)\(\?<");
+        assert_eq!(fixed_pattern("(?<a (?<v>*)"), r"\(\?<a (?<v>.+)");
     }
 
+    #[cfg(not(feature = "bigint"))]
     #[test]
-    fn test_missing_parameter_for_append() {
-        let result = "<test>:append".parse::<Operator>();
-        assert!(result.is_err());
-        assert!(
-            result
-                .unwrap_err()
-                .to_string()
-                .contains("parameter required in 'append' operator")
-        );
+    fn test_overflow() {
+        let captures = vec![capture(r"(?<v>-?\d+)")];
+        for (op, content) in [
+            ("<v>:inc", isize::MAX.to_string()),
+            ("<v>:dec", isize::MIN.to_string()),
+            ("<v>:div:-1", isize::MIN.to_string()),
+        ] {
+            assert!(matches!(
+                regop(&captures, &[operator(op)], content),
+                Err(Error::Overflow(_))
+            ));
+        }
     }
 
     #[test]
-    fn test_missing_parameter_for_prepend() {
-        let result = "<test>:prepend".parse::<Operator>();
+    fn test_ordinal_operation_not_a_number() {
+        let captures = vec![capture(r"place = (?<place>\w+)")];
+        let operators = vec![operator("<place>:ordinal")];
+        let content = "place = first".to_string();
+
+        let result = regop(&captures, &operators, content);
         assert!(result.is_err());
-        assert!(
-            result
-                .unwrap_err()
-                .to_string()
-                .contains("parameter required in 'prepend' operator")
-        );
     }
 
+    #[cfg(not(feature = "bigint"))]
     #[test]
     fn test_mul_overflow_protection() {
         let captures = vec![capture(r"value = (?<value>\d+)")];
         let operators = vec![operator("<value>:mul:1000000000000")];
         let content = "value = 1000000000000".to_string();
 
-        // Should not panic due to wrapping_mul
-        let result = regop(&captures, &operators, content).unwrap();
-        assert!(result.is_some());
+        let result = regop(&captures, &operators, content);
+        assert!(matches!(result, Err(Error::Overflow(_))));
     }
 }
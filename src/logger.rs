@@ -0,0 +1,38 @@
+//! Logging to stderr.
+//!
+//! Warnings are logged by default, `-q` only logs errors while `-v` and
+//! `-vv` also log which files were processed and which regular expressions
+//! and operators matched and edited what.
+
+use console::style;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Logger writing records to stderr.
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            Level::Error => eprintln!("{} {}", style("Error:").red(), record.args()),
+            Level::Warn => eprintln!("{} {}", style("Warning:").yellow(), record.args()),
+            Level::Info => eprintln!("{}", record.args()),
+            Level::Debug | Level::Trace => eprintln!("{}", style(record.args()).dim()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Install the logger, logging records up to a level.
+pub fn init(level: LevelFilter) -> anyhow::Result<()> {
+    log::set_logger(&Logger)?;
+    log::set_max_level(level);
+    Ok(())
+}
@@ -3,15 +3,41 @@
 //! This binary provides a powerful text transformation tool that uses
 //! regular expressions with named capture groups and operators.
 
-use std::fs;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::fs::{self, File};
 use std::io::{IsTerminal, Read};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Instant, SystemTime};
 
-use anyhow::{Context, ensure};
-use clap::Parser;
+use anyhow::{Context, anyhow, bail, ensure};
+use clap::parser::ValueSource;
+use clap::{CommandFactory, Parser, Subcommand};
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use regex::Regex;
 
+mod cache;
+mod config;
 mod diff;
+#[cfg(feature = "highlight")]
+mod highlight;
+mod logger;
+mod report;
+mod walk;
 
-use regop::{Capture, Operator, process};
+use regop::{
+    Arity, Block, Capture, Chunk, Condition, Engine, Expectation, Fragment, OnConflict, Operation,
+    Operator, Options, Position, Processor, RegexOptions, Selector, Stats, SwapPairing, TypeCheck,
+    ValueMatch, find_full_matches, glob_match,
+};
+
+/// Number of files above which a progress bar is shown.
+const PROGRESS_THRESHOLD: usize = 100;
+
+/// Number of files processed per thread before their results are output,
+/// which bounds how many files are held in memory at once.
+const FILES_PER_THREAD: usize = 16;
 
 /// Easy file manipulation with regex and operators.
 ///
@@ -49,80 +75,1519 @@ use regop::{Capture, Operator, process};
     -w \
     -r "version = \"(?<major>\d)\.(?<minor>\d)" \
     -o "<major>:rep:21" \
-    -"#)
+    -
+
+  # Run the bump-deps task from regop.toml
+  regop run bump-deps"#),
+    args_conflicts_with_subcommands = true,
+    args_override_self = true
 )]
+#[allow(clippy::struct_excessive_bools)]
 struct Regop {
     /// Write to files, will write to stdout if input file is `-`
     #[arg(short, long)]
     #[clap(default_value_t = false)]
     write: bool,
 
+    /// How to show changes, `preview` or `unified` for a patch that `patch -p1` and `git apply`
+    /// accept
+    #[arg(long, default_value = "preview", value_parser = clap::value_parser!(diff::Format))]
+    diff_format: diff::Format,
+
+    /// Highlight the content of diffs by file type, requires regop to be built with the
+    /// `highlight` feature
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    highlight: bool,
+
+    /// Style a part of diffs, `delete`, `insert`, `context`, `emphasis` or `frame`, with dot
+    /// separated colors and attributes, e.g. `delete=blue.bold` or `emphasis=reverse`
+    #[arg(long, value_name = "PART=STYLE", value_parser = clap::value_parser!(diff::PartStyle))]
+    diff_style: Vec<diff::PartStyle>,
+
+    /// Width of diffs in columns, defaults to the width of the terminal
+    #[arg(long, value_parser = parse_width)]
+    width: Option<usize>,
+
+    /// When to color output, `auto`, `always` or `never`, auto colors output to a terminal unless
+    /// `NO_COLOR` is set
+    #[arg(long, default_value = "auto", value_parser = clap::value_parser!(diff::Color))]
+    color: diff::Color,
+
+    /// Don't show diffs or warnings, only errors
+    #[arg(short, long, conflicts_with = "verbose")]
+    #[clap(default_value_t = false)]
+    quiet: bool,
+
+    /// Log the files processed, repeat to also log where regular expressions matched and what
+    /// operators edited
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Never show a progress bar, which is otherwise shown when processing many files in a
+    /// terminal
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    no_progress: bool,
+
+    /// Print a summary of the files, matches and edits after processing all files
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    stats: bool,
+
+    /// List every match of each named capture group with the file and line it is on, instead of
+    /// applying operators
+    #[arg(long, conflicts_with_all = ["write", "interactive", "stdout", "check", "patch_file", "output_dir", "output"])]
+    #[clap(default_value_t = false)]
+    list: bool,
+
+    /// Print a line for every match of the regular expressions from a template instead of applying
+    /// operators, e.g. `{file}:{line}: {version}`. Supports capture group names, `{0}` for the
+    /// whole match, `{file}` and `{line}`
+    #[arg(long, conflicts_with_all = ["write", "interactive", "stdout", "check", "patch_file", "output_dir", "output", "list"])]
+    print: Option<String>,
+
+    /// Only print the names of files that would change, one per line
+    #[arg(long, conflicts_with_all = ["write", "interactive", "stdout", "patch_file", "output_dir", "output", "list", "print"])]
+    #[clap(default_value_t = false)]
+    files_with_matches: bool,
+
+    /// Write a report of the files that would change, e.g. for CI, as `junit=<path>`,
+    /// `tap=<path>` or `sarif=<path>`
+    #[arg(long, value_parser = clap::value_parser!(report::Report))]
+    report: Option<report::Report>,
+
+    /// Remember files left unchanged in this file, and skip them on later runs with the same
+    /// regexes, operators and options as long as they don't change, e.g. in pre-commit hooks
+    #[arg(long)]
+    cache: Option<PathBuf>,
+
+    /// Don't change any files, but fail if any of them would change, e.g. in CI
+    #[arg(long, conflicts_with_all = ["write", "interactive", "stdout", "output_dir", "output"])]
+    #[clap(default_value_t = false)]
+    check: bool,
+
+    /// Print the content of all files to stdout after editing them, each with a
+    /// `==> file <==` header
+    #[arg(long, conflicts_with_all = ["write", "output_dir", "output"])]
+    #[clap(default_value_t = false)]
+    stdout: bool,
+
+    /// Write all files to this directory, mirroring their paths, instead of editing them. Files
+    /// skipped by `--cache` or `--max-filesize` are copied as they are
+    #[arg(long)]
+    output_dir: Option<PathBuf>,
+
+    /// Write files to a path derived from a template instead of editing them, e.g.
+    /// `{stem}.new{ext}`, relative to the directory of each file. Supports `{name}`, `{stem}`
+    /// and `{ext}`
+    #[arg(short = 'O', long)]
+    output: Option<String>,
+
+    /// Write the changes to all files as a unified diff to this file instead of editing them,
+    /// to apply later with `patch -p1` or `git apply`
+    #[arg(long, conflicts_with_all = ["write", "interactive", "stdout", "output_dir", "output"])]
+    patch_file: Option<PathBuf>,
+
+    /// Keep a copy of files before writing them, named with this suffix or `.bak`
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = ".bak")]
+    backup: Option<String>,
+
+    /// Show each change and ask whether to apply it, answer `y` to apply it, `n` to skip it,
+    /// `a` to apply the rest of the file or `q` to quit. Files are only written with `-w`,
+    /// otherwise the accepted changes are shown as a diff
+    #[arg(long, conflicts_with = "stdout")]
+    #[clap(default_value_t = false)]
+    interactive: bool,
+
+    /// Write files even if they were modified by something else while regop was processing them
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    force: bool,
+
+    /// Keep the modification time of files when writing them
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    preserve_mtime: bool,
+
     /// Operate on lines induvidually, one by one
     #[arg(short, long)]
     #[clap(default_value_t = false)]
     lines: bool,
 
+    /// Only operate inside blocks starting after a line matching this regex, e.g. `^\[dependencies\]`
+    #[arg(long, requires = "block_end")]
+    block_start: Option<Regex>,
+
+    /// End blocks before a line matching this regex, e.g. `^\[`
+    #[arg(long, requires = "block_start")]
+    block_end: Option<Regex>,
+
+    /// Operate on chunks of lines individually, a number of lines or `paragraphs` separated by
+    /// blank lines. Stdin written with `-w` is then rewritten a chunk at a time
+    #[arg(long, value_parser = clap::value_parser!(Chunk))]
+    chunk: Option<Chunk>,
+
+    /// Only match inside regions matched by this regex, can be repeated to narrow down further
+    #[arg(long, value_parser = clap::value_parser!(Regex))]
+    within: Vec<Regex>,
+
+    /// Never edit inside regions matched by this regex, e.g. `(?m)^#.*`, can be repeated
+    #[arg(long, value_parser = clap::value_parser!(Regex))]
+    skip_regex: Vec<Regex>,
+
+    /// Only edit the first match of each operator per file
+    #[arg(long, conflicts_with = "last")]
+    #[clap(default_value_t = false)]
+    first: bool,
+
+    /// Only edit the last match of each operator per file
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    last: bool,
+
+    /// Only edit matches where a capture equals a value, e.g. `<env>=staging`, or matches a
+    /// regex, e.g. `<version>~^0\.`, can be repeated
+    #[arg(long, value_parser = clap::value_parser!(Condition))]
+    when: Vec<Condition>,
+
+    /// Skip files where this regex matches anywhere, can be repeated
+    #[arg(long, value_parser = clap::value_parser!(Regex))]
+    skip_if_regex: Vec<Regex>,
+
+    /// Fail if operators would edit more than this many matches in a single file
+    #[arg(long)]
+    max_matches: Option<usize>,
+
+    /// Fail if an operator edits no matches in a file
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    strict: bool,
+
+    /// How swap operators pair up matches: `error`, `index`, `nearest` or `truncate`
+    #[arg(long, default_value = "error", value_parser = clap::value_parser!(SwapPairing))]
+    swap_pairing: SwapPairing,
+
+    /// Which match of a capture used as a parameter provides its value: `nearest` to prefer the
+    /// same regex match as the target and else the closest one, `same-match`, `first` or
+    /// `index:N`
+    #[arg(long, default_value = "nearest", value_parser = clap::value_parser!(ValueMatch))]
+    value_match: ValueMatch,
+
+    /// What to do when edits of different operators overlap: `error`, `skip-later` to keep the
+    /// edit that starts first, `first-wins` or `last-wins` to keep the edit of the operator given
+    /// first or last
+    #[arg(long, default_value = "error", value_parser = clap::value_parser!(OnConflict))]
+    on_conflict: OnConflict,
+
+    /// Fail unless a capture matches a number of times in each file, e.g. `<version>=1` or
+    /// `<dep>=1..5`, can be repeated
+    #[arg(long, value_parser = clap::value_parser!(Expectation))]
+    expect: Vec<Expectation>,
+
+    /// Fail unless all values of a capture have a type, e.g. `<port>:int` or
+    /// `<port>:int:1..65535`, can be repeated
+    #[arg(long = "type", value_parser = clap::value_parser!(TypeCheck))]
+    types: Vec<TypeCheck>,
+
+    /// Match regular expressions case insensitively
+    #[arg(short, long)]
+    #[clap(default_value_t = false)]
+    ignore_case: bool,
+
+    /// Make `^` and `$` in regular expressions match at the start and end of lines
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    multiline: bool,
+
+    /// Make `.` in regular expressions match line endings
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    dotall: bool,
+
+    /// Ignore whitespace and allow `#` comments in regular expressions
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    extended: bool,
+
+    /// Regex engine, `standard` or `fancy` for lookaround and backreferences, `fancy` requires
+    /// regop to be built with the `fancy` feature
+    #[arg(long, default_value = "standard", value_parser = clap::value_parser!(Engine))]
+    engine: Engine,
+
+    /// Fail if a regular expression compiles to more than this size, e.g. `1M`, supports `K`, `M`
+    /// and `G` suffixes
+    #[arg(long, value_parser = parse_limit)]
+    regex_size_limit: Option<usize>,
+
+    /// Limit the memory used by the DFA of each regular expression while matching to this size,
+    /// e.g. `1M`, matching falls back to slower engines beyond it
+    #[arg(long, value_parser = parse_limit)]
+    dfa_size_limit: Option<usize>,
+
+    /// Treat regular expressions as literal text, where only `(?<name>*)` placeholders capture
+    #[arg(short = 'F', long)]
+    #[clap(default_value_t = false)]
+    fixed_strings: bool,
+
+    /// Only match whole words, matches may neither be preceded nor followed by a word character
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    word_regexp: bool,
+
+    /// Define a regex fragment, e.g. `num=\d+`, that is interpolated into regular expressions
+    /// as `{num}`, can be repeated
+    #[arg(long, value_parser = clap::value_parser!(Fragment))]
+    define: Vec<Fragment>,
+
     /// Regular expression, can be repeated
-    #[arg(short, long, value_parser = clap::value_parser!(Capture))]
-    regex: Vec<Capture>,
+    #[arg(short, long)]
+    regex: Vec<String>,
 
     /// Operator, can be repeated
-    #[arg(short, long, value_parser = clap::value_parser!(Operator))]
+    #[arg(short, long, value_parser = clap::value_parser!(Operator), long_help = operators_help())]
     op: Vec<Operator>,
 
-    /// File to operate on, use `-` for stdin, can be repeated
+    /// Read operators from a file, one per line, blank lines and lines starting with `#` are
+    /// ignored, can be repeated
+    #[arg(long)]
+    ops_file: Vec<String>,
+
+    /// Define a variable, e.g. `version=2.0.0`, that operator parameters can reference as
+    /// `{version}`, can be repeated
+    #[arg(long, value_name = "NAME=VALUE", value_parser = parse_var)]
+    var: Vec<(String, String)>,
+
+    /// Read the list of files to operate on from a file, one per line, can be repeated
+    #[arg(long)]
+    files_from: Vec<String>,
+
+    /// Always read the list of files to operate on from stdin
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    stdin_files: bool,
+
+    /// Operate on the content piped to stdin, same as passing `-` as the file
+    #[arg(long, conflicts_with_all = ["file", "files_from", "stdin_files"])]
+    #[clap(default_value_t = false)]
+    stdin_content: bool,
+
+    /// Read the list of files from stdin or `--files-from` separated by NUL bytes, e.g. from
+    /// `find -print0`, and print file names with `--files-with-matches` separated by NUL bytes
+    #[arg(short = '0', long)]
+    #[clap(default_value_t = false)]
+    null: bool,
+
+    /// Only operate on files matching this glob, where `*` also matches `/`, can be repeated
+    #[arg(long)]
+    include: Vec<String>,
+
+    /// Skip files matching this glob, e.g. `vendor/*`, can be repeated
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    /// Don't skip files ignored by `.gitignore` and similar files when walking directories
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    no_ignore: bool,
+
+    /// Skip files larger than this size, e.g. `10M`, supports `K`, `M` and `G` suffixes
+    #[arg(long, value_parser = parse_size)]
+    max_filesize: Option<u64>,
+
+    /// Number of threads to process files and parts of large files with, defaults to the number
+    /// of CPUs
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    /// Use the flags of a profile in the user config, `~/.config/regop/config.toml`
+    #[arg(short, long)]
+    profile: Option<String>,
+
+    /// File or directory to operate on, use `-` for stdin, can be repeated
     #[arg()]
     file: Vec<String>,
+
+    /// Files matching the globs of a task or profile, used if no files are given
+    #[arg(skip)]
+    globbed: Vec<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run a task from `regop.toml`, flags and files given after the task override it
+    Run {
+        /// Name of the task
+        task: String,
+
+        /// Flags and files overriding the task
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+}
+
+impl Regop {
+    /// Compile the regular expressions with the regex flags.
+    fn captures(&self) -> anyhow::Result<Vec<Capture>> {
+        let options = RegexOptions {
+            ignore_case: self.ignore_case,
+            multi_line: self.multiline,
+            dot_all: self.dotall,
+            extended: self.extended,
+            engine: self.engine,
+            fixed_strings: self.fixed_strings,
+            fragments: self.define.clone(),
+            word_regexp: self.word_regexp,
+            size_limit: self.regex_size_limit,
+            dfa_size_limit: self.dfa_size_limit,
+        };
+        self.regex
+            .iter()
+            .map(|r| Capture::with_options(r, &options).map_err(Into::into))
+            .collect()
+    }
+
+    /// The processor for the captures, operators and options, failing before
+    /// any file is read if the operators can't be used with the captures.
+    fn processor(&self) -> anyhow::Result<Processor> {
+        let (captures, ops) = (self.captures()?, self.operators()?);
+        if let Err(issues) = regop::validate(&captures, &ops) {
+            for issue in &issues {
+                log::error!("{issue}");
+            }
+            bail!("{} invalid operator(s)", issues.len());
+        }
+        Ok(Processor::new(self.options(), captures, ops))
+    }
+
+    /// Operators from the command line followed by those in operator files.
+    fn operators(&self) -> anyhow::Result<Vec<Operator>> {
+        let mut ops = self.op.clone();
+        for file in &self.ops_file {
+            let content =
+                fs::read_to_string(file).context(format!("unable to read ops file '{file}'"))?;
+            for (i, line) in content.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                ops.push(
+                    line.parse()
+                        .context(format!("invalid operator on line {} of '{file}'", i + 1))?,
+                );
+            }
+        }
+        let vars: HashMap<_, _> = self.var.iter().cloned().collect();
+        Ok(ops.iter().map(|op| op.with_vars(&vars)).collect())
+    }
+
+    /// The files to operate on, from the arguments, file lists and stdin,
+    /// with directories expanded and duplicates removed.
+    ///
+    /// Also returns the files that were found by walking directories or
+    /// matching globs rather than named, which are skipped if they aren't
+    /// text, see [`read_file`].
+    fn files(&self) -> anyhow::Result<(Vec<String>, HashSet<String>)> {
+        let mut files = self.file.clone();
+        for manifest in &self.files_from {
+            let content = fs::read_to_string(manifest)
+                .context(format!("unable to read file list '{manifest}'"))?;
+            files.extend(file_list(&content, self.null));
+        }
+        if self.stdin_content {
+            files.push("-".to_string());
+        } else if self.stdin_files
+            || (files.is_empty() && self.globbed.is_empty() && self.files_from.is_empty())
+        {
+            ensure!(
+                self.stdin_files || !std::io::stdin().is_terminal(),
+                "supply filename or pipe a list of files to stdin"
+            );
+            let mut stdin = String::new();
+            std::io::stdin().read_to_string(&mut stdin)?;
+            let listed = file_list(&stdin, self.null);
+            if !self.stdin_files
+                && let Some(missing) = listed.iter().find(|file| !Path::new(file).exists())
+            {
+                bail!(
+                    "'{missing}' piped to stdin is not a file, pass `-` or `--stdin-content` to \
+                     operate on the content of stdin, or `--stdin-files` if it is a list of files"
+                );
+            }
+            files.extend(listed);
+        }
+        let mut expanded = Vec::new();
+        let mut walked = HashSet::new();
+        for file in &files {
+            let found = self.expand(file)?;
+            if Path::new(file).is_dir() {
+                walked.extend(found.iter().cloned());
+            }
+            expanded.extend(found);
+        }
+        for file in self.globbed.iter().filter(|file| self.included(file)) {
+            walked.insert(file.clone());
+            expanded.push(file.clone());
+        }
+        walked.retain(|file| !files.contains(file));
+        Ok((dedup_files(expanded), walked))
+    }
+
+    /// Expand a file argument into the files to operate on, walking it if it
+    /// is a directory, filtered by the include and exclude globs.
+    fn expand(&self, file: &str) -> anyhow::Result<Vec<String>> {
+        let files = if Path::new(file).is_dir() {
+            walk::walk(file, self.no_ignore)?
+        } else {
+            vec![file.to_string()]
+        };
+        Ok(files.into_iter().filter(|f| self.included(f)).collect())
+    }
+
+    /// Check if a file passes the include and exclude globs. Stdin is always
+    /// included.
+    fn included(&self, file: &str) -> bool {
+        let path = file.strip_prefix("./").unwrap_or(file);
+        file == "-"
+            || ((self.include.is_empty() || self.include.iter().any(|g| glob_match(g, path)))
+                && !self.exclude.iter().any(|g| glob_match(g, path)))
+    }
+
+    /// Where to write a file instead of editing it, if an output directory or
+    /// template is given. Templates are relative to the directory of the file.
+    fn output_path(&self, file: &str) -> anyhow::Result<Option<PathBuf>> {
+        if self.output_dir.is_none() && self.output.is_none() {
+            return Ok(None);
+        }
+        ensure!(file != "-", "stdin can't be written to an output path");
+        let mut path = PathBuf::from(file);
+        if let Some(template) = &self.output {
+            path = path.with_file_name(render_output(template, &path));
+        }
+        if let Some(dir) = &self.output_dir {
+            path = mirror_path(dir, &path)?;
+        }
+        Ok(Some(path))
+    }
+
+    /// Whether to rewrite stdin to stdout a chunk at a time rather than
+    /// reading all of it, when nothing else needs to see the whole content.
+    fn streams(&self, files: &[String]) -> bool {
+        self.chunk.is_some()
+            && self.write
+            && files == ["-"]
+            && !self.interactive
+            && !self.check
+            && !self.stats
+            && !self.files_with_matches
+            && self.report.is_none()
+            && self.patch_file.is_none()
+            && self.max_filesize.is_none()
+    }
+
+    /// Width of diffs, the terminal width unless overridden.
+    fn diff_width(&self) -> usize {
+        self.width.unwrap_or_else(diff::terminal_width)
+    }
+
+    /// A progress bar for processing files, hidden unless there are many
+    /// files and the output is a terminal.
+    fn progress(&self, files: usize) -> anyhow::Result<ProgressBar> {
+        if self.no_progress
+            || self.quiet
+            || files <= PROGRESS_THRESHOLD
+            || !std::io::stdout().is_terminal()
+        {
+            return Ok(ProgressBar::hidden());
+        }
+        let progress = ProgressBar::new(files as u64);
+        progress.set_style(ProgressStyle::with_template(
+            "{bar:40} {pos}/{len} files ({eta} left)",
+        )?);
+        Ok(progress)
+    }
+
+    /// Level of the messages to log.
+    const fn log_level(&self) -> log::LevelFilter {
+        match (self.quiet, self.verbose) {
+            (true, _) => log::LevelFilter::Error,
+            (false, 0) => log::LevelFilter::Warn,
+            (false, 1) => log::LevelFilter::Info,
+            (false, 2) => log::LevelFilter::Debug,
+            (false, _) => log::LevelFilter::Trace,
+        }
+    }
+
+    /// Processing options derived from the command line flags.
+    fn options(&self) -> Options {
+        Options {
+            lines: self.lines,
+            select: if self.first {
+                Selector::Index(1)
+            } else if self.last {
+                Selector::Last
+            } else {
+                Selector::All
+            },
+            when: self.when.clone(),
+            skip_if: self.skip_if_regex.clone(),
+            max_matches: self.max_matches,
+            swap_pairing: self.swap_pairing,
+            value_match: self.value_match,
+            on_conflict: self.on_conflict,
+            expect: self.expect.clone(),
+            types: self.types.clone(),
+            block: self
+                .block_start
+                .clone()
+                .zip(self.block_end.clone())
+                .map(|(start, end)| Block { start, end }),
+            chunk: self.chunk,
+            within: self.within.clone(),
+            skip: self.skip_regex.clone(),
+            strict: self.strict,
+        }
+    }
+}
+
+/// Parse the command line, resolving tasks and profiles into the flags they
+/// define. Flags on the command line override tasks, which override profiles.
+fn parse() -> anyhow::Result<Regop> {
+    let mut args = std::env::args().collect::<Vec<_>>();
+    let mut regop = Regop::parse_from(&args);
+    let mut globs = if let Some(Command::Run { task, args: rest }) = regop.command {
+        let config = config::Config::load(Path::new(config::PROJECT_CONFIG))?;
+        let rest = args.into_iter().take(1).chain(rest).collect();
+        let task_globs;
+        (args, task_globs) = with_flags(rest, config.task(&task)?)?;
+        regop = Regop::try_parse_from(&args).context(format!("invalid task '{task}'"))?;
+        task_globs
+    } else {
+        Vec::new()
+    };
+
+    if let Some(profile) = &regop.profile {
+        let config = config::Config::load(&config::user_config()?)?;
+        let profile_globs;
+        (args, profile_globs) = with_flags(args, config.profile(profile)?)?;
+        regop = Regop::try_parse_from(&args).context(format!("invalid profile '{profile}'"))?;
+        if globs.is_empty() {
+            globs = profile_globs;
+        }
+    }
+
+    if regop.file.is_empty() && !globs.is_empty() {
+        regop.globbed = config::expand_globs(&globs, regop.no_ignore)?;
+        ensure!(!regop.globbed.is_empty(), "no files match {globs:?}");
+    }
+    Ok(regop)
+}
+
+/// Put the flags of a task or profile before the command line arguments,
+/// returning them along with the file globs of the task or profile. Flags
+/// given in the arguments replace the ones in the table.
+fn with_flags(
+    args: Vec<String>,
+    table: &toml::Table,
+) -> anyhow::Result<(Vec<String>, Vec<String>)> {
+    let matches = Regop::command().try_get_matches_from(&args)?;
+    let given = Regop::command()
+        .get_arguments()
+        .filter(|arg| matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine))
+        .filter_map(|arg| arg.get_long().map(ToString::to_string))
+        .collect();
+    let (flags, globs) = config::args(table, &given)?;
+    let mut args = args.into_iter();
+    let args = args.next().into_iter().chain(flags).chain(args).collect();
+    Ok((args, globs))
 }
 
 /// Main entry point for the regop CLI.
 fn main() -> anyhow::Result<()> {
-    let regop = Regop::parse();
+    let started = Instant::now();
+    let regop = parse()?;
+    regop.color.apply();
+    diff::set_styles(&regop.diff_style);
+    if regop.highlight {
+        diff::enable_highlighting()?;
+    }
+    logger::init(regop.log_level())?;
+    let processor = regop.processor()?;
 
-    if regop.file.is_empty() {
-        ensure!(
-            !std::io::stdin().is_terminal(),
-            "supply filename or pipe a list of files to stdin"
+    let (expanded, walked) = regop.files()?;
+    if regop.list || regop.print.is_some() {
+        return print_matches(&regop, &processor, &expanded, &walked);
+    }
+    if regop.streams(&expanded) {
+        processor.process_stream(std::io::stdin().lock(), std::io::stdout().lock())?;
+        return Ok(());
+    }
+
+    let mut cache = regop
+        .cache
+        .as_deref()
+        .map(|path| cache::Cache::load(path, &processor));
+    let progress = regop.progress(expanded.len())?;
+    let changes = process_files(
+        &regop,
+        &processor,
+        &expanded,
+        &walked,
+        cache.as_mut(),
+        &progress,
+    )?;
+
+    let mut failed = 0;
+    let mut output = Output::default();
+    let mut summary = Summary::default();
+    let mut outcomes = Vec::new();
+    for (file, change) in expanded.iter().zip(changes) {
+        if output.quit {
+            break;
+        }
+        let mut outcome = match &change {
+            Ok(Some(change)) => {
+                summary.add(file, change);
+                match &change.new {
+                    Some(new) if regop.report.is_some() => report::Outcome::Changed {
+                        old: change.old.clone(),
+                        new: new.clone(),
+                    },
+                    _ => report::Outcome::Unchanged,
+                }
+            }
+            Ok(None) | Err(_) => report::Outcome::Skipped,
+        };
+        let result = change.and_then(|change| {
+            change.map_or_else(
+                || copy_skipped(&regop, file),
+                |change| {
+                    progress
+                        .suspend(|| handle_change(&regop, &processor, file, change, &mut output))
+                },
+            )
+        });
+        if let Err(e) = result {
+            log::error!("{e:#}");
+            failed += 1;
+            outcome = report::Outcome::Failed(format!("{e:#}"));
+        }
+        outcomes.push((file.clone(), outcome));
+    }
+    progress.finish_and_clear();
+    output.print_diffstat();
+    if regop.stats {
+        summary.print(processor.ops(), started);
+    }
+    if let Some(path) = &regop.patch_file {
+        fs::write(path, &output.patch)
+            .context(format!("unable to write patch '{}'", path.display()))?;
+    }
+    if let Some(report) = &regop.report {
+        report.write(&outcomes)?;
+    }
+    if let (Some(cache), Some(path)) = (&cache, &regop.cache) {
+        cache.save(path)?;
+    }
+    ensure!(
+        failed == 0,
+        "failed to process {failed} of {} files",
+        expanded.len()
+    );
+    ensure!(
+        !regop.check || summary.changed == 0,
+        "{} of {} files would change",
+        summary.changed,
+        expanded.len()
+    );
+
+    Ok(())
+}
+
+/// Process files in parallel a batch at a time, showing progress.
+///
+/// Files are processed as the returned iterator advances, so only a batch of
+/// files is held in memory at once. The result for each file is recorded in
+/// the cache. With expectations, all files are processed before the first
+/// is returned, see [`check_expectations`].
+fn process_files<'a>(
+    regop: &'a Regop,
+    processor: &'a Processor,
+    files: &'a [String],
+    walked: &'a HashSet<String>,
+    mut cache: Option<&'a mut cache::Cache>,
+    progress: &'a ProgressBar,
+) -> anyhow::Result<Box<dyn Iterator<Item = anyhow::Result<Option<FileChange>>> + 'a>> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(regop.jobs.unwrap_or(0))
+        .build()?;
+    let batch = pool.current_num_threads() * FILES_PER_THREAD;
+    let changes = files.chunks(batch).flat_map(move |batch| {
+        let known = cache.as_deref();
+        let changes = pool.install(|| {
+            batch
+                .par_iter()
+                .map(|file| {
+                    let walked = walked.contains(file);
+                    let change = process_file(processor, regop.max_filesize, file, walked, known);
+                    progress.inc(1);
+                    change
+                })
+                .collect::<Vec<_>>()
+        });
+        if let Some(cache) = cache.as_deref_mut() {
+            for (file, change) in batch.iter().zip(&changes) {
+                if let Ok(Some(change)) = change {
+                    cache.record(file, change.stamp, &change.old, change.new.is_none());
+                }
+            }
+        }
+        changes
+    });
+    if regop.expect.is_empty() {
+        Ok(Box::new(changes))
+    } else {
+        Ok(Box::new(check_expectations(files, changes)?.into_iter()))
+    }
+}
+
+/// Process all files before handing out their changes, failing if an
+/// expectation isn't met in any of them.
+///
+/// Expectations guard the whole run, so nothing may be written before they
+/// are checked against every file.
+fn check_expectations(
+    files: &[String],
+    changes: impl Iterator<Item = anyhow::Result<Option<FileChange>>>,
+) -> anyhow::Result<Vec<anyhow::Result<Option<FileChange>>>> {
+    let changes = changes.collect::<Vec<_>>();
+    let mut unmet = 0;
+    for (file, change) in files.iter().zip(&changes) {
+        if let Err(e) = change
+            && let Some(regop::Error::Expectation { .. }) = e.downcast_ref()
+        {
+            log::error!("'{file}': {e:#}");
+            unmet += 1;
+        }
+    }
+    ensure!(
+        unmet == 0,
+        "expectations not met in {unmet} of {} files, nothing was written",
+        files.len()
+    );
+    Ok(changes)
+}
+
+/// State of the output over all processed files.
+#[derive(Default)]
+struct Output {
+    /// Number of files printed with `--stdout`
+    printed: usize,
+    /// Unified diffs for `--patch-file`
+    patch: String,
+    /// Whether the user asked to stop in interactive mode
+    quit: bool,
+    /// Number of files shown as preview diffs
+    diffs: usize,
+    /// Lines inserted and deleted in the preview diffs
+    diffstat: diff::DiffStat,
+}
+
+impl Output {
+    /// Print the total lines inserted and deleted, if any diffs were shown.
+    fn print_diffstat(&self) {
+        if self.diffs > 0 {
+            println!(
+                "{} file{} changed, {}",
+                self.diffs,
+                if self.diffs == 1 { "" } else { "s" },
+                self.diffstat
+            );
+        }
+    }
+}
+
+/// Output a processed file according to the output mode.
+fn handle_change(
+    regop: &Regop,
+    processor: &Processor,
+    file: &str,
+    change: FileChange,
+    output: &mut Output,
+) -> anyhow::Result<()> {
+    match change {
+        change if regop.interactive && change.new.is_some() => {
+            let new = confirm_edits(regop, processor, file, &change.old, &mut output.quit)?;
+            output_file(regop, file, &FileChange { new, ..change }, output)
+        }
+        change if regop.files_with_matches => {
+            if change.new.is_some() {
+                print!("{file}{}", if regop.null { '\0' } else { '\n' });
+            }
+            Ok(())
+        }
+        change if regop.patch_file.is_some() => {
+            if let Some(new) = &change.new {
+                output
+                    .patch
+                    .push_str(&diff::unified(file, &change.old, new));
+            }
+            Ok(())
+        }
+        change if regop.stdout => {
+            print_file(
+                file,
+                change.new.as_deref().unwrap_or(&change.old),
+                output.printed == 0,
+            );
+            output.printed += 1;
+            Ok(())
+        }
+        change => output_file(regop, file, &change, output),
+    }
+}
+
+/// Totals over all processed files.
+#[derive(Default)]
+struct Summary {
+    /// Number of files processed
+    scanned: usize,
+    /// Number of files that changed or would change
+    changed: usize,
+    /// Matches and edits in all files
+    stats: Stats,
+}
+
+impl Summary {
+    /// Add a processed file to the totals.
+    fn add(&mut self, file: &str, change: &FileChange) {
+        let changed = change.new.is_some();
+        log::info!(
+            "Processed '{file}', {} matches, {}",
+            change.stats.matches,
+            if changed { "changed" } else { "unchanged" }
         );
-        for file in std::io::stdin().lines() {
-            handle_file(&regop, &file?)?;
+        self.scanned += 1;
+        self.changed += usize::from(changed);
+        self.stats.add(&change.stats);
+    }
+
+    /// Print the totals to stderr.
+    fn print(&self, ops: &[Operator], started: Instant) {
+        eprintln!("Files scanned: {}", self.scanned);
+        eprintln!("Files changed: {}", self.changed);
+        eprintln!("Matches: {}", self.stats.matches);
+        for (i, (op, edits)) in ops.iter().zip(&self.stats.edits).enumerate() {
+            eprintln!("Edits by operator {} on '<{}>': {edits}", i + 1, op.target);
         }
+        eprintln!("Elapsed: {:.2?}", started.elapsed());
+    }
+}
+
+/// Split a list of files separated by newlines or NUL bytes.
+fn file_list(content: &str, null: bool) -> Vec<String> {
+    let files = if null {
+        content.split('\0').collect::<Vec<_>>()
+    } else {
+        content.lines().collect()
+    };
+    files
+        .into_iter()
+        .filter(|file| !file.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Remove files that are given more than once, through different paths or
+/// symlinks, keeping the first path each file was given with. Files that
+/// can't be resolved are compared by their path.
+fn dedup_files(files: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    files
+        .into_iter()
+        .filter(|file| seen.insert(fs::canonicalize(file).unwrap_or_else(|_| PathBuf::from(file))))
+        .collect()
+}
+
+/// The content of a processed file.
+struct FileChange {
+    /// Content as it was read
+    old: String,
+    /// New content, if the file would change
+    new: Option<String>,
+    /// Modification time and length of the file when it was read
+    stamp: Option<Stamp>,
+    /// Matches and edits in the file
+    stats: Stats,
+}
+
+/// Modification time and length of a file.
+type Stamp = (SystemTime, u64);
+
+/// Modification time and length of a file, to detect changes to it.
+fn stamp(metadata: &fs::Metadata) -> Option<Stamp> {
+    metadata
+        .modified()
+        .ok()
+        .map(|mtime| (mtime, metadata.len()))
+}
+
+/// Read a file, or stdin if the file is "-", along with the stamp of the
+/// file. Files larger than `max_filesize` are skipped without being read into
+/// memory.
+///
+/// Files that aren't valid UTF-8 are an error if named, but skipped with a
+/// warning if `walked`, as are files containing NUL bytes, so binary files in
+/// a directory don't fail the run.
+fn read_file(
+    file: &str,
+    walked: bool,
+    max_filesize: Option<u64>,
+) -> anyhow::Result<Option<(String, Option<Stamp>)>> {
+    let (reader, stamp): (Box<dyn Read>, _) = if file == "-" {
+        (Box::new(std::io::stdin()), None)
     } else {
-        for file in &regop.file {
-            handle_file(&regop, file)?;
+        let f = File::open(file).context(format!("unable to read file '{file}'"))?;
+        let stamp = f.metadata().ok().as_ref().and_then(stamp);
+        (Box::new(f), stamp)
+    };
+    let mut bytes = Vec::new();
+    reader
+        .take(max_filesize.map_or(u64::MAX, |max| max + 1))
+        .read_to_end(&mut bytes)
+        .context(format!("unable to read file '{file}'"))?;
+    if let Some(max) = max_filesize
+        && bytes.len() as u64 > max
+    {
+        log::warn!("Skipping '{file}', it is larger than {max} bytes");
+        return Ok(None);
+    }
+    let content = match String::from_utf8(bytes) {
+        Ok(content) if walked && content.contains('\0') => {
+            log::warn!("Skipping '{file}', it is a binary file");
+            return Ok(None);
         }
+        Err(_) if walked => {
+            log::warn!("Skipping '{file}', it is not valid UTF-8");
+            return Ok(None);
+        }
+        content => content.context(format!("'{file}' is not valid UTF-8"))?,
+    };
+    Ok(Some((content, stamp)))
+}
+
+/// Process a single file with the given regex patterns and operators.
+///
+/// Handles both regular files and stdin (when file is "-"). Returns the old
+/// content along with the new content if the file would change, or nothing if
+/// the file was skipped. Files the cache knows to be left unchanged are
+/// skipped, or not processed if only their content is known.
+fn process_file(
+    processor: &Processor,
+    max_filesize: Option<u64>,
+    file: &str,
+    walked: bool,
+    cache: Option<&cache::Cache>,
+) -> anyhow::Result<Option<FileChange>> {
+    if cache.is_some_and(|cache| cache.fresh(file)) {
+        log::info!("Skipping '{file}', it is unchanged since the last run");
+        return Ok(None);
+    }
+    let Some((old_content, stamp)) = read_file(file, walked, max_filesize)? else {
+        return Ok(None);
+    };
+    if cache.is_some_and(|cache| cache.known(file, &old_content)) {
+        return Ok(Some(FileChange {
+            old: old_content,
+            new: None,
+            stamp,
+            stats: Stats::default(),
+        }));
     }
 
+    let (new_content, stats) = processor.process_with_stats(&old_content)?;
+    Ok(Some(FileChange {
+        old: old_content,
+        new: new_content,
+        stamp,
+        stats,
+    }))
+}
+
+/// Process a file again, asking for each change whether to apply it.
+///
+/// Returns the new content with the accepted changes, if any. Sets `quit`
+/// when asked to stop, in which case the changes accepted so far are kept.
+fn confirm_edits(
+    regop: &Regop,
+    processor: &Processor,
+    file: &str,
+    old: &str,
+    quit: &mut bool,
+) -> anyhow::Result<Option<String>> {
+    let term = console::Term::stderr();
+    let mut all = false;
+    let mut error = None;
+    let new = processor.process_filtered(old, |content, edit| {
+        if all {
+            return true;
+        }
+        if *quit || error.is_some() {
+            return false;
+        }
+        let line = Position::of(content, edit.start).line;
+        let mut preview = content.to_string();
+        preview.replace_range(edit.start..edit.end, &edit.new);
+        diff::diff(
+            &format!("{file}:{line}"),
+            content,
+            &preview,
+            regop.diff_width(),
+        );
+        loop {
+            eprint!("Apply this change [y,n,a,q]? ");
+            let answer = term.read_char();
+            eprintln!();
+            match answer {
+                Ok('y') => return true,
+                Ok('n') => return false,
+                Ok('a') => {
+                    all = true;
+                    return true;
+                }
+                Ok('q') => {
+                    *quit = true;
+                    return false;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    error = Some(e);
+                    return false;
+                }
+            }
+        }
+    })?;
+    if let Some(e) = error {
+        return Err(e).context("unable to read answer from the terminal");
+    }
+    Ok(new)
+}
+
+/// Print the matches in the files, a line from the `--print` template for
+/// every match of the regular expressions, or every match of each named
+/// capture group as `file:line: <name> = value`.
+fn print_matches(
+    regop: &Regop,
+    processor: &Processor,
+    files: &[String],
+    walked: &HashSet<String>,
+) -> anyhow::Result<()> {
+    let (options, captures) = (processor.options(), processor.regex());
+    let placeholder = Regex::new(r"\{(\w+)\}")?;
+    if let Some(template) = &regop.print {
+        for name in placeholder
+            .captures_iter(template)
+            .map(|c| c[1].to_string())
+        {
+            ensure!(
+                ["file", "line", "0"].contains(&name.as_str())
+                    || captures.iter().any(|c| c.names.contains(&name)),
+                "'{{{name}}}' in the print template is not a capture group, `0`, `file` or `line`"
+            );
+        }
+    }
+
+    let mut failed = 0;
+    for file in files {
+        let result = read_file(file, walked.contains(file), regop.max_filesize).and_then(|read| {
+            let Some((content, _)) = read else {
+                return Ok(());
+            };
+            let Some(template) = &regop.print else {
+                for m in processor.captures(&content)? {
+                    let line = Position::of(&content, m.start).line;
+                    println!("{file}:{line}: <{}> = {}", m.name, m.value);
+                }
+                return Ok(());
+            };
+            for m in find_full_matches(options, captures, &content)? {
+                let line = Position::of(&content, m.start).line;
+                let rendered =
+                    placeholder.replace_all(template, |c: &regex::Captures| match &c[1] {
+                        "file" => file.clone(),
+                        "line" => line.to_string(),
+                        "0" => m.value.clone(),
+                        name => m.get(name).unwrap_or_default().to_string(),
+                    });
+                println!("{rendered}");
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            log::error!("{e:#}");
+            failed += 1;
+        }
+    }
+    ensure!(
+        failed == 0,
+        "failed to process {failed} of {} files",
+        files.len()
+    );
     Ok(())
 }
 
-/// Process a single file with the given regex patterns and operators.
+/// Long help of `--op`, listing every operation.
+fn operators_help() -> String {
+    let mut help = "Operator in the format `<target>:operation:parameter`, can be repeated\n\n\
+                    Operations:"
+        .to_string();
+    for info in Operation::all() {
+        let params = info
+            .params
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" or ");
+        let _ = write!(help, "\n  {:<8} {}", info.name, info.description);
+        let _ = match (info.arity, info.default) {
+            (Arity::None, _) => Ok(()),
+            (Arity::Optional, Some(default)) => write!(help, " ({params}, default {default})"),
+            (Arity::Optional, None) => write!(help, " (optional {params})"),
+            (Arity::Required, _) => write!(help, " ({params})"),
+        };
+    }
+    help
+}
+
+/// Parse a variable in the format `name=value`.
+fn parse_var(s: &str) -> anyhow::Result<(String, String)> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("'{s}' not a valid variable format, expected NAME=VALUE"))?;
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Parse a size in bytes with an optional `K`, `M` or `G` suffix, e.g. `10M`.
+fn parse_size(s: &str) -> anyhow::Result<u64> {
+    let upper = s.to_ascii_uppercase();
+    let size = upper.strip_suffix('B').unwrap_or(&upper);
+    let (number, shift) = match size.char_indices().last() {
+        Some((i, 'K')) => (&size[..i], 10),
+        Some((i, 'M')) => (&size[..i], 20),
+        Some((i, 'G')) => (&size[..i], 30),
+        _ => (size, 0),
+    };
+    let number = number
+        .parse::<u64>()
+        .context(format!("'{s}' not a valid size"))?;
+    number
+        .checked_mul(1 << shift)
+        .ok_or_else(|| anyhow!("'{s}' is too large"))
+}
+
+/// Parse a size limit of a regex, see [`parse_size`].
+fn parse_limit(s: &str) -> anyhow::Result<usize> {
+    usize::try_from(parse_size(s)?).map_err(|_| anyhow!("'{s}' is too large"))
+}
+
+/// Parse the width of diffs, which must be at least one column.
+fn parse_width(s: &str) -> anyhow::Result<usize> {
+    let width = s
+        .parse::<usize>()
+        .context(format!("'{s}' is not a valid width"))?;
+    ensure!(width > 0, "the width must be at least 1");
+    Ok(width)
+}
+
+/// Print the content of a file to stdout with a header, like `head` does for
+/// several files.
+fn print_file(file: &str, content: &str, first: bool) {
+    if !first {
+        println!();
+    }
+    println!("==> {file} <==");
+    print!("{content}");
+}
+
+/// Output the changes to a single file.
 ///
-/// Handles both regular files and stdin (when file is "-").
 /// In preview mode (default), shows a diff of changes.
 /// In write mode (-w flag), applies changes to the file.
-fn handle_file(regop: &Regop, file: &str) -> anyhow::Result<()> {
-    let old_content = match file {
-        "-" => {
-            let mut stdin = String::new();
-            std::io::stdin().read_to_string(&mut stdin)?;
-            stdin
+/// With an output directory or template, writes the file to its output path
+/// whether it changed or not. Files modified since they were read are not
+/// written unless forced.
+fn output_file(
+    regop: &Regop,
+    file: &str,
+    change: &FileChange,
+    output: &mut Output,
+) -> anyhow::Result<()> {
+    let (old, new) = (change.old.as_str(), change.new.as_deref());
+    if let Some(path) = regop.output_path(file)? {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("unable to create directory '{}'", parent.display()))?;
         }
-        _ => fs::read_to_string(file).context(format!("unable to read file '{file}'"))?,
+        fs::write(&path, new.unwrap_or(old))
+            .context(format!("unable to write file '{}'", path.display()))?;
+        return Ok(());
+    }
+    let Some(new) = new else {
+        return Ok(());
     };
-
     if !regop.write {
-        if let Some(new_content) =
-            process(regop.lines, &regop.regex, &regop.op, old_content.clone())?
-        {
-            diff::diff(file, &old_content, &new_content);
+        if regop.quiet {
+            return Ok(());
         }
-    } else if let Some(new_content) = process(regop.lines, &regop.regex, &regop.op, old_content)? {
-        match file {
-            "-" => print!("{new_content}"),
-            _ => fs::write(file, new_content).context(format!("unable to write file '{file}'"))?,
+        match regop.diff_format {
+            diff::Format::Preview => {
+                diff::diff(file, old, new, regop.diff_width());
+                let stat = diff::DiffStat::new(old, new);
+                println!("{stat}");
+                output.diffs += 1;
+                output.diffstat.add(stat);
+            }
+            diff::Format::Unified => print!("{}", diff::unified(file, old, new)),
         }
+        return Ok(());
+    }
+    if file == "-" {
+        print!("{new}");
+        return Ok(());
     }
+    if let Some(read) = change.stamp {
+        let current = fs::metadata(file).ok().as_ref().and_then(stamp);
+        ensure!(
+            regop.force || current == Some(read),
+            "'{file}' was modified while processing it, use --force to write it anyway"
+        );
+    }
+    if let Some(suffix) = &regop.backup {
+        let backup = format!("{file}{suffix}");
+        fs::copy(file, &backup).context(format!("unable to back up file to '{backup}'"))?;
+    }
+    regop::atomic_write(Path::new(file), new, regop.preserve_mtime)?;
+    Ok(())
+}
 
+/// Copy a file that was skipped without processing it to its output path, if
+/// it has one, so the output has every file. A file whose output path is the
+/// file itself is left as it is, copying it onto itself would truncate it.
+fn copy_skipped(regop: &Regop, file: &str) -> anyhow::Result<()> {
+    let Some(path) = regop.output_path(file)? else {
+        return Ok(());
+    };
+    if fs::canonicalize(&path).ok() == Some(fs::canonicalize(file)?) {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("unable to create directory '{}'", parent.display()))?;
+    }
+    fs::copy(file, &path).context(format!("unable to write file '{}'", path.display()))?;
     Ok(())
 }
+
+/// The path of a file mirrored under an output directory. Absolute paths are
+/// mirrored as if they were relative to the root.
+fn mirror_path(dir: &Path, file: &Path) -> anyhow::Result<PathBuf> {
+    let mut path = dir.to_path_buf();
+    for component in file.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::ParentDir => bail!(
+                "'{}' is outside of the current directory and can't be mirrored",
+                file.display()
+            ),
+            Component::RootDir | Component::Prefix(_) | Component::CurDir => {}
+        }
+    }
+    Ok(path)
+}
+
+/// Render an output path template for a file, replacing `{name}`, `{stem}`
+/// and `{ext}` with the file name, the file name without its extension and
+/// the extension including the dot.
+fn render_output(template: &str, file: &Path) -> String {
+    let part = |p: Option<&std::ffi::OsStr>| p.map(|p| p.to_string_lossy().to_string());
+    template
+        .replace("{name}", &part(file.file_name()).unwrap_or_default())
+        .replace("{stem}", &part(file.file_stem()).unwrap_or_default())
+        .replace(
+            "{ext}",
+            &part(file.extension())
+                .map(|ext| format!(".{ext}"))
+                .unwrap_or_default(),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_flags_replaces_lists() {
+        let table = r"
+            regex = ['a(?<v>\d)']
+            op = ['<v>:inc', '<v>:mul:2']
+            files = ['*.txt']
+        "
+        .parse::<toml::Table>()
+        .unwrap();
+        let args = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        let (merged, globs) = with_flags(args(&["regop"]), &table).unwrap();
+        assert_eq!(
+            merged,
+            args(&[
+                "regop",
+                "--op=<v>:inc",
+                "--op=<v>:mul:2",
+                r"--regex=a(?<v>\d)"
+            ])
+        );
+        assert_eq!(globs, ["*.txt"]);
+
+        let (merged, _) = with_flags(args(&["regop", "-o", "<v>:dec", "f.txt"]), &table).unwrap();
+        assert_eq!(
+            merged,
+            args(&["regop", r"--regex=a(?<v>\d)", "-o", "<v>:dec", "f.txt"])
+        );
+        assert_eq!(Regop::parse_from(&merged).op.len(), 1);
+
+        let (merged, _) = with_flags(args(&["regop", "--regex=b(?<v>\\d)"]), &table).unwrap();
+        let regop = Regop::parse_from(&merged);
+        assert_eq!(regop.regex, [r"b(?<v>\d)"]);
+        assert_eq!(regop.op.len(), 2);
+    }
+
+    #[test]
+    fn test_with_flags_values_starting_with_dash() {
+        let table = r"
+            regex = ['-(?<n>\d+)']
+            op = '<n>:inc'
+            stats = true
+            check = false
+        "
+        .parse::<toml::Table>()
+        .unwrap();
+
+        let (merged, _) = with_flags(vec!["regop".to_string()], &table).unwrap();
+        let regop = Regop::try_parse_from(&merged).unwrap();
+        assert_eq!(regop.regex, [r"-(?<n>\d+)"]);
+        assert!(regop.stats);
+        assert!(!regop.check);
+    }
+
+    #[test]
+    fn test_check_expectations() {
+        let files = ["a.txt".to_string(), "b.txt".to_string()];
+        let unmet = || {
+            "<v>=1"
+                .parse::<Expectation>()
+                .unwrap()
+                .check(2)
+                .unwrap_err()
+        };
+
+        let changes = vec![Ok(None), Err(unmet().into())];
+        assert!(check_expectations(&files, changes.into_iter()).is_err());
+
+        let changes = vec![Ok(None), Err(anyhow!("unreadable"))];
+        let changes = check_expectations(&files, changes.into_iter()).unwrap();
+        assert!(changes[1].is_err());
+    }
+
+    #[test]
+    fn test_read_file_skips_walked_binary_files() {
+        let dir = std::env::temp_dir().join("regop-read-file");
+        fs::create_dir_all(&dir).unwrap();
+        let invalid = dir.join("invalid.bin");
+        fs::write(&invalid, b"v = \xff\n").unwrap();
+        let binary = dir.join("nul.bin");
+        fs::write(&binary, b"v = 1\0\n").unwrap();
+        let (invalid, binary) = (invalid.to_str().unwrap(), binary.to_str().unwrap());
+
+        assert!(read_file(invalid, true, None).unwrap().is_none());
+        assert!(read_file(invalid, false, None).is_err());
+        assert!(read_file(binary, true, None).unwrap().is_none());
+        assert!(read_file(binary, false, None).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_render_output() {
+        let file = Path::new("dir/config.toml");
+        assert_eq!(render_output("{name}", file), "config.toml");
+        assert_eq!(render_output("{stem}.new{ext}", file), "config.new.toml");
+        assert_eq!(
+            render_output("{stem}{ext}", Path::new("Makefile")),
+            "Makefile"
+        );
+    }
+
+    #[test]
+    fn test_mirror_path() {
+        let dir = Path::new("out");
+        assert_eq!(
+            mirror_path(dir, Path::new("./a/b.txt")).unwrap(),
+            Path::new("out/a/b.txt")
+        );
+        assert_eq!(
+            mirror_path(dir, Path::new("/abs/b.txt")).unwrap(),
+            Path::new("out/abs/b.txt")
+        );
+        assert!(mirror_path(dir, Path::new("../b.txt")).is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("100").unwrap(), 100);
+        assert_eq!(parse_size("2k").unwrap(), 2048);
+        assert_eq!(parse_size("1MB").unwrap(), 1 << 20);
+        assert_eq!(parse_size("3G").unwrap(), 3 << 30);
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("1T").is_err());
+        assert!(parse_size(&format!("{}G", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_file_list() {
+        assert_eq!(file_list("a.txt\n\nb c.txt\n", false), ["a.txt", "b c.txt"]);
+        assert_eq!(file_list("a\nb.txt\0c.txt\0", true), ["a\nb.txt", "c.txt"]);
+    }
+
+    #[test]
+    fn test_dedup_files() {
+        let dir = std::env::temp_dir().join("regop-dedup-files");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "").unwrap();
+        let file = dir.join("a.txt").to_str().unwrap().to_string();
+        let other = dir.join("../regop-dedup-files/a.txt");
+        let other = other.to_str().unwrap().to_string();
+
+        let files = vec![file.clone(), other, "missing".into(), "missing".into()];
+        assert_eq!(dedup_files(files), [file, "missing".to_string()]);
+    }
+
+    #[test]
+    fn test_copy_skipped_onto_itself() {
+        let dir = std::env::temp_dir().join("regop-copy-skipped");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("k.txt");
+        fs::write(&file, "v = 1\n").unwrap();
+        let file = file.to_str().unwrap();
+
+        copy_skipped(&Regop::parse_from(["regop", "-O", "{name}", file]), file).unwrap();
+        assert_eq!(fs::read_to_string(file).unwrap(), "v = 1\n");
+
+        copy_skipped(
+            &Regop::parse_from(["regop", "-O", "{stem}.new", file]),
+            file,
+        )
+        .unwrap();
+        assert_eq!(fs::read_to_string(dir.join("k.new")).unwrap(), "v = 1\n");
+    }
+}
@@ -0,0 +1,363 @@
+//! Reports of the files that would change, for CI.
+//!
+//! In test reports every file is a test case that fails if the file would
+//! change, so CI systems can show which files aren't in the expected state.
+//! SARIF reports have a result for every changed region of a file, so code
+//! scanning can show them inline.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{Context, anyhow, bail};
+use serde_json::json;
+use similar::TextDiff;
+
+use crate::diff;
+
+/// Format of a report.
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    /// `JUnit` XML
+    Junit,
+    /// Test Anything Protocol
+    Tap,
+    /// Static Analysis Results Interchange Format
+    Sarif,
+}
+
+/// A report to write, parsed from `<format>=<path>`.
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// Format of the report
+    pub format: Format,
+    /// Where to write the report
+    pub path: PathBuf,
+}
+
+impl FromStr for Report {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (format, path) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!(format!("'{s}' not a valid report, use <format>=<path>")))?;
+        let format = match format {
+            "junit" => Format::Junit,
+            "tap" => Format::Tap,
+            "sarif" => Format::Sarif,
+            _ => bail!(format!(
+                "'{format}' not a valid report format, use 'junit', 'tap' or 'sarif'"
+            )),
+        };
+        Ok(Self {
+            format,
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+/// What happened to a file.
+pub enum Outcome {
+    /// The file is already in the expected state
+    Unchanged,
+    /// The file would change
+    Changed {
+        /// Current content
+        old: String,
+        /// New content
+        new: String,
+    },
+    /// The file was skipped
+    Skipped,
+    /// The file couldn't be processed, with the error
+    Failed(String),
+}
+
+impl Report {
+    /// Write the report for the outcome of each file.
+    pub fn write(&self, outcomes: &[(String, Outcome)]) -> anyhow::Result<()> {
+        let report = match self.format {
+            Format::Junit => junit(outcomes),
+            Format::Tap => tap(outcomes),
+            Format::Sarif => sarif(outcomes)?,
+        };
+        fs::write(&self.path, report)
+            .context(format!("unable to write report '{}'", self.path.display()))
+    }
+}
+
+/// Render a `JUnit` XML report.
+fn junit(outcomes: &[(String, Outcome)]) -> String {
+    let count = |f: fn(&Outcome) -> bool| outcomes.iter().filter(|(_, o)| f(o)).count();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuite name=\"regop\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\">",
+        outcomes.len(),
+        count(|o| matches!(o, Outcome::Changed { .. })),
+        count(|o| matches!(o, Outcome::Failed(_))),
+        count(|o| matches!(o, Outcome::Skipped)),
+    );
+    for (file, outcome) in outcomes {
+        let name = escape(file);
+        match outcome {
+            Outcome::Unchanged => {
+                let _ = writeln!(xml, "  <testcase name=\"{name}\" classname=\"regop\"/>");
+            }
+            Outcome::Changed { old, new } => {
+                let _ = writeln!(
+                    xml,
+                    "  <testcase name=\"{name}\" classname=\"regop\">\n    \
+                     <failure message=\"file would change\">{}</failure>\n  </testcase>",
+                    escape(&diff::unified(file, old, new))
+                );
+            }
+            Outcome::Skipped => {
+                let _ = writeln!(
+                    xml,
+                    "  <testcase name=\"{name}\" classname=\"regop\">\n    \
+                     <skipped/>\n  </testcase>"
+                );
+            }
+            Outcome::Failed(error) => {
+                let _ = writeln!(
+                    xml,
+                    "  <testcase name=\"{name}\" classname=\"regop\">\n    \
+                     <error message=\"{}\"/>\n  </testcase>",
+                    escape(error)
+                );
+            }
+        }
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+/// Render a TAP report, with diffs and errors as YAML diagnostics.
+fn tap(outcomes: &[(String, Outcome)]) -> String {
+    let mut tap = format!("TAP version 13\n1..{}\n", outcomes.len());
+    for (i, (file, outcome)) in outcomes.iter().enumerate() {
+        let n = i + 1;
+        let (key, text) = match outcome {
+            Outcome::Unchanged => {
+                let _ = writeln!(tap, "ok {n} - {file}");
+                continue;
+            }
+            Outcome::Skipped => {
+                let _ = writeln!(tap, "ok {n} - {file} # SKIP");
+                continue;
+            }
+            Outcome::Changed { old, new } => ("diff", diff::unified(file, old, new)),
+            Outcome::Failed(error) => ("error", error.clone()),
+        };
+        let _ = writeln!(tap, "not ok {n} - {file}\n  ---\n  {key}: |");
+        for line in text.lines() {
+            let _ = writeln!(tap, "    {line}");
+        }
+        tap.push_str("  ...\n");
+    }
+    tap
+}
+
+/// Render a SARIF report, with a result for every changed region of a file
+/// and every file that couldn't be processed.
+fn sarif(outcomes: &[(String, Outcome)]) -> anyhow::Result<String> {
+    let mut results = Vec::new();
+    for (file, outcome) in outcomes {
+        let uri = file.strip_prefix("./").unwrap_or(file);
+        match outcome {
+            Outcome::Changed { old, new } => {
+                for (start, end, replacement) in regions(old, new) {
+                    results.push(json!({
+                        "ruleId": "would-change",
+                        "level": "error",
+                        "message": {
+                            "text": format!("regop would change this to:\n{replacement}"),
+                        },
+                        "locations": [{
+                            "physicalLocation": {
+                                "artifactLocation": { "uri": uri },
+                                "region": { "startLine": start, "endLine": end },
+                            },
+                        }],
+                    }));
+                }
+            }
+            Outcome::Failed(error) => results.push(json!({
+                "ruleId": "error",
+                "level": "error",
+                "message": { "text": error },
+                "locations": [{
+                    "physicalLocation": { "artifactLocation": { "uri": uri } },
+                }],
+            })),
+            Outcome::Unchanged | Outcome::Skipped => {}
+        }
+    }
+    let report = json!({
+        "version": "2.1.0",
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "regop",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": [
+                        {
+                            "id": "would-change",
+                            "shortDescription": { "text": "File would change" },
+                        },
+                        {
+                            "id": "error",
+                            "shortDescription": { "text": "File couldn't be processed" },
+                        },
+                    ],
+                },
+            },
+            "results": results,
+        }],
+    });
+    Ok(serde_json::to_string_pretty(&report)? + "\n")
+}
+
+/// Changed regions between old and new content, as the first and last line
+/// of each region in the old content along with the new text of the region.
+fn regions(old: &str, new: &str) -> Vec<(usize, usize, String)> {
+    let diff = TextDiff::from_lines(old, new);
+    diff.grouped_ops(0)
+        .iter()
+        .filter_map(|group| {
+            let (first, last) = (group.first()?, group.last()?);
+            let old_range = first.old_range().start..last.old_range().end;
+            let new_range = first.new_range().start..last.new_range().end;
+            let replacement = new_range
+                .filter_map(|i| diff.new_slice(i))
+                .collect::<String>();
+            let start = old_range.start + 1;
+            Some((start, old_range.end.max(start), replacement))
+        })
+        .collect()
+}
+
+/// Escape text for XML.
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write a report of the outcomes and read it back.
+    fn write(format: &str, outcomes: &[(String, Outcome)]) -> String {
+        let path = std::env::temp_dir().join(format!("regop-report.{format}"));
+        let report = format!("{format}={}", path.display())
+            .parse::<Report>()
+            .unwrap();
+        report.write(outcomes).unwrap();
+        fs::read_to_string(path).unwrap()
+    }
+
+    fn outcomes() -> Vec<(String, Outcome)> {
+        vec![
+            ("a.txt".to_string(), Outcome::Unchanged),
+            (
+                "./b<&>.txt".to_string(),
+                Outcome::Changed {
+                    old: "v = \"1\"\nx\n".to_string(),
+                    new: "v = \"2\"\nx\n".to_string(),
+                },
+            ),
+            ("c.txt".to_string(), Outcome::Skipped),
+            (
+                "d.txt".to_string(),
+                Outcome::Failed("'<d>' can't be \"read\"".to_string()),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_junit() {
+        let xml = write("junit", &outcomes());
+        assert!(xml.contains(
+            "<testsuite name=\"regop\" tests=\"4\" failures=\"1\" errors=\"1\" skipped=\"1\">"
+        ));
+        assert!(xml.contains("<testcase name=\"a.txt\" classname=\"regop\"/>"));
+        assert!(xml.contains("<testcase name=\"./b&lt;&amp;&gt;.txt\" classname=\"regop\">"));
+        assert!(xml.contains("-v = &quot;1&quot;\n+v = &quot;2&quot;\n"));
+        assert!(xml.contains("<skipped/>"));
+        assert!(
+            xml.contains(
+                "<error message=\"&apos;&lt;d&gt;&apos; can&apos;t be &quot;read&quot;\"/>"
+            )
+        );
+        assert!(xml.ends_with("</testsuite>\n"));
+    }
+
+    #[test]
+    fn test_tap() {
+        let tap = write("tap", &outcomes());
+        let expected = "\
+TAP version 13
+1..4
+ok 1 - a.txt
+not ok 2 - ./b<&>.txt
+  ---
+  diff: |
+    --- a/b<&>.txt
+    +++ b/b<&>.txt
+    @@ -1,2 +1,2 @@
+    -v = \"1\"
+    +v = \"2\"
+     x
+  ...
+ok 3 - c.txt # SKIP
+not ok 4 - d.txt
+  ---
+  error: |
+    '<d>' can't be \"read\"
+  ...
+";
+        assert_eq!(tap, expected);
+    }
+
+    #[test]
+    fn test_sarif() {
+        let sarif = write("sarif", &outcomes());
+        let sarif = serde_json::from_str::<serde_json::Value>(&sarif).unwrap();
+        assert_eq!(sarif["version"], "2.1.0");
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "regop");
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+
+        assert_eq!(results[0]["ruleId"], "would-change");
+        assert_eq!(
+            results[0]["message"]["text"],
+            "regop would change this to:\nv = \"2\"\n"
+        );
+        let location = &results[0]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "b<&>.txt");
+        assert_eq!(location["region"]["startLine"], 1);
+        assert_eq!(location["region"]["endLine"], 1);
+
+        assert_eq!(results[1]["ruleId"], "error");
+        assert_eq!(results[1]["message"]["text"], "'<d>' can't be \"read\"");
+    }
+
+    #[test]
+    fn test_regions() {
+        assert_eq!(
+            regions("a\nb\nc\nd\n", "a\nB\nc\n"),
+            [(2, 2, "B\n".to_string()), (4, 4, String::new())]
+        );
+        assert_eq!(regions("a\n", "a\nb\n"), [(2, 2, "b\n".to_string())]);
+    }
+}
@@ -0,0 +1,62 @@
+//! Directory traversal for finding files to operate on.
+
+use anyhow::Context;
+use ignore::WalkBuilder;
+
+/// Recursively find all files in a directory, sorted by path.
+///
+/// Hidden files and directories are skipped. Unless `no_ignore` is set,
+/// files ignored by `.gitignore`, `.ignore` and git exclude files are skipped
+/// as well. Paths below the current directory are relative to it.
+pub fn walk(dir: &str, no_ignore: bool) -> anyhow::Result<Vec<String>> {
+    let mut files = Vec::new();
+    let walker = WalkBuilder::new(dir)
+        .standard_filters(!no_ignore)
+        .hidden(true)
+        .sort_by_file_path(Ord::cmp)
+        .build();
+    for entry in walker {
+        let entry = entry.context(format!("unable to walk directory '{dir}'"))?;
+        if entry.file_type().is_some_and(|t| t.is_file()) {
+            let path = entry.path();
+            let path = path.strip_prefix("./").unwrap_or(path);
+            files.push(path.to_string_lossy().to_string());
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_walk() {
+        let dir = std::env::temp_dir().join("regop-walk");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::create_dir_all(dir.join(".hidden")).unwrap();
+        for file in [
+            "b.txt",
+            "a.txt",
+            "sub/c.txt",
+            ".hidden/d.txt",
+            ".e.txt",
+            "f.log",
+        ] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        fs::write(dir.join(".ignore"), "*.log\n").unwrap();
+        let dir = dir.to_str().unwrap();
+
+        let files = walk(dir, false).unwrap();
+        let expected = ["a.txt", "b.txt", "sub/c.txt"].map(|f| format!("{dir}/{f}"));
+        assert_eq!(files, expected);
+
+        let files = walk(dir, true).unwrap();
+        let expected = ["a.txt", "b.txt", "f.log", "sub/c.txt"].map(|f| format!("{dir}/{f}"));
+        assert_eq!(files, expected);
+    }
+}
@@ -0,0 +1,80 @@
+//! Bindings for JavaScript through `wasm-bindgen`, enabled with the `wasm`
+//! feature.
+//!
+//! ```js
+//! import { Capture, Operator, process } from "regop";
+//!
+//! new Capture("(?<num>\\d+)").names; // ["num"]
+//! process(["(?<num>\\d+)"], ["<num>:inc"], "v1", false); // "v2"
+//! ```
+
+use std::str::FromStr;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{Capture, Operator};
+
+/// A compiled regex, see [`Capture`].
+#[wasm_bindgen(js_name = Capture)]
+pub struct JsCapture(Capture);
+
+#[wasm_bindgen(js_class = Capture)]
+impl JsCapture {
+    /// Compile a regex, throwing if it is not valid.
+    #[wasm_bindgen(constructor)]
+    pub fn new(pattern: &str) -> Result<Self, JsError> {
+        Ok(Self(Capture::from_str(pattern)?))
+    }
+
+    /// Names of all capture groups in the regex, sorted.
+    #[must_use]
+    #[wasm_bindgen(getter)]
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.0.names.iter().cloned().collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+/// A parsed operator, see [`Operator`].
+#[wasm_bindgen(js_name = Operator)]
+pub struct JsOperator(Operator);
+
+#[wasm_bindgen(js_class = Operator)]
+impl JsOperator {
+    /// Parse an operator like `<num>:inc`, throwing if it is not valid.
+    #[wasm_bindgen(constructor)]
+    pub fn new(operator: &str) -> Result<Self, JsError> {
+        Ok(Self(Operator::from_str(operator)?))
+    }
+
+    /// Name of the capture group the operator targets.
+    #[must_use]
+    #[wasm_bindgen(getter)]
+    pub fn target(&self) -> String {
+        self.0.target.clone()
+    }
+}
+
+/// Process content with captures and operators given as strings, see
+/// [`process`](crate::process).
+///
+/// Returns the new content, or `undefined` if nothing matched. Throws if a
+/// capture or operator is not valid or processing fails.
+#[wasm_bindgen(js_name = process)]
+pub fn js_process(
+    captures: Vec<String>,
+    operators: Vec<String>,
+    content: String,
+    lines: bool,
+) -> Result<Option<String>, JsError> {
+    let captures = captures
+        .into_iter()
+        .map(|c| JsCapture::new(&c).map(|c| c.0))
+        .collect::<Result<Vec<_>, _>>()?;
+    let operators = operators
+        .into_iter()
+        .map(|o| JsOperator::new(&o).map(|o| o.0))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(crate::process(lines, &captures, &operators, content)?)
+}
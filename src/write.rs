@@ -0,0 +1,202 @@
+//! Crash-safe writing of files.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{Error, Result};
+
+/// Atomically replace the content of a file.
+///
+/// The content is written to a temporary file in the same directory, which
+/// is then renamed over the original, so an interrupted write never leaves a
+/// half-written file behind. The permissions and, where possible, ownership
+/// of the original are preserved, as is the modification time if
+/// `preserve_mtime` is set. Symlinks are followed.
+pub fn atomic_write(path: &Path, content: &str, preserve_mtime: bool) -> Result<()> {
+    let error = |source| Error::Write {
+        path: path.to_path_buf(),
+        source,
+    };
+    let path = fs::canonicalize(path).map_err(error)?;
+    let tmp = tmp_path(&path).map_err(error)?;
+
+    let result =
+        write_tmp(&path, &tmp, content, preserve_mtime).and_then(|()| fs::rename(&tmp, &path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result.map_err(error)
+}
+
+/// Atomically replace the content of a file with `tokio::fs`, see
+/// [`atomic_write`].
+#[cfg(feature = "tokio")]
+pub async fn atomic_write_async(path: &Path, content: &str, preserve_mtime: bool) -> Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let error = |source| Error::Write {
+        path: path.to_path_buf(),
+        source,
+    };
+    let path = tokio::fs::canonicalize(path).await.map_err(error)?;
+    let tmp = tmp_path(&path).map_err(error)?;
+
+    let write_tmp = async {
+        let metadata = tokio::fs::metadata(&path).await?;
+        let mut file = tokio::fs::OpenOptions::from(tmp_options(&metadata))
+            .open(&tmp)
+            .await?;
+        file.write_all(content.as_bytes()).await?;
+        let file = file.into_std().await;
+        copy_metadata(&file, &tmp, &metadata, preserve_mtime)?;
+        tokio::fs::File::from_std(file).sync_all().await?;
+        tokio::fs::rename(&tmp, &path).await
+    };
+    let result = write_tmp.await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&tmp).await;
+    }
+    result.map_err(error)
+}
+
+/// Path of the temporary file written next to a file before replacing it.
+fn tmp_path(path: &Path) -> io::Result<PathBuf> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "not a file in a directory",
+        ));
+    };
+    Ok(dir.join(format!(
+        ".{}.regop-{}.tmp",
+        name.to_string_lossy(),
+        std::process::id()
+    )))
+}
+
+/// Write content to a new temporary file with the metadata of the original.
+fn write_tmp(path: &Path, tmp: &Path, content: &str, preserve_mtime: bool) -> io::Result<()> {
+    let metadata = fs::metadata(path)?;
+    let mut file = tmp_options(&metadata).open(tmp)?;
+    file.write_all(content.as_bytes())?;
+    copy_metadata(&file, tmp, &metadata, preserve_mtime)?;
+    file.sync_all()
+}
+
+/// Options to create a temporary file with. On Unix it is created with the
+/// mode of the original, so the new content is never readable by users that
+/// can't read the original, not even before its permissions are copied.
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn tmp_options(metadata: &fs::Metadata) -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(metadata.permissions().mode());
+    }
+    options
+}
+
+/// Give a temporary file the permissions, ownership and, if
+/// `preserve_mtime` is set, modification time of the original.
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn copy_metadata(
+    file: &fs::File,
+    tmp: &Path,
+    metadata: &fs::Metadata,
+    preserve_mtime: bool,
+) -> io::Result<()> {
+    if preserve_mtime {
+        file.set_modified(metadata.modified()?)?;
+    }
+    file.set_permissions(metadata.permissions())?;
+    #[cfg(unix)]
+    chown(tmp, metadata);
+    Ok(())
+}
+
+/// Give a file the owner of the original. Only privileged users can give
+/// files away, so this is best effort.
+#[cfg(unix)]
+fn chown(tmp: &Path, metadata: &fs::Metadata) {
+    use std::os::unix::fs::MetadataExt;
+    let _ = std::os::unix::fs::chown(tmp, Some(metadata.uid()), Some(metadata.gid()));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+
+    /// A file in a fresh directory of its own.
+    fn file(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("regop-write-{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, "old").unwrap();
+        path
+    }
+
+    #[test]
+    fn test_atomic_write() {
+        let path = file("content.txt");
+        atomic_write(&path, "new", false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        let entries = fs::read_dir(path.parent().unwrap()).unwrap().count();
+        assert_eq!(entries, 1, "the temporary file is left behind");
+    }
+
+    #[test]
+    fn test_atomic_write_missing_file() {
+        let path = file("missing.txt").with_file_name("other.txt");
+        assert!(matches!(
+            atomic_write(&path, "new", false),
+            Err(Error::Write { .. })
+        ));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_atomic_write_preserves_mtime() {
+        let path = file("mtime.txt");
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        atomic_write(&path, "new", true).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), mtime);
+        atomic_write(&path, "newer", false).unwrap();
+        assert_ne!(fs::metadata(&path).unwrap().modified().unwrap(), mtime);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_atomic_write_preserves_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = file("mode.sh");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+        atomic_write(&path, "new", false).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o750);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_atomic_write_follows_symlinks() {
+        let path = file("target.txt");
+        let link = path.with_file_name("link.txt");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        atomic_write(&link, "new", false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+    }
+}